@@ -1,27 +1,157 @@
-use crate::http::HttpClient;
+use crate::archive::ArchiveEntry;
+use crate::changes::{self, Change};
+use crate::config::Opt;
+use crate::error::{ApiError, ErrorCode};
+use crate::http::{check_login, HttpClient};
+use crate::keywords::{top_keywords, Keyword};
+use crate::metrics;
+use crate::palette;
+use crate::scrape::{parse_episode_file, EpisodeFile};
+use crate::trending;
+use crate::util::{normalize_date, now_secs, parse_date, today, xml_escape, CacheMeta, Page};
 use anyhow::Result;
+use futures::StreamExt;
 use lazy_static::*;
 use moka::future::{Cache, CacheBuilder};
 use regex::Regex;
 use serde::{Deserialize, Serialize};
-use std::sync::Arc;
-use std::time::Duration;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::future::Future;
+use std::hash::{Hash, Hasher};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::time::Instant;
 use visdom::Vis;
 
 lazy_static! {
     static ref MOVIE_CACHE: Cache<String, MovieInfo> = CacheBuilder::new(CACHE_SIZE)
+        .time_to_live(MOVIE_CACHE_TTL)
+        .build();
+    // 电影详情的抓取时间
+    static ref MOVIE_FETCHED_AT: Cache<String, u64> = CacheBuilder::new(CACHE_SIZE)
+        .time_to_live(MOVIE_CACHE_TTL)
+        .build();
+    // 搜索结果缓存完整列表，不同count/筛选条件共用，key 为 {小写搜索词}_{image_size}
+    static ref SEARCH_CACHE: Cache<String, (Vec<Movie>, SearchSource)> = CacheBuilder::new(CACHE_SIZE)
         .time_to_live(Duration::from_secs(10 * 60))
         .build();
+    // 搜索建议，用于自动补全，key 为小写搜索词
+    static ref SUGGEST_CACHE: Cache<String, Vec<Suggestion>> = CacheBuilder::new(SUGGEST_CACHE_SIZE)
+        .time_to_live(Duration::from_secs(60))
+        .build();
     static ref PHOTO_CACHE: Cache<String, Vec<Photo>> = CacheBuilder::new(CACHE_SIZE)
         .time_to_live(Duration::from_secs(10 * 60))
         .build();
+    static ref CELEBRITY_CACHE: Cache<String, CelebrityInfo> = CacheBuilder::new(CACHE_SIZE)
+        .time_to_live(Duration::from_secs(10 * 60))
+        .build();
+    static ref QUOTE_CACHE: Cache<String, Vec<Quote>> = CacheBuilder::new(CACHE_SIZE)
+        .time_to_live(Duration::from_secs(10 * 60))
+        .build();
+    static ref COMMENT_KEYWORD_CACHE: Cache<String, CommentKeywords> = CacheBuilder::new(CACHE_SIZE)
+        .time_to_live(Duration::from_secs(60 * 60))
+        .build();
+    static ref EPISODES_CACHE: Cache<String, Episodes> = CacheBuilder::new(CACHE_SIZE)
+        .time_to_live(Duration::from_secs(60 * 60))
+        .build();
+    static ref TIMELINE_CACHE: Cache<String, CelebrityTimeline> = CacheBuilder::new(CACHE_SIZE)
+        .time_to_live(Duration::from_secs(60 * 60))
+        .build();
+    // 封面主色调，key 为封面地址
+    static ref PALETTE_CACHE: Cache<String, Vec<String>> = CacheBuilder::new(CACHE_SIZE)
+        .time_to_live(Duration::from_secs(24 * 60 * 60))
+        .build();
+    // OMDb 的 IMDb 评分，key 为 IMDb 编号
+    static ref IMDB_RATING_CACHE: Cache<String, ImdbRating> = CacheBuilder::new(CACHE_SIZE)
+        .time_to_live(Duration::from_secs(24 * 60 * 60))
+        .build();
+    static ref PARENTAL_GUIDE_CACHE: Cache<String, ParentalGuide> = CacheBuilder::new(CACHE_SIZE)
+        .time_to_live(Duration::from_secs(24 * 60 * 60))
+        .build();
+    static ref DAILY_CACHE: Cache<String, Daily> = CacheBuilder::new(CACHE_SIZE)
+        .time_to_live(Duration::from_secs(60 * 60))
+        .build();
+    static ref BOX_OFFICE_CACHE: Cache<String, BoxOffice> = CacheBuilder::new(1)
+        .time_to_live(Duration::from_secs(60 * 60))
+        .build();
+    // 最近两期票房榜，用于计算排名变化
+    static ref BOX_OFFICE_HISTORY: RwLock<Vec<BoxOffice>> = RwLock::new(Vec::new());
+    // 负缓存，记录确定无结果的搜索词与不存在的sid，期间不再请求豆瓣
+    static ref NEGATIVE_CACHE: Cache<String, ()> = CacheBuilder::new(NEGATIVE_CACHE_SIZE)
+        .time_to_live(Duration::from_secs(5 * 60))
+        .build();
+    // 本地索引，保存搜索/详情获取过的条目，作为搜索的最后一级降级数据源
+    static ref LOCAL_INDEX: RwLock<HashMap<String, Movie>> = RwLock::new(HashMap::new());
+    // 上映日期索引，key 为 sid，value 为 YYYY-MM-DD 格式的上映日期
+    static ref RELEASE_INDEX: RwLock<HashMap<String, Vec<String>>> = RwLock::new(HashMap::new());
+    // 译名索引，key 为小写译名，value 为 sid
+    static ref ALIAS_INDEX: RwLock<HashMap<String, String>> = RwLock::new(HashMap::new());
+    // IMDb编号索引，key 为 IMDb 编号，value 为 sid
+    static ref IMDB_INDEX: RwLock<HashMap<String, String>> = RwLock::new(HashMap::new());
+    // 影人生日索引，保存获取过详情的影人，key为影人id
+    static ref BIRTHDAY_INDEX: RwLock<HashMap<String, CelebrityBirthday>> = RwLock::new(HashMap::new());
+    // 条目状态订阅，key 为订阅 id
+    static ref SUBSCRIPTIONS: RwLock<HashMap<String, Subscription>> = RwLock::new(HashMap::new());
+    static ref NEW_RELEASES: RwLock<NewReleases> = RwLock::new(NewReleases {
+        updated_at: 0,
+        movies: Vec::new(),
+    });
 }
 
 const CACHE_SIZE: usize = 100;
+const BATCH_CONCURRENCY: usize = 4;
+const EXPAND_CELEBRITY_SIZE: usize = 5;
+const ALIAS_SEARCH_LIMIT: i32 = 3;
+const LOCAL_INDEX_SIZE: usize = 5000;
+const DEFAULT_SEARCH_TIMEOUT: u64 = 30;
+const MAX_SUBSCRIPTIONS: usize = 1000;
+const NEGATIVE_CACHE_SIZE: usize = 10000;
+const SUGGEST_CACHE_SIZE: usize = 1000;
+const MOVIE_CACHE_TTL: Duration = Duration::from_secs(10 * 60);
+const SCRAPE_CANDIDATE_SIZE: usize = 3;
+const WIDGET_INTRO_SIZE: usize = 120;
+const EXPLORE_PAGE_SIZE: u32 = 20;
+const COMMENT_PAGE_SIZE: usize = 20;
+const PALETTE_SIZE: usize = 5;
+const OMDB_URL: &str = "https://www.omdbapi.com/";
+const OMDB_TIMEOUT: Duration = Duration::from_secs(5);
+const PHOTO_PAGE_SIZE: usize = 30;
+const TIMELINE_PAGE_SIZE: usize = 10;
+const TIMELINE_MAX_PAGES: usize = 20;
+// 抓取分集页的最大集数
+const EPISODE_DETAIL_LIMIT: u32 = 200;
+// 导出缓存时尝试的图片尺寸，详情缓存的 key 包含 image_size
+const CACHE_IMAGE_SIZES: [&str; 4] = ["", "s", "m", "l"];
+// 演员列表可筛选的职责，(参数名, 演职员页的职责)
+const CELEBRITY_ROLES: [(&str, &str); 8] = [
+    ("director", "导演"),
+    ("actor", "演员"),
+    ("voice", "配音"),
+    ("writer", "编剧"),
+    ("producer", "制片"),
+    ("composer", "音乐"),
+    ("cinematographer", "摄影"),
+    ("editor", "剪辑"),
+];
+pub const DEFAULT_CELEBRITY_ROLES: &str = "director,actor,voice";
+pub const DEFAULT_CELEBRITY_COUNT: usize = 15;
+// IMDb 家长指导的分类，(页面 section id, 分类名)
+const PARENTAL_GUIDE_CATEGORIES: [(&str, &str); 5] = [
+    ("nudity", "sex_nudity"),
+    ("violence", "violence_gore"),
+    ("profanity", "profanity"),
+    ("alcohol", "alcohol_drugs_smoking"),
+    ("frightening", "frightening_intense"),
+];
 
 #[derive(Clone)]
 pub struct Douban {
     client: Arc<HttpClient>,
+    search_strategy: Vec<(SearchSource, Duration)>,
+    noise_keywords: Vec<String>,
+    concurrency: usize,
+    omdb_api_key: String,
     re_id: Regex,
     re_backgroud_image: Regex,
     re_sid: Regex,
@@ -35,15 +165,35 @@ pub struct Douban {
     re_language: Regex,
     re_duration: Regex,
     re_screen: Regex,
+    re_company: Regex,
     re_subname: Regex,
     re_imdb: Regex,
     re_site: Regex,
     re_name_math: Regex,
     re_role: Regex,
+    re_season: Regex,
+    re_content_rating: Regex,
+    re_votes: Regex,
+    re_collection_year: Regex,
+    re_date: Regex,
+    re_release_date: Regex,
+    re_img_host: Regex,
+    re_img_size: Regex,
+    re_episode_count: Regex,
+    re_episode_duration: Regex,
+    re_wish: Regex,
+    re_doing: Regex,
 }
 
 impl Douban {
-    pub fn new(client: Arc<HttpClient>) -> Douban {
+    pub fn new(client: Arc<HttpClient>, config: &Opt) -> Douban {
+        let search_strategy = parse_search_strategy(&config.search_strategy);
+        let noise_keywords = config
+            .noise_keywords
+            .split(',')
+            .map(|x| x.trim().to_string())
+            .filter(|x| !x.is_empty())
+            .collect();
         let re_id = Regex::new(r"/(\d+?)/").unwrap();
         let re_backgroud_image = Regex::new(r"url\((.+?)\)").unwrap();
         let re_sid = Regex::new(r"sid: (\d+?),").unwrap();
@@ -57,13 +207,32 @@ impl Douban {
         let re_language = Regex::new(r"语言: (.+?)\n").unwrap();
         let re_duration = Regex::new(r"片长: (.+?)\n").unwrap();
         let re_screen = Regex::new(r"上映日期: (.+?)\n").unwrap();
+        let re_company =
+            Regex::new(r"(?:出品公司|制片公司|制作公司|发行公司|出品方|发行方): (.+?)\n").unwrap();
         let re_subname = Regex::new(r"又名: (.+?)\n").unwrap();
         let re_imdb = Regex::new(r"IMDb: (.+?)\n").unwrap();
         let re_site = Regex::new(r"官方网站: (.+?)\n").unwrap();
         let re_name_math = Regex::new(r"(.+第\w季|[\w\uff1a\uff01\uff0c\u00b7]+)\s*(.*)").unwrap();
         let re_role = Regex::new(r"\([饰|配] (.+?)\)").unwrap();
+        let re_content_rating = Regex::new(r#"("contentRating"\s*:\s*"|分级: )([^"\n]+)"#).unwrap();
+        let re_votes = Regex::new(r"(\d+)\s*人评价").unwrap();
+        let re_collection_year = Regex::new(r"年份:\s*(\d{4})").unwrap();
+        let re_date = Regex::new(r"(\d{4})-(\d{2})-(\d{2})").unwrap();
+        // 2019-07-26(美国)、2019-07(中国大陆)、2019，拆分为日期与地区
+        let re_release_date = Regex::new(r"^([^(（]*)(?:[(（]([^)）]*)[)）])?").unwrap();
+        let re_img_host = Regex::new(r"img\d+\.doubanio\.com").unwrap();
+        let re_img_size = Regex::new(r"/view/(photo|celebrity|personage)/[a-z_]+/").unwrap();
+        let re_season = Regex::new(r"^(.+?)\s*第([\d零一二两三四五六七八九十百]+)季").unwrap();
+        let re_episode_count = Regex::new(r"集数:\s*(\d+)").unwrap();
+        let re_episode_duration = Regex::new(r"单集片长:\s*(.+?)\n").unwrap();
+        let re_wish = Regex::new(r"(\d+)\s*人想看").unwrap();
+        let re_doing = Regex::new(r"(\d+)\s*人在看").unwrap();
         Self {
             client,
+            search_strategy,
+            noise_keywords,
+            concurrency: config.concurrency.max(1),
+            omdb_api_key: config.omdb_api_key.trim().to_string(),
             re_id,
             re_backgroud_image,
             re_sid,
@@ -77,32 +246,166 @@ impl Douban {
             re_language,
             re_duration,
             re_screen,
+            re_company,
             re_subname,
             re_imdb,
             re_site,
             re_name_math,
             re_role,
+            re_season,
+            re_content_rating,
+            re_votes,
+            re_collection_year,
+            re_date,
+            re_release_date,
+            re_img_host,
+            re_img_size,
+            re_episode_count,
+            re_episode_duration,
+            re_wish,
+            re_doing,
+        }
+    }
+
+    /// 按配置的降级链依次搜索，返回结果及实际使用的数据源
+    pub async fn search(
+        &self,
+        q: &str,
+        limit: i32,
+        image_size: &str,
+        filter: &SearchFilter,
+    ) -> Result<(Vec<Movie>, SearchSource)> {
+        let (vec, source, _) = self.search_with_status(q, limit, image_size, filter).await?;
+        Ok((vec, source))
+    }
+
+    /// 同search，另外返回结果状态，区分真无结果与被限流/请求或解析失败
+    pub async fn search_with_status(
+        &self,
+        q: &str,
+        limit: i32,
+        image_size: &str,
+        filter: &SearchFilter,
+    ) -> Result<(Vec<Movie>, SearchSource, SearchStatus)> {
+        let vec = Vec::new();
+        if q.is_empty() {
+            return Ok((vec, SearchSource::None, SearchStatus::Empty));
+        }
+        let negative_key = format!("q_{}", q.trim().to_lowercase());
+        if NEGATIVE_CACHE.get(&negative_key).is_some() {
+            return Ok((vec, SearchSource::None, SearchStatus::Empty));
+        }
+        let cache_key = format!("{}_{}", q.trim().to_lowercase(), image_size);
+        if let Some((list, source)) = SEARCH_CACHE.get(&cache_key) {
+            let (vec, status) = self.filter_search(list, source, limit, filter);
+            if !self.needs_fallback(&vec, source, filter) {
+                return Ok((vec, source, status));
+            }
+        }
+
+        // 所有数据源都正常返回空结果时才确定无结果，失败或超时不计入
+        let mut all_empty = true;
+        let mut status = SearchStatus::Empty;
+        for (source, timeout) in self.search_strategy.iter() {
+            let res = tokio::time::timeout(*timeout, async {
+                match source {
+                    SearchSource::Suggest => self.search_suggest(q, image_size).await,
+                    SearchSource::Web => self.search_web(q, image_size).await,
+                    SearchSource::Mobile => self.search_mobile(q, image_size).await,
+                    SearchSource::Local => Ok(self.search_local(q)),
+                    SearchSource::None => Ok(Vec::new()),
+                }
+            })
+            .await;
+            match res {
+                Ok(Ok(list)) if !list.is_empty() => {
+                    if *source != SearchSource::Local {
+                        add_local_index(&list);
+                        SEARCH_CACHE.insert(cache_key.clone(), (list.clone(), *source)).await;
+                    }
+                    let (vec, status) = self.filter_search(list, *source, limit, filter);
+                    if self.needs_fallback(&vec, *source, filter) {
+                        all_empty = false;
+                        println!("{} 搜索结果无分类，继续尝试下一个数据源: {}", source, q);
+                        continue;
+                    }
+                    return Ok((vec, *source, status));
+                }
+                Ok(Ok(_)) => println!("{} 搜索无结果: {}", source, q),
+                Ok(Err(err)) => {
+                    all_empty = false;
+                    println!("{} 搜索失败: {:?}", source, err);
+                    let code = ApiError::from(err).code;
+                    if code == ErrorCode::DoubanRateLimited || code == ErrorCode::NeedLogin {
+                        status = SearchStatus::RateLimited;
+                    } else if status == SearchStatus::Empty {
+                        status = SearchStatus::ParseError;
+                    }
+                }
+                Err(_) => {
+                    all_empty = false;
+                    println!("{} 搜索超时: {}", source, q);
+                    // 上游超时与限流一样是暂时性的，调用方稍后重试即可
+                    status = SearchStatus::RateLimited;
+                }
+            }
+        }
+        if all_empty {
+            NEGATIVE_CACHE.insert(negative_key, ()).await;
+        }
+
+        Ok((vec, SearchSource::None, status))
+    }
+
+    /// 指定了分类而数据源不带分类信息，过滤后没有结果时不能断定无结果，需继续降级
+    fn needs_fallback(&self, vec: &[Movie], source: SearchSource, filter: &SearchFilter) -> bool {
+        vec.is_empty() && !filter.cat.is_empty() && !source.has_cat()
+    }
+
+    /// 按筛选条件过滤完整的搜索结果，再按limit截取
+    fn filter_search(
+        &self,
+        mut vec: Vec<Movie>,
+        source: SearchSource,
+        limit: i32,
+        filter: &SearchFilter,
+    ) -> (Vec<Movie>, SearchStatus) {
+        let has_votes = source.has_votes();
+        vec.retain(|x| {
+            filter.matches_cat(&x.cat) && filter.matches(&x.rating, has_votes.then_some(x.votes))
+        });
+        if !filter.keep_noise {
+            vec.retain(|x| !self.is_noise(x));
         }
+        if limit > 0 {
+            vec.truncate(limit as usize);
+        }
+        let status = if vec.is_empty() {
+            SearchStatus::Empty
+        } else {
+            SearchStatus::Ok
+        };
+
+        (vec, status)
     }
 
-    pub async fn search(&self, q: &str, limit: i32, image_size: &str) -> Result<Vec<Movie>> {
+    async fn search_web(&self, q: &str, image_size: &str) -> Result<Vec<Movie>> {
         let mut vec = Vec::new();
         if q.is_empty() {
             return Ok(vec);
         }
 
-        let url = "https://www.douban.com/search";
+        let url = format!("{}/search", self.client.www_base());
         let res = self
             .client
-            .get(url)
-            .query(&[("cat", "1002"), ("q", q)])
-            .send()
+            .send(self.client.get(&url).query(&[("cat", "1002"), ("q", q)]))
             .await?
             .error_for_status();
 
         match res {
             Ok(res) => {
                 println!("Response Headers: {:#?}", res.headers());
+                check_login(&res, &url)?;
                 let res = res.text().await?;
                 let document = Vis::load(&res).unwrap();
                 let iter = document
@@ -134,22 +437,21 @@ impl Douban {
                         let cat = self.parse_cat(&title_mark);
                         let subject = x.find("div.rating-info>.subject-cast").text().to_string();
                         let year = self.parse_year(subject);
+                        let votes = self.parse_votes(x.find("div.rating-info").text());
                         Movie {
+                            cat_kind: CatKind::parse(&cat),
                             cat,
                             sid,
                             name,
                             rating,
+                            votes,
                             img,
                             year,
                         }
                     })
                     .into_iter()
                     .filter(|x| x.cat == "电影" || x.cat == "电视剧");
-                if limit > 0 {
-                    vec = iter.take(limit as usize).collect::<Vec<Movie>>();
-                } else {
-                    vec = iter.collect::<Vec<Movie>>();
-                }
+                vec = iter.collect::<Vec<Movie>>();
             }
             Err(err) => {
                 println!("{:?}", err)
@@ -159,257 +461,2097 @@ impl Douban {
         Ok(vec)
     }
 
-    pub async fn search_full(
-        &self,
-        q: &str,
-        limit: i32,
-        image_size: &str,
-    ) -> Result<Vec<MovieInfo>> {
-        let movies = self.search(q, limit, image_size).await.unwrap();
-        let mut list = Vec::with_capacity(movies.len());
-        for i in movies.iter() {
-            list.push(self.get_movie_info(&i.sid, image_size).await.unwrap())
+    /// 透传豆瓣搜索建议，只返回影视条目的极简信息，用于自动补全
+    pub async fn suggest(&self, q: &str) -> Result<Vec<Suggestion>> {
+        let cache_key = q.trim().to_lowercase();
+        if let Some(list) = SUGGEST_CACHE.get(&cache_key) {
+            return Ok(list);
         }
+        let url = format!("{}/j/subject_suggest", self.client.movie_base());
+        let list = self
+            .client
+            .send(self.client.get(url).query(&[("q", q)]))
+            .await?
+            .error_for_status()?
+            .json::<Vec<SuggestItem>>()
+            .await?
+            .into_iter()
+            .filter(|x| x.r#type == "movie")
+            .map(|x| Suggestion {
+                kind: if x.episode.is_empty() { "movie" } else { "tv" },
+                sid: x.id,
+                title: x.title,
+                year: x.year,
+                img: x.img,
+            })
+            .collect::<Vec<Suggestion>>();
+        SUGGEST_CACHE.insert(cache_key, list.clone()).await;
 
         Ok(list)
     }
 
-    pub async fn get_movie_info(&self, sid: &str, image_size: &str) -> Result<MovieInfo> {
-        let cache_key = format!("movie_{}_{}", sid, image_size);
-        if MOVIE_CACHE.get(&cache_key).is_some() {
-            return Ok(MOVIE_CACHE.get(&cache_key).unwrap());
-        }
-        let url = format!("https://movie.douban.com/subject/{}/", sid);
+    async fn search_suggest(&self, q: &str, image_size: &str) -> Result<Vec<Movie>> {
+        let url = format!("{}/j/subject_suggest", self.client.movie_base());
+        let list = self
+            .client
+            .send(self.client.get(url).query(&[("q", q)]))
+            .await?
+            .error_for_status()?
+            .json::<Vec<SuggestItem>>()
+            .await?;
+
+        Ok(list
+            .into_iter()
+            .filter(|x| x.r#type == "movie")
+            .map(|x| Movie {
+                cat: if x.episode.is_empty() {
+                    "电影".to_string()
+                } else {
+                    "电视剧".to_string()
+                },
+                cat_kind: if x.episode.is_empty() {
+                    Some(CatKind::Movie)
+                } else {
+                    Some(CatKind::Tv)
+                },
+                sid: x.id,
+                name: x.title,
+                rating: "0".to_string(),
+                votes: 0,
+                img: self.get_img_by_size(&x.img, image_size),
+                year: x.year,
+            })
+            .collect::<Vec<Movie>>())
+    }
+
+    async fn search_mobile(&self, q: &str, image_size: &str) -> Result<Vec<Movie>> {
+        let url = format!("{}/search/", self.client.mobile_base());
         let res = self
             .client
-            .get(url)
-            .send()
+            .send(
+                self.client
+                    .get(url)
+                    .query(&[("query", q), ("type", "movie")]),
+            )
             .await?
-            .error_for_status()
-            .unwrap();
+            .error_for_status()?
+            .text()
+            .await?;
 
-        let res = res.text().await?;
         let document = Vis::load(&res).unwrap();
-        let x = document.find("#content");
+        let vec = document
+            .find("ul.search_results_subjects>li>a")
+            .map(|_index, x| {
+                let x = Vis::dom(x);
+                let href = x.attr("href").map(|x| x.to_string()).unwrap_or_default();
+                let sid = self.parse_id(&href);
+                let img = match x.find("img").attr("src") {
+                    Some(src) => self.get_img_by_size(src.to_string().as_str(), image_size),
+                    None => String::new(),
+                };
+                let name = x.find("span.subject-title").text().trim().to_string();
+                let mut rating = x.find("p.rating span:last-child").text().trim().to_string();
+                if rating.parse::<f32>().is_err() {
+                    rating = "0".to_string();
+                }
+                // 移动站的电影与电视剧链接相同，页面上没有可区分分类的标记
+                Movie {
+                    cat: String::new(),
+                    cat_kind: None,
+                    sid,
+                    name,
+                    rating,
+                    votes: 0,
+                    img,
+                    year: String::new(),
+                }
+            })
+            .into_iter()
+            .filter(|x| !x.sid.is_empty())
+            .collect::<Vec<Movie>>();
 
-        let sid = sid.to_string();
-        let name_str = x.find("h1>span:first-child").text().to_string();
-        let cs = self.re_name_math.captures(&name_str).unwrap();
-        let name = (&cs[1]).to_string();
-        let original_name = (&cs[2]).to_string();
+        Ok(vec)
+    }
 
-        let year_str = x.find("h1>span.year").text().to_string();
-        let year = self.parse_year_for_detail(&year_str);
+    fn search_local(&self, q: &str) -> Vec<Movie> {
+        let q = q.to_lowercase();
+        LOCAL_INDEX
+            .read()
+            .unwrap()
+            .values()
+            .filter(|x| x.name.to_lowercase().contains(&q))
+            .cloned()
+            .collect::<Vec<Movie>>()
+    }
 
-        let mut rating = x
-            .find("div.rating_self strong.rating_num")
-            .text()
-            .to_string();
-        if rating.is_empty() {
-            rating = "0".to_string();
+    /// 噪音条目：无年份、无评分且标题含预告/花絮等关键词
+    fn is_noise(&self, movie: &Movie) -> bool {
+        movie.year.trim().is_empty()
+            && movie.rating.trim().parse::<f32>().unwrap_or(0.0) <= 0.0
+            && self
+                .noise_keywords
+                .iter()
+                .any(|x| movie.name.contains(x.as_str()))
+    }
+
+    /// 离线模式，只从本地索引与详情缓存中查询
+    pub fn search_cached(&self, q: &str, limit: i32, filter: &SearchFilter) -> Vec<Movie> {
+        let mut vec = self.search_local(q);
+        vec.retain(|x| filter.matches_cat(&x.cat) && filter.matches(&x.rating, None));
+        if !filter.keep_noise {
+            vec.retain(|x| !self.is_noise(x));
+        }
+        if limit > 0 {
+            vec.truncate(limit as usize);
         }
-        let img = self.get_img_by_size(
-            x.find("a.nbgnbg>img")
-                .attr("src")
-                .unwrap()
-                .to_string()
-                .as_str(),
-            image_size,
-        );
 
-        let intro = x.find("div.indent>span").text().trim().replace("©豆瓣", "");
-        let info = x.find("#info").text().to_string();
-        let (
-            director,
-            writer,
-            actor,
-            genre,
-            site,
-            country,
-            language,
-            screen,
-            duration,
-            subname,
-            imdb,
-        ) = self.parse_info(&info);
+        vec
+    }
 
-        let celebrities: Vec<Celebrity> =
-            x.find("#celebrities li.celebrity")
-                .first()
-                .map(|_index, x| {
-                    let x = Vis::dom(x);
-                    let id_str = x.find("div.info a.name").attr("href").unwrap().to_string();
-                    let id = self.parse_id(&id_str);
-                    let img_str = x.find("div.avatar").attr("style").unwrap().to_string();
-                    let img = self
-                        .get_img_by_size(self.parse_backgroud_image(&img_str).as_str(), image_size);
-                    let name = x.find("div.info a.name").text().to_string();
-                    let role = x.find("div.info span.role").text().to_string();
-                    let role_type = String::new();
+    pub fn search_full_cached(
+        &self,
+        q: &str,
+        limit: i32,
+        image_size: &str,
+        filter: &SearchFilter,
+    ) -> Vec<MovieInfo> {
+        let mut list = Vec::new();
+        for movie in self.search_local(q).iter() {
+            if !filter.matches_cat(&movie.cat) || (!filter.keep_noise && self.is_noise(movie)) {
+                continue;
+            }
+            let cache_key = format!("movie_{}_{}", movie.sid, image_size);
+            if let Some(info) = MOVIE_CACHE.get(&cache_key) {
+                if filter.matches(&info.rating, Some(info.votes)) {
+                    list.push(info);
+                }
+            }
+            if limit > 0 && list.len() >= limit as usize {
+                break;
+            }
+        }
 
-                    Celebrity {
-                        id,
-                        img,
-                        name,
-                        role_type,
-                        role,
-                    }
-                });
+        list
+    }
 
-        let info = MovieInfo {
-            sid,
-            name,
-            original_name,
-            rating,
-            img,
-            year,
-            intro,
-            director,
-            writer,
-            actor,
-            genre,
-            site,
-            country,
-            language,
-            screen,
-            duration,
-            subname,
-            imdb,
-            celebrities,
+    /// deadline: 超时预算截止时间，到期后未完成的条目记入missing
+    pub async fn search_full(
+        &self,
+        q: &str,
+        limit: i32,
+        image_size: &str,
+        filter: &SearchFilter,
+        deadline: Option<Instant>,
+    ) -> Result<(Partial<MovieInfo>, SearchSource, SearchStatus)> {
+        let mut result = Partial::default();
+        // 有过滤条件时，搜索结果中的评分人数不一定准确，预取详情后再过滤
+        let search_limit = if filter.is_empty() { limit } else { 0 };
+        let search_filter = SearchFilter {
+            keep_noise: filter.keep_noise,
+            cat: filter.cat,
+            ..SearchFilter::default()
         };
-        MOVIE_CACHE.insert(cache_key, info.clone()).await;
+        let (movies, source, status) = match within(
+            deadline,
+            self.search_with_status(q, search_limit, image_size, &search_filter),
+        )
+        .await
+        {
+            Some(res) => res?,
+            None => {
+                result.incomplete = true;
+                return Ok((result, SearchSource::None, SearchStatus::RateLimited));
+            }
+        };
+        // 按搜索结果顺序并发获取详情，达到数量后不再继续
+        let mut details = futures::stream::iter(movies.iter())
+            .map(|i| async move {
+                let info = within(deadline, self.get_movie_info(&i.sid, image_size)).await;
+                (i, info)
+            })
+            .buffered(self.concurrency);
+        while let Some((i, info)) = details.next().await {
+            let info = match info {
+                Some(Ok(info)) => info,
+                // 超时或获取详情失败的条目记入missing
+                _ => {
+                    result.incomplete = true;
+                    result.missing.push(i.sid.clone());
+                    continue;
+                }
+            };
+            if !filter.matches(&info.rating, Some(info.votes)) {
+                continue;
+            }
+            result.data.push(info);
+            if limit > 0 && result.data.len() + result.missing.len() >= limit as usize {
+                break;
+            }
+        }
 
-        Ok(info)
+        // 详情过滤后没有剩余条目时视为无结果
+        let status = if status == SearchStatus::Ok && result.data.is_empty() && !result.incomplete
+        {
+            SearchStatus::Empty
+        } else {
+            status
+        };
+
+        Ok((result, source, status))
     }
 
-    pub async fn get_celebrities(&self, sid: &str) -> Result<Vec<Celebrity>> {
-        let url = format!("https://movie.douban.com/subject/{}/celebrities", sid);
-        let res = self
-            .client
-            .get(url)
-            .send()
-            .await?
-            .error_for_status()
-            .unwrap();
+    /// 抓取新片榜，并预热榜单条目的详情缓存
+    pub async fn refresh_new_releases(&self) -> Result<()> {
+        let url = format!("{}/chart", self.client.movie_base());
+        let res = self.client.get_html(&url).await?.body;
 
-        let res = res.text().await?;
         let document = Vis::load(&res).unwrap();
-        let x = document.find("#content");
-
-        let celebrities: Vec<Celebrity> = x
-            .find("ul.celebrities-list li.celebrity")
+        let movies = document
+            .find("div.indent table tr.item")
             .map(|_index, x| {
                 let x = Vis::dom(x);
-                let id_str = x.find("div.info a.name").attr("href").unwrap().to_string();
-                let id = self.parse_id(&id_str);
-                let img_str = x.find("div.avatar").attr("style").unwrap().to_string();
-                let img = self.parse_backgroud_image(&img_str);
-                let name = x
-                    .find("div.info a.name")
-                    .text()
-                    .split_whitespace()
-                    .next()
-                    .unwrap_or("")
-                    .to_string();
-                let mut role = match self.re_role.captures(x.find("div.info span.role").text()) {
-                    Some(x) => x.get(1).unwrap().as_str().trim().to_string(),
+                let href = x.find("a.nbg").attr("href").map(|x| x.to_string());
+                let sid = self.parse_id(&href.unwrap_or_default());
+                let img = match x.find("a.nbg>img").attr("src") {
+                    Some(src) => src.to_string(),
                     None => String::new(),
                 };
-                let role_type = x
-                    .find("div.info span.role")
+                let name = x
+                    .find("div.pl2>a")
                     .text()
-                    .split_whitespace()
+                    .split('/')
                     .next()
                     .unwrap_or("")
+                    .trim()
                     .to_string();
-                if role.is_empty() {
-                    role = role_type.clone();
+                let mut rating = x.find("span.rating_nums").text().trim().to_string();
+                if rating.is_empty() {
+                    rating = "0".to_string();
                 }
-
-                Celebrity {
-                    id,
-                    img,
+                let year = x.find("p.pl").text().trim().chars().take(4).collect();
+                let votes = self.parse_votes(x.find("span.pl").text());
+                Movie {
+                    cat: "电影".to_string(),
+                    cat_kind: Some(CatKind::Movie),
+                    sid,
                     name,
-                    role_type,
-                    role,
+                    rating,
+                    votes,
+                    img,
+                    year,
                 }
             })
             .into_iter()
-            .filter(|x| x.role_type == "导演" || x.role_type == "配音" || x.role_type == "演员")
-            .take(15)
-            .collect::<Vec<Celebrity>>();
+            .filter(|x| !x.sid.is_empty())
+            .collect::<Vec<Movie>>();
 
-        Ok(celebrities)
-    }
+        for movie in movies.iter() {
+            if let Err(err) = self.get_movie_info(&movie.sid, "").await {
+                println!("预热新片详情失败: {} {:?}", movie.sid, err);
+            }
+        }
+        add_local_index(&movies);
 
-    pub async fn get_celebrity(&self, id: &str) -> Result<CelebrityInfo> {
-        let url = format!("https://movie.douban.com/celebrity/{}/", id);
-        let res = self
-            .client
-            .get(url)
-            .send()
-            .await?
-            .error_for_status()
-            .unwrap();
+        let updated_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|x| x.as_secs())
+            .unwrap_or(0);
+        *NEW_RELEASES.write().unwrap() = NewReleases { updated_at, movies };
+        Ok(())
+    }
 
-        let res = res.text().await?;
+    /// 解析豆瓣片单，每页25条
+    pub async fn get_collection(&self, id: &str, start: u32) -> Result<Collection> {
+        let url = format!("{}/doulist/{}/?start={}", self.client.www_base(), id, start);
+        let res = self.client.get_html(&url).await?.body;
         let document = Vis::load(&res).unwrap();
         let x = document.find("#content");
-        let id = id.to_string();
-        let img = x
-            .find("#headline .nbg img")
-            .attr("src")
-            .unwrap()
-            .to_string();
-        let name = x.find("h1").text().to_string();
-        let mut intro = x.find("#intro span.all").text().trim().to_string();
-        if intro.is_empty() {
-            intro = x.find("#intro div.bd").text().trim().to_string();
-        }
 
-        let info = x.find("div.info").text().to_string();
-        let (gender, constellation, birthdate, birthplace, role, nickname, family, imdb) =
-            self.parse_celebrity_info(&info);
+        let title = x.find("h1").text().trim().to_string();
+        let intro = x.find("div.doulist-about").text().trim().to_string();
+        let items = x
+            .find("div.doulist-item")
+            .map(|_index, x| {
+                let x = Vis::dom(x);
+                let href = x.find("div.title a").attr("href").map(|x| x.to_string());
+                let sid = self.parse_id(&href.unwrap_or_default());
+                let name = x.find("div.title a").text().trim().to_string();
+                let img = match x.find("div.post img").attr("src") {
+                    Some(src) => src.to_string(),
+                    None => String::new(),
+                };
+                let mut rating = x.find("span.rating_nums").text().trim().to_string();
+                if rating.is_empty() {
+                    rating = "0".to_string();
+                }
+                let votes = self.parse_votes(x.find("div.rating").text());
+                let abstract_text = x.find("div.abstract").text().to_string();
+                let year = match self.re_collection_year.captures(&abstract_text) {
+                    Some(x) => x.get(1).unwrap().as_str().to_string(),
+                    None => String::new(),
+                };
+                Movie {
+                    cat: String::new(),
+                    cat_kind: None,
+                    sid,
+                    name,
+                    rating,
+                    votes,
+                    img,
+                    year,
+                }
+            })
+            .into_iter()
+            .filter(|x| !x.sid.is_empty())
+            .collect::<Vec<Movie>>();
+        let has_more = x.find("div.paginator span.next a").length() > 0;
 
-        Ok(CelebrityInfo {
-            id,
-            img,
-            name,
-            role,
+        Ok(Collection {
+            id: id.to_string(),
+            title,
             intro,
-            gender,
-            constellation,
+            start,
+            has_more,
+            items,
+        })
+    }
+
+    /// 历史上的今天上映的电影，只从已获取过详情的条目中查找，按年份排序
+    pub fn get_on_this_day(&self, month: u32, day: u32) -> Vec<OnThisDay> {
+        let suffix = format!("-{:02}-{:02}", month, day);
+        let index = LOCAL_INDEX.read().unwrap();
+        let mut list = RELEASE_INDEX
+            .read()
+            .unwrap()
+            .iter()
+            .filter_map(|(sid, dates)| {
+                let date = dates.iter().find(|x| x.ends_with(&suffix))?;
+                let movie = index.get(sid)?;
+                Some(OnThisDay {
+                    year: date[..4].parse::<i32>().unwrap_or(0),
+                    date: date.clone(),
+                    movie: movie.clone(),
+                })
+            })
+            .collect::<Vec<OnThisDay>>();
+        list.sort_by_key(|x| x.year);
+
+        list
+    }
+
+    /// 指定月日生日的影人(仅获取过详情的影人)，按出生年份排序
+    pub fn get_birthdays(&self, month: u32, day: u32) -> Vec<CelebrityBirthday> {
+        let suffix = format!("-{:02}-{:02}", month, day);
+        let mut list = BIRTHDAY_INDEX
+            .read()
+            .unwrap()
+            .values()
+            .filter(|x| x.birthdate.ends_with(&suffix))
+            .cloned()
+            .collect::<Vec<CelebrityBirthday>>();
+        list.sort_by(|a, b| a.birthdate.cmp(&b.birthdate));
+
+        list
+    }
+
+    /// 识别一批剧集文件名，按剧名+季号分组后匹配豆瓣条目
+    pub async fn scrape_series(&self, files: &[String]) -> ScrapeResult {
+        let mut groups: Vec<(EpisodeFile, Vec<EpisodeFile>)> = Vec::new();
+        let mut unmatched = Vec::new();
+        for file in files.iter() {
+            let episode = match parse_episode_file(file) {
+                Some(x) if !x.title.is_empty() => x,
+                _ => {
+                    unmatched.push(file.clone());
+                    continue;
+                }
+            };
+            match groups.iter_mut().find(|(key, _)| {
+                key.title.to_lowercase() == episode.title.to_lowercase()
+                    && key.season == episode.season
+            }) {
+                Some((_, list)) => list.push(episode),
+                None => groups.push((episode.clone(), vec![episode])),
+            }
+        }
+
+        // 各剧集的搜索与详情抓取按配置的并发数进行，结果保持提交顺序
+        let mut matches = futures::stream::iter(groups)
+            .map(|(key, list)| async move {
+                let info = self.match_series(&key).await;
+                (key, list, info)
+            })
+            .buffered(self.concurrency);
+        let mut series = Vec::new();
+        while let Some((key, mut list, info)) = matches.next().await {
+            list.sort_by_key(|x| x.episode);
+            let info = match info {
+                Ok(info) => info,
+                Err(err) => {
+                    println!("剧集匹配失败: {} {:?}", key.title, err);
+                    None
+                }
+            };
+            series.push(SeriesMatch {
+                title: key.title,
+                season: key.season,
+                year: key.year,
+                matched: info.is_some(),
+                sid: info.as_ref().map(|x| x.sid.clone()).unwrap_or_default(),
+                name: info.as_ref().map(|x| x.name.clone()).unwrap_or_default(),
+                rating: info.as_ref().map(|x| x.rating.clone()).unwrap_or_default(),
+                episodes: list
+                    .into_iter()
+                    .map(|x| EpisodeMatch {
+                        file: x.file,
+                        episode: x.episode,
+                    })
+                    .collect(),
+            });
+        }
+
+        ScrapeResult { series, unmatched }
+    }
+
+    /// 搜索剧名(第二季起带季号)，优先选择季号和年份一致的电视剧
+    async fn match_series(&self, key: &EpisodeFile) -> Result<Option<MovieInfo>> {
+        let q = if key.season > 1 {
+            format!("{} 第{}季", key.title, key.season)
+        } else {
+            key.title.clone()
+        };
+        let (mut movies, _) = self.search(&q, 0, "", &SearchFilter::default()).await?;
+        // 电视剧排在前面
+        movies.sort_by_key(|x| x.cat != "电视剧");
+        let mut fallback = None;
+        for movie in movies.iter().take(SCRAPE_CANDIDATE_SIZE) {
+            let info = self.get_movie_info(&movie.sid, "").await?;
+            let season_ok =
+                info.season_number == key.season || (info.season_number == 0 && key.season == 1);
+            let year_ok = key.year.is_empty() || info.year == key.year;
+            if season_ok && year_ok {
+                return Ok(Some(info));
+            }
+            if season_ok && fallback.is_none() {
+                fallback = Some(info);
+            }
+        }
+
+        Ok(fallback)
+    }
+
+    /// 基于豆瓣选影视(/tag/)按年代、地区、类型组合筛选
+    pub async fn explore(&self, filter: &ExploreFilter, start: u32) -> Result<Explore> {
+        let year_range = match parse_decade(&filter.decade) {
+            Some((from, to)) => format!("{},{}", from, to),
+            None => String::new(),
+        };
+        let sort = match filter.sort.as_str() {
+            "rating" => "S",
+            "votes" => "T",
+            "time" => "R",
+            _ => "U",
+        };
+        let start_str = start.to_string();
+        let url = format!("{}/j/new_search_subjects", self.client.movie_base());
+        let res = self
+            .client
+            .send(
+                self.client
+                    .get(url)
+                    .query(&[
+                        ("sort", sort),
+                        ("range", "0,10"),
+                        ("tags", filter.tag.as_str()),
+                        ("start", start_str.as_str()),
+                        ("genres", filter.genre.as_str()),
+                        ("countries", filter.region.as_str()),
+                        ("year_range", year_range.as_str()),
+                    ])
+                    .header("Referer", format!("{}/tag/", self.client.movie_base())),
+            )
+            .await?
+            .error_for_status()?
+            .json::<ExploreResponse>()
+            .await?;
+
+        let items = res
+            .data
+            .into_iter()
+            .map(|x| Movie {
+                cat: String::new(),
+                cat_kind: None,
+                sid: x.id,
+                name: x.title,
+                rating: if x.rate.is_empty() {
+                    "0".to_string()
+                } else {
+                    x.rate
+                },
+                votes: 0,
+                img: x.cover,
+                year: String::new(),
+            })
+            .collect::<Vec<Movie>>();
+
+        Ok(Explore {
+            start,
+            has_more: items.len() as u32 >= EXPLORE_PAGE_SIZE,
+            items,
+        })
+    }
+
+    /// 豆瓣电影日历的当日推荐
+    pub async fn get_daily(&self) -> Result<Daily> {
+        let (year, month, day) = today();
+        let date = format!("{}-{:02}-{:02}", year, month, day);
+        if let Some(daily) = DAILY_CACHE.get(&date) {
+            return Ok(daily);
+        }
+        let url = format!("{}/rexxar/api/v2/calendar/today", self.client.mobile_base());
+        let res = self
+            .client
+            .send(
+                self.client
+                    .get(url)
+                    .query(&[("for_mobile", "1")])
+                    .header("Referer", format!("{}/movie/", self.client.mobile_base())),
+            )
+            .await?
+            .error_for_status()?
+            .json::<serde_json::Value>()
+            .await?;
+
+        // 兼容当日推荐在顶层或today字段下
+        let item = if res["subject"].is_object() {
+            &res
+        } else {
+            &res["today"]
+        };
+        let subject = &item["subject"];
+        let comment = &item["comment"];
+        let text = |x: &serde_json::Value| x.as_str().unwrap_or("").trim().to_string();
+        let daily = Daily {
+            date: date.clone(),
+            sid: text(&subject["id"]),
+            name: text(&subject["title"]),
+            rating: match subject["rating"]["value"].as_f64() {
+                Some(x) if x > 0.0 => format!("{:.1}", x),
+                _ => "0".to_string(),
+            },
+            img: text(&subject["pic"]["normal"]),
+            year: text(&subject["year"]),
+            quote: text(&comment["content"]),
+            quote_source: text(&comment["source"]),
+        };
+        if daily.sid.is_empty() {
+            return Err(anyhow::anyhow!("解析电影日历失败"));
+        }
+        DAILY_CACHE.insert(date, daily.clone()).await;
+
+        Ok(daily)
+    }
+
+    /// 解析豆瓣电影首页的周票房榜，last_rank为上一期排名，新上榜为None
+    pub async fn get_weekly_box_office(&self) -> Result<BoxOffice> {
+        let cache_key = "weekly".to_string();
+        if let Some(box_office) = BOX_OFFICE_CACHE.get(&cache_key) {
+            return Ok(box_office);
+        }
+        let res = self
+            .client
+            .get_html(&format!("{}/", self.client.movie_base()))
+            .await?
+            .body;
+        let document = Vis::load(&res).unwrap();
+        let x = document.find("#billboard");
+        let period = x.find(".billboard-hd span").text().trim().to_string();
+        let mut items: Vec<BoxOfficeItem> = x.find(".billboard-bd tr").map(|_index, tr| {
+            let tr = Vis::dom(tr);
+            let a = tr.find("td.title a");
+            let href = a.attr("href").map(|x| x.to_string()).unwrap_or_default();
+            BoxOfficeItem {
+                rank: tr
+                    .find("td.order")
+                    .text()
+                    .trim()
+                    .parse::<u32>()
+                    .unwrap_or(0),
+                name: a.text().trim().to_string(),
+                sid: self.parse_id(&href),
+                box_office: tr.find("td.box_office").text().trim().to_string(),
+                last_rank: None,
+                change: None,
+            }
+        });
+        items.retain(|x| x.rank > 0 && !x.sid.is_empty());
+        if items.is_empty() {
+            return Err(anyhow::anyhow!("解析周票房榜失败"));
+        }
+
+        add_box_office_history(&period, &mut items);
+        let box_office = BoxOffice { period, items };
+        BOX_OFFICE_CACHE.insert(cache_key, box_office.clone()).await;
+
+        Ok(box_office)
+    }
+
+    /// 订阅条目状态变化，同一 sid+webhook 重复订阅返回已有订阅，webhook只能指向公网地址
+    pub async fn add_subscription(&self, sid: &str, webhook: &str) -> Result<Subscription> {
+        self.client.check_webhook(webhook).await?;
+        let mut hasher = DefaultHasher::new();
+        (sid, webhook).hash(&mut hasher);
+        let id = format!("{:016x}", hasher.finish());
+        if let Some(subscription) = SUBSCRIPTIONS.read().unwrap().get(&id) {
+            return Ok(subscription.clone());
+        }
+        if SUBSCRIPTIONS.read().unwrap().len() >= MAX_SUBSCRIPTIONS {
+            return Err(anyhow::anyhow!("订阅数量超过上限{}", MAX_SUBSCRIPTIONS));
+        }
+
+        let info = self.get_movie_info(sid, "").await?;
+        let subscription = Subscription {
+            id: id.clone(),
+            sid: sid.to_string(),
+            name: info.name,
+            webhook: webhook.to_string(),
+            rating: info.rating,
+            playable: info.playable,
+            created_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|x| x.as_secs())
+                .unwrap_or(0),
+        };
+        SUBSCRIPTIONS
+            .write()
+            .unwrap()
+            .insert(id, subscription.clone());
+
+        Ok(subscription)
+    }
+
+    pub fn get_subscriptions(&self) -> Vec<Subscription> {
+        SUBSCRIPTIONS.read().unwrap().values().cloned().collect()
+    }
+
+    pub fn remove_subscription(&self, id: &str) -> Option<Subscription> {
+        SUBSCRIPTIONS.write().unwrap().remove(id)
+    }
+
+    /// 检查订阅条目的评分与播放资源，开分或资源状态变化时回调webhook
+    pub async fn check_subscriptions(&self) {
+        let subscriptions = self.get_subscriptions();
+        for subscription in subscriptions.iter() {
+            let info = match self.get_movie_info(&subscription.sid, "").await {
+                Ok(info) => info,
+                Err(err) => {
+                    println!("检查订阅失败: {} {:?}", subscription.sid, err);
+                    continue;
+                }
+            };
+            let mut events = Vec::new();
+            if subscription.rating == "0" && info.rating != "0" {
+                events.push("rated");
+            }
+            if subscription.playable != info.playable {
+                events.push("playable_changed");
+            }
+            if events.is_empty() {
+                continue;
+            }
+
+            let notification = SubscriptionEvent {
+                id: subscription.id.clone(),
+                sid: info.sid.clone(),
+                name: info.name.clone(),
+                events,
+                rating: info.rating.clone(),
+                votes: info.votes,
+                playable: info.playable,
+            };
+            // 发送时重新检查地址，避免域名解析已变为内网地址
+            let res = self
+                .client
+                .post_webhook(&subscription.webhook, &notification)
+                .await;
+            match res {
+                Ok(_) => {
+                    // 通知成功后才更新状态，失败时下次检查重试
+                    if let Some(x) = SUBSCRIPTIONS.write().unwrap().get_mut(&subscription.id) {
+                        x.rating = info.rating;
+                        x.playable = info.playable;
+                    }
+                }
+                Err(err) => println!("订阅回调失败: {} {:?}", subscription.webhook, err),
+            }
+        }
+    }
+
+    /// 导出本地索引、订阅以及索引中条目的详情/影人/图片缓存
+    pub fn export_cache(&self) -> Vec<CacheRecord> {
+        let mut records = Vec::new();
+        let movies = LOCAL_INDEX
+            .read()
+            .unwrap()
+            .values()
+            .cloned()
+            .collect::<Vec<Movie>>();
+        for movie in movies.iter() {
+            for image_size in CACHE_IMAGE_SIZES.iter() {
+                let key = format!("movie_{}_{}", movie.sid, image_size);
+                if let Some(info) = MOVIE_CACHE.get(&key) {
+                    for celebrity in info.celebrities.iter() {
+                        if let Some(value) = CELEBRITY_CACHE.get(&celebrity.id) {
+                            records.push(CacheRecord::Celebrity {
+                                key: celebrity.id.clone(),
+                                value: Box::new(value),
+                            });
+                        }
+                    }
+                    records.push(CacheRecord::Movie {
+                        key,
+                        value: Box::new(info),
+                    });
+                }
+            }
+            for photo_type in ["R", "S", "W"].iter() {
+                let key = format!("{}_{}", movie.sid, photo_type);
+                if let Some(value) = PHOTO_CACHE.get(&key) {
+                    records.push(CacheRecord::Photo { key, value });
+                }
+            }
+        }
+        records.extend(movies.into_iter().map(|movie| CacheRecord::Index { movie }));
+        records.extend(RELEASE_INDEX.read().unwrap().iter().map(|(sid, dates)| {
+            CacheRecord::Release {
+                sid: sid.clone(),
+                dates: dates.clone(),
+            }
+        }));
+        records.extend(
+            ALIAS_INDEX
+                .read()
+                .unwrap()
+                .iter()
+                .map(|(name, sid)| CacheRecord::Alias {
+                    name: name.clone(),
+                    sid: sid.clone(),
+                }),
+        );
+        records.extend(
+            self.get_subscriptions()
+                .into_iter()
+                .map(|value| CacheRecord::Subscription { value }),
+        );
+        records.extend(
+            changes::all()
+                .into_iter()
+                .map(|(key, value)| CacheRecord::Change { key, value }),
+        );
+
+        records
+    }
+
+    /// 统计本地索引中刮削过的条目构成，导演/演员/类型取自仍在缓存中的详情
+    pub fn library_stats(&self, top: usize) -> LibraryStats {
+        let movies = LOCAL_INDEX
+            .read()
+            .unwrap()
+            .values()
+            .cloned()
+            .collect::<Vec<Movie>>();
+        let mut by_year: HashMap<String, usize> = HashMap::new();
+        let mut by_cat: HashMap<String, usize> = HashMap::new();
+        let mut by_rating: HashMap<String, usize> = HashMap::new();
+        let mut by_genre: HashMap<String, usize> = HashMap::new();
+        let mut directors: HashMap<String, usize> = HashMap::new();
+        let mut actors: HashMap<String, usize> = HashMap::new();
+        let mut detailed = 0;
+        for movie in movies.iter() {
+            let year = if movie.year.is_empty() {
+                "unknown".to_string()
+            } else {
+                movie.year.clone()
+            };
+            *by_year.entry(year).or_insert(0) += 1;
+            let cat = if movie.cat.is_empty() {
+                "unknown".to_string()
+            } else {
+                movie.cat.clone()
+            };
+            *by_cat.entry(cat).or_insert(0) += 1;
+            *by_rating.entry(rating_bucket(&movie.rating)).or_insert(0) += 1;
+
+            let info = CACHE_IMAGE_SIZES
+                .iter()
+                .find_map(|size| MOVIE_CACHE.get(&format!("movie_{}_{}", movie.sid, size)));
+            if let Some(info) = info {
+                detailed += 1;
+                count_names(&mut by_genre, &info.genre);
+                count_names(&mut directors, &info.director);
+                count_names(&mut actors, &info.actor);
+            }
+        }
+
+        LibraryStats {
+            total: movies.len(),
+            detailed,
+            by_year: sorted_counts(by_year, false, usize::MAX),
+            by_cat: sorted_counts(by_cat, true, usize::MAX),
+            by_rating: sorted_counts(by_rating, false, usize::MAX),
+            by_genre: sorted_counts(by_genre, true, usize::MAX),
+            top_directors: sorted_counts(directors, true, top),
+            top_actors: sorted_counts(actors, true, top),
+        }
+    }
+
+    /// 导入导出的缓存记录，返回导入的条数
+    pub async fn import_cache(&self, records: Vec<CacheRecord>) -> usize {
+        let count = records.len();
+        for record in records.into_iter() {
+            match record {
+                CacheRecord::Index { movie } => add_local_index(&[movie]),
+                CacheRecord::Release { sid, dates } => {
+                    RELEASE_INDEX.write().unwrap().insert(sid, dates);
+                }
+                CacheRecord::Alias { name, sid } => {
+                    ALIAS_INDEX.write().unwrap().insert(name, sid);
+                }
+                CacheRecord::Movie { key, value } => MOVIE_CACHE.insert(key, *value).await,
+                CacheRecord::Celebrity { key, value } => {
+                    add_birthday_index(&value);
+                    CELEBRITY_CACHE.insert(key, *value).await
+                }
+                CacheRecord::Photo { key, value } => PHOTO_CACHE.insert(key, value).await,
+                CacheRecord::Subscription { value } => {
+                    SUBSCRIPTIONS
+                        .write()
+                        .unwrap()
+                        .insert(value.id.clone(), value);
+                }
+                CacheRecord::Change { key, value } => changes::insert(key, value),
+            }
+        }
+
+        count
+    }
+
+    /// 用归档的电影/影人详情页重建缓存记录，同一条目以最后抓取的为准
+    pub fn rebuild_from_archive(&self, entries: &[ArchiveEntry]) -> Vec<CacheRecord> {
+        let re_movie = Regex::new(r"/subject/(\d+)/?$").unwrap();
+        let re_celebrity = Regex::new(r"/celebrity/(\d+)/?$").unwrap();
+        let mut movies: HashMap<String, MovieInfo> = HashMap::new();
+        let mut celebrities: HashMap<String, CelebrityInfo> = HashMap::new();
+        for entry in entries.iter() {
+            let url = entry.url.split('?').next().unwrap_or("");
+            if let Some(cs) = re_movie.captures(url) {
+                if !url.starts_with(self.client.movie_base()) {
+                    continue;
+                }
+                match self.parse_movie_html(&cs[1], &entry.body, "") {
+                    Ok(info) => {
+                        movies.insert(cs[1].to_string(), info);
+                    }
+                    Err(err) => println!("解析归档页面失败: {} {:?}", entry.url, err),
+                }
+            } else if let Some(cs) = re_celebrity.captures(url) {
+                match self.parse_celebrity_html(&cs[1], &entry.body) {
+                    Ok(info) => {
+                        celebrities.insert(cs[1].to_string(), info);
+                    }
+                    Err(err) => println!("解析归档页面失败: {} {:?}", entry.url, err),
+                }
+            }
+        }
+
+        let mut records = Vec::new();
+        for (sid, info) in movies.into_iter() {
+            records.push(CacheRecord::Index {
+                movie: Movie {
+                    cat: String::new(),
+                    cat_kind: None,
+                    sid: sid.clone(),
+                    name: info.name.clone(),
+                    rating: info.rating.clone(),
+                    votes: info.votes,
+                    img: info.img.clone(),
+                    year: info.year.clone(),
+                },
+            });
+            records.push(CacheRecord::Movie {
+                key: format!("movie_{}_", sid),
+                value: Box::new(info),
+            });
+        }
+        records.extend(
+            celebrities.into_iter().map(|(key, value)| CacheRecord::Celebrity {
+                key,
+                value: Box::new(value),
+            }),
+        );
+
+        records
+    }
+
+    /// 重新抓取跟踪中条目的想看/在看人数，并写入快照文件
+    pub async fn refresh_trending(&self, file: &str) {
+        for sid in trending::sids().iter() {
+            if let Err(err) = self.get_movie_info(sid, "").await {
+                println!("抓取条目热度失败: {} {:?}", sid, err);
+            }
+        }
+        trending::save(file).await;
+    }
+
+    pub fn get_new_releases(&self) -> NewReleases {
+        NEW_RELEASES.read().unwrap().clone()
+    }
+
+    pub async fn get_movie_info(&self, sid: &str, image_size: &str) -> Result<MovieInfo> {
+        let cache_key = format!("movie_{}_{}", sid, image_size);
+        if MOVIE_CACHE.get(&cache_key).is_some() {
+            return Ok(MOVIE_CACHE.get(&cache_key).unwrap());
+        }
+        let negative_key = format!("movie_{}", sid);
+        if NEGATIVE_CACHE.get(&negative_key).is_some() {
+            return Err(ApiError::with_detail(ErrorCode::SubjectNotFound, sid).into());
+        }
+        let url = format!("{}/subject/{}/", self.client.movie_base(), sid);
+        let res = match self.client.get_html(&url).await {
+            Ok(page) => page.body,
+            Err(err) => {
+                if is_not_found(&err) {
+                    NEGATIVE_CACHE.insert(negative_key, ()).await;
+                }
+                return Err(err);
+            }
+        };
+        let info = {
+            let douban = self.clone();
+            let (sid, image_size) = (sid.to_string(), image_size.to_string());
+            parse_blocking(move || douban.parse_movie_html(&sid, &res, &image_size)).await?
+        };
+        metrics::validate("movie", &info);
+        add_alias_index(&info.aliases());
+        let dates = self
+            .re_date
+            .find_iter(&info.screen)
+            .map(|x| x.as_str().to_string())
+            .collect::<Vec<String>>();
+        if !dates.is_empty() {
+            RELEASE_INDEX
+                .write()
+                .unwrap()
+                .insert(info.sid.clone(), dates);
+        }
+        add_local_index(&[Movie {
+            cat: String::new(),
+            cat_kind: None,
+            sid: info.sid.clone(),
+            name: info.name.clone(),
+            rating: info.rating.clone(),
+            votes: info.votes,
+            img: info.img.clone(),
+            year: info.year.clone(),
+        }]);
+        changes::record("movie", &info.sid, &info.rating);
+        trending::record(&info.sid, &info.name, info.wish_count, info.doing_count);
+        if !info.imdb.is_empty() {
+            IMDB_INDEX
+                .write()
+                .unwrap()
+                .insert(info.imdb.clone(), info.sid.clone());
+        }
+        MOVIE_FETCHED_AT.insert(cache_key.clone(), now_secs()).await;
+        MOVIE_CACHE.insert(cache_key, info.clone()).await;
+
+        Ok(info)
+    }
+
+    /// 按IMDb编号查找豆瓣条目，先查本地索引，再用编号搜索并核对详情中的IMDb
+    pub async fn get_movie_by_imdb(&self, imdb: &str, image_size: &str) -> Result<MovieInfo> {
+        let sid = IMDB_INDEX.read().unwrap().get(imdb).cloned();
+        if let Some(sid) = sid {
+            return self.get_movie_info(&sid, image_size).await;
+        }
+        let filter = SearchFilter {
+            keep_noise: true,
+            ..SearchFilter::default()
+        };
+        let (movies, _) = self
+            .search(imdb, ALIAS_SEARCH_LIMIT, image_size, &filter)
+            .await?;
+        for movie in movies.iter() {
+            match self.get_movie_info(&movie.sid, image_size).await {
+                Ok(info) if info.imdb.eq_ignore_ascii_case(imdb) => return Ok(info),
+                Ok(_) => {}
+                Err(err) => println!("获取电影详情失败: {} {:?}", movie.sid, err),
+            }
+        }
+
+        Err(ApiError::with_detail(ErrorCode::SubjectNotFound, imdb).into())
+    }
+
+    /// 获取电影信息，同时返回是否命中缓存、抓取时间与剩余缓存时间
+    pub async fn get_movie_info_meta(
+        &self,
+        sid: &str,
+        image_size: &str,
+    ) -> Result<(MovieInfo, CacheMeta)> {
+        let cache_key = format!("movie_{}_{}", sid, image_size);
+        let hit = MOVIE_CACHE.get(&cache_key).is_some();
+        let info = self.get_movie_info(sid, image_size).await?;
+        let meta = CacheMeta::new(hit, MOVIE_FETCHED_AT.get(&cache_key), MOVIE_CACHE_TTL);
+        Ok((info, meta))
+    }
+
+    /// 解析电影详情页html，不发起网络请求
+    pub fn parse_movie_html(&self, sid: &str, html: &str, image_size: &str) -> Result<MovieInfo> {
+        let document = Vis::load(html).map_err(|e| anyhow::anyhow!("解析页面失败: {}", e))?;
+        let x = document.find("#content");
+
+        let sid = sid.to_string();
+        let name_str = x.find("h1>span:first-child").text().to_string();
+        let cs = self
+            .re_name_math
+            .captures(&name_str)
+            .ok_or_else(|| anyhow::anyhow!("解析电影名称失败: {}", sid))?;
+        let name = cs[1].to_string();
+        let original_name = cs[2].to_string();
+        let (base_name, season_number) = self.parse_season(&name);
+
+        let year_str = x.find("h1>span.year").text().to_string();
+        let year = self.parse_year_for_detail(&year_str);
+
+        let mut rating = x
+            .find("div.rating_self strong.rating_num")
+            .text()
+            .to_string();
+        if rating.is_empty() {
+            rating = "0".to_string();
+        }
+        let votes = x
+            .find("span[property='v:votes']")
+            .text()
+            .trim()
+            .parse::<u32>()
+            .unwrap_or(0);
+        let img_str = x
+            .find("a.nbgnbg>img")
+            .attr("src")
+            .map(|x| x.to_string())
+            .unwrap_or_default();
+        let img = self.get_img_by_size(&img_str, image_size);
+
+        let intro = x.find("div.indent>span").text().trim().replace("©豆瓣", "");
+        // 有“在哪儿看”的在线播放资源
+        let play_sources = x.find("ul.bs a.playBtn").map(|_index, x| {
+            let x = Vis::dom(x);
+            let name = match x.attr("data-cn") {
+                Some(name) => name.to_string(),
+                None => x.text().trim().to_string(),
+            };
+            let href = x.attr("href").map(|x| x.to_string()).unwrap_or_default();
+            PlaySource {
+                name,
+                url: parse_link2(&href),
+            }
+        });
+        let playable = !play_sources.is_empty();
+        // 想看/在看人数，如"1234人在看 / 5678人想看"
+        let interests = x.find(".subject-others-interests-ft").text().to_string();
+        let parse_interest = |re: &Regex| match re.captures(&interests) {
+            Some(cs) => cs[1].parse::<u32>().unwrap_or(0),
+            None => 0,
+        };
+        let wish_count = parse_interest(&self.re_wish);
+        let doing_count = parse_interest(&self.re_doing);
+        let content_rating = self.parse_content_rating(html);
+        let info = x.find("#info").text().to_string();
+        let (
+            director,
+            writer,
+            actor,
+            genre,
+            site,
+            country,
+            language,
+            screen,
+            duration,
+            subname,
+            imdb,
+        ) = self.parse_info(&info);
+
+        let (aka_cn, aka_en, aka_other) = classify_aka(&subname);
+        let release_dates = self.parse_release_dates(&screen);
+        let production_companies = self.parse_companies(&info);
+
+        let celebrities: Vec<Celebrity> =
+            x.find("#celebrities li.celebrity")
+                .first()
+                .map(|_index, x| {
+                    let x = Vis::dom(x);
+                    let id_str = x
+                        .find("div.info a.name")
+                        .attr("href")
+                        .map(|x| x.to_string())
+                        .unwrap_or_default();
+                    let id = self.parse_id(&id_str);
+                    let img_str = x
+                        .find("div.avatar")
+                        .attr("style")
+                        .map(|x| x.to_string())
+                        .unwrap_or_default();
+                    let img = self
+                        .get_img_by_size(self.parse_backgroud_image(&img_str).as_str(), image_size);
+                    let name = x.find("div.info a.name").text().to_string();
+                    let role = x.find("div.info span.role").text().to_string();
+                    let role_type = String::new();
+                    let role_kind = RoleKind::parse(&role);
+
+                    Celebrity {
+                        id,
+                        img,
+                        name,
+                        role_type,
+                        role_kind,
+                        role,
+                        birthplace: None,
+                        works: None,
+                        info: None,
+                    }
+                });
+
+        Ok(MovieInfo {
+            sid,
+            name,
+            original_name,
+            base_name,
+            season_number,
+            rating,
+            votes,
+            img,
+            year,
+            intro,
+            director,
+            writer,
+            actor,
+            genre,
+            site,
+            country,
+            language,
+            screen_normalized: normalize_date(screen.split('/').next().unwrap_or_default()),
+            screen,
+            release_dates,
+            production_companies,
+            duration,
+            subname,
+            aka_cn,
+            aka_en,
+            aka_other,
+            imdb,
+            content_rating,
+            playable,
+            play_sources,
+            wish_count,
+            doing_count,
+            celebrities,
+            palette: None,
+            imdb_rating: None,
+            imdb_votes: None,
+        })
+    }
+
+    pub async fn get_aliases(&self, sid: &str) -> Result<MovieAliases> {
+        let info = self.get_movie_info(sid, "").await?;
+        Ok(info.aliases())
+    }
+
+    /// 用任意译名反查 sid，本地索引中没有时搜索并获取详情后再查找
+    pub async fn find_by_alias(&self, name: &str) -> Result<Option<MovieAliases>> {
+        let key = name.trim().to_lowercase();
+        if key.is_empty() {
+            return Ok(None);
+        }
+        let sid = ALIAS_INDEX.read().unwrap().get(&key).cloned();
+        if let Some(sid) = sid {
+            return Ok(Some(self.get_aliases(&sid).await?));
+        }
+
+        let (movies, _source) = self
+            .search(name, ALIAS_SEARCH_LIMIT, "", &SearchFilter::default())
+            .await?;
+        for movie in movies.iter() {
+            let info = self.get_movie_info(&movie.sid, "").await?;
+            let aliases = info.aliases();
+            if aliases.all().iter().any(|x| x.to_lowercase() == key) {
+                return Ok(Some(aliases));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// 拼装适合字幕站搜索的关键词
+    pub async fn get_subtitle_info(&self, sid: &str) -> Result<SubtitleInfo> {
+        let info = self.get_movie_info(sid, "").await?;
+        let aliases = info.aliases().aliases;
+
+        let mut names = Vec::new();
+        if !info.original_name.is_empty() {
+            names.push(info.original_name.clone());
+        }
+        names.push(info.base_name.clone());
+        let mut keywords = Vec::new();
+        for name in names.iter() {
+            if info.season_number > 0 {
+                keywords.push(format!("{} S{:02}", name, info.season_number));
+            }
+            if !info.year.is_empty() {
+                keywords.push(format!("{} {}", name, info.year));
+            }
+            keywords.push(name.clone());
+        }
+        if !info.imdb.is_empty() {
+            keywords.push(info.imdb.clone());
+        }
+        keywords.extend(aliases.iter().cloned());
+        keywords.dedup();
+
+        Ok(SubtitleInfo {
+            sid: info.sid,
+            imdb: info.imdb,
+            year: info.year,
+            season_number: info.season_number,
+            aliases,
+            keywords,
+        })
+    }
+
+    /// 解析详情页“喜欢的台词”区块，没有该区块时返回空列表
+    pub async fn get_quotes(&self, sid: &str) -> Result<Vec<Quote>> {
+        if let Some(quotes) = QUOTE_CACHE.get(&sid.to_string()) {
+            return Ok(quotes);
+        }
+        let url = format!("{}/subject/{}/", self.client.movie_base(), sid);
+        let res = self.client.get_html(&url).await?.body;
+        let document = Vis::load(&res).unwrap();
+        let mut quotes: Vec<Quote> =
+            document
+                .find("#quotes-section .quote-item")
+                .map(|_index, x| {
+                    let x = Vis::dom(x);
+                    Quote {
+                        text: x.find(".quote-text").text().trim().to_string(),
+                        votes: self.parse_quote_votes(x.find(".quote-votes").text()),
+                    }
+                });
+        quotes.retain(|x| !x.text.is_empty());
+        QUOTE_CACHE.insert(sid.to_string(), quotes.clone()).await;
+
+        Ok(quotes)
+    }
+
+    /// 剧集的集数、单集片长，detail为true时再抓取各集分集页的标题与简介
+    pub async fn get_episodes(&self, sid: &str, detail: bool) -> Result<Episodes> {
+        let cache_key = format!("{}_{}", sid, detail);
+        if let Some(result) = EPISODES_CACHE.get(&cache_key) {
+            return Ok(result);
+        }
+        let url = format!("{}/subject/{}/", self.client.movie_base(), sid);
+        let res = self.client.get_html(&url).await?.body;
+        let (name, info, links) = {
+            let document = Vis::load(&res).unwrap();
+            let name = document
+                .find("#content h1 span:first-child")
+                .text()
+                .trim()
+                .to_string();
+            let info = document.find("#info").text().to_string();
+            let links = document.find(".episode_list a").length() as u32;
+            (name, info, links)
+        };
+        let episode_count = match self.re_episode_count.captures(&info) {
+            Some(x) => x[1].parse::<u32>().unwrap_or(0),
+            None => links,
+        };
+        if episode_count == 0 {
+            return Err(ApiError::with_detail(ErrorCode::NotFound, "episodes").into());
+        }
+        let episode_duration = match self.re_episode_duration.captures(&info) {
+            Some(x) => x[1].trim().to_string(),
+            None => String::new(),
+        };
+
+        let numbers = 1..=episode_count.min(EPISODE_DETAIL_LIMIT);
+        let episodes = if detail {
+            futures::stream::iter(numbers)
+                .map(|number| async move {
+                    match self.get_episode(sid, number).await {
+                        Ok(episode) => episode,
+                        Err(err) => {
+                            println!("获取分集信息失败: {} {} {:?}", sid, number, err);
+                            Episode::new(number)
+                        }
+                    }
+                })
+                .buffered(BATCH_CONCURRENCY)
+                .collect::<Vec<Episode>>()
+                .await
+        } else {
+            numbers.map(Episode::new).collect()
+        };
+
+        let result = Episodes {
+            sid: sid.to_string(),
+            name,
+            episode_count,
+            episode_duration,
+            episodes,
+        };
+        EPISODES_CACHE.insert(cache_key, result.clone()).await;
+
+        Ok(result)
+    }
+
+    /// 分集页 ep-info 中的本集中文名、原名、播放时间与剧情简介
+    async fn get_episode(&self, sid: &str, number: u32) -> Result<Episode> {
+        let url = format!(
+            "{}/subject/{}/episode/{}/",
+            self.client.movie_base(),
+            sid,
+            number
+        );
+        let res = self.client.get_html(&url).await?.body;
+        let document = Vis::load(&res).unwrap();
+        let mut episode = Episode::new(number);
+        document.find("ul.ep-info li").map(|_index, x| {
+            let x = Vis::dom(x);
+            let key = x.find("span.tit").text().trim().trim_end_matches(':').to_string();
+            let all = x.find("span.all").text().trim().to_string();
+            let value = if all.is_empty() {
+                x.text().replacen(x.find("span.tit").text(), "", 1).trim().to_string()
+            } else {
+                all
+            };
+            let value = if value == "暂无" { String::new() } else { value };
+            match key.as_str() {
+                "本集中文名" => episode.title = value,
+                "本集原名" => episode.original_title = value,
+                "播放时间" => episode.air_date = value,
+                "剧情简介" => episode.intro = value,
+                _ => {}
+            }
+        });
+
+        Ok(episode)
+    }
+
+    /// 抓取前几页热门短评，统计高频关键词
+    pub async fn get_comment_keywords(
+        &self,
+        sid: &str,
+        pages: usize,
+        top: usize,
+    ) -> Result<CommentKeywords> {
+        let cache_key = format!("{}_{}_{}", sid, pages, top);
+        if let Some(result) = COMMENT_KEYWORD_CACHE.get(&cache_key) {
+            return Ok(result);
+        }
+        let mut comments = Vec::new();
+        for page in 0..pages {
+            let url = format!(
+                "{}/subject/{}/comments?start={}&limit={}&status=P&sort=new_score",
+                self.client.movie_base(),
+                sid,
+                page * COMMENT_PAGE_SIZE,
+                COMMENT_PAGE_SIZE
+            );
+            let res = self.client.get_html(&url).await?.body;
+            let document = Vis::load(&res).unwrap();
+            let mut list: Vec<String> = document
+                .find("div.comment-item span.short")
+                .map(|_index, x| x.text().trim().to_string());
+            list.retain(|x| !x.is_empty());
+            let done = list.len() < COMMENT_PAGE_SIZE;
+            comments.extend(list);
+            if done {
+                break;
+            }
+        }
+
+        let result = CommentKeywords {
+            sid: sid.to_string(),
+            comments: comments.len(),
+            keywords: top_keywords(&comments, top),
+        };
+        COMMENT_KEYWORD_CACHE.insert(cache_key, result.clone()).await;
+
+        Ok(result)
+    }
+
+    fn parse_quote_votes(&self, text: &str) -> u32 {
+        text.chars()
+            .filter(|c| c.is_ascii_digit())
+            .collect::<String>()
+            .parse::<u32>()
+            .unwrap_or(0)
+    }
+
+    /// 通过详情中的 IMDb ID 抓取 IMDb 家长指导摘要
+    pub async fn get_parental_guide(&self, sid: &str) -> Result<ParentalGuide> {
+        let info = self.get_movie_info(sid, "").await?;
+        if info.imdb.is_empty() {
+            return Err(anyhow::anyhow!("条目没有IMDb ID: {}", sid));
+        }
+        if let Some(guide) = PARENTAL_GUIDE_CACHE.get(&info.imdb) {
+            return Ok(guide);
+        }
+        let url = format!("https://www.imdb.com/title/{}/parentalguide", info.imdb);
+        let res = self
+            .client
+            .get(&url)
+            .header("Referer", "https://www.imdb.com/")
+            .header("Accept-Language", "en-US,en;q=0.9")
+            .send()
+            .await?
+            .error_for_status()?
+            .text()
+            .await?;
+        let document = Vis::load(&res).unwrap();
+
+        let certificate = document
+            .find("#mpaa-rating td:last-child")
+            .text()
+            .trim()
+            .to_string();
+        let categories = PARENTAL_GUIDE_CATEGORIES
+            .iter()
+            .map(|(id, category)| ParentalGuideItem {
+                category: category.to_string(),
+                severity: document
+                    .find(&format!("section#advisory-{} .ipl-status-pill", id))
+                    .first()
+                    .text()
+                    .trim()
+                    .to_string(),
+            })
+            .collect::<Vec<ParentalGuideItem>>();
+
+        let guide = ParentalGuide {
+            sid: sid.to_string(),
+            imdb: info.imdb.clone(),
+            certificate,
+            categories,
+        };
+        PARENTAL_GUIDE_CACHE.insert(info.imdb, guide.clone()).await;
+
+        Ok(guide)
+    }
+
+    /// roles为演职员页的职责(见parse_celebrity_roles)，按页面顺序取前count位
+    pub async fn get_celebrities(
+        &self,
+        sid: &str,
+        image_size: &str,
+        count: usize,
+        roles: &[&str],
+    ) -> Result<Vec<Celebrity>> {
+        let groups = self.get_celebrity_groups(sid, image_size).await?;
+        let celebrities = groups
+            .into_iter()
+            .flat_map(|x| x.celebrities)
+            .filter(|x| roles.iter().any(|role| x.role_type.contains(role)))
+            .take(count)
+            .collect::<Vec<Celebrity>>();
+
+        Ok(celebrities)
+    }
+
+    /// 分页获取全部演职员，按导演/编剧/演员/配音等区块分组返回
+    pub async fn get_celebrity_page(
+        &self,
+        sid: &str,
+        image_size: &str,
+        start: usize,
+        limit: usize,
+    ) -> Result<CelebrityPage> {
+        let groups = self.get_celebrity_groups(sid, image_size).await?;
+        let total = groups.iter().map(|x| x.celebrities.len()).sum();
+        let mut skip = start;
+        let mut remain = limit;
+        let mut page = Vec::new();
+        for group in groups.into_iter() {
+            if remain == 0 {
+                break;
+            }
+            let len = group.celebrities.len();
+            if skip >= len {
+                skip -= len;
+                continue;
+            }
+            let celebrities = group
+                .celebrities
+                .into_iter()
+                .skip(skip)
+                .take(remain)
+                .collect::<Vec<Celebrity>>();
+            skip = 0;
+            remain -= celebrities.len();
+            page.push(CelebrityGroup {
+                name: group.name,
+                celebrities,
+            });
+        }
+
+        Ok(CelebrityPage {
+            sid: sid.to_string(),
+            start,
+            limit,
+            total,
+            groups: page,
+        })
+    }
+
+    async fn get_celebrity_groups(
+        &self,
+        sid: &str,
+        image_size: &str,
+    ) -> Result<Vec<CelebrityGroup>> {
+        let url = format!("{}/subject/{}/celebrities", self.client.movie_base(), sid);
+        let res = self.client.get_html(&url).await?.body;
+        let document = Vis::load(&res).unwrap();
+        let x = document.find("#content");
+
+        let mut groups = x.find("div.list-wrapper").map(|_index, x| {
+            let x = Vis::dom(x);
+            let name = x
+                .find("h2")
+                .text()
+                .split_whitespace()
+                .next()
+                .unwrap_or("")
+                .to_string();
+            CelebrityGroup {
+                name,
+                celebrities: self.parse_celebrity_list(&x, image_size),
+            }
+        });
+        // 没有分组区块时，全部演职员作为一组
+        if groups.is_empty() {
+            groups.push(CelebrityGroup {
+                name: String::new(),
+                celebrities: self.parse_celebrity_list(&x, image_size),
+            });
+        }
+
+        Ok(groups)
+    }
+
+    fn parse_celebrity_list(
+        &self,
+        x: &visdom::types::Elements,
+        image_size: &str,
+    ) -> Vec<Celebrity> {
+        x.find("ul.celebrities-list li.celebrity").map(|_index, x| {
+            let x = Vis::dom(x);
+            let id_str = x.find("div.info a.name").attr("href").unwrap().to_string();
+            let id = self.parse_id(&id_str);
+            let img_str = x.find("div.avatar").attr("style").unwrap().to_string();
+            let img =
+                self.get_img_by_size(self.parse_backgroud_image(&img_str).as_str(), image_size);
+            let name = x
+                .find("div.info a.name")
+                .text()
+                .split_whitespace()
+                .next()
+                .unwrap_or("")
+                .to_string();
+            let mut role = match self.re_role.captures(x.find("div.info span.role").text()) {
+                Some(x) => x.get(1).unwrap().as_str().trim().to_string(),
+                None => String::new(),
+            };
+            let role_type = x
+                .find("div.info span.role")
+                .text()
+                .split_whitespace()
+                .next()
+                .unwrap_or("")
+                .to_string();
+            if role.is_empty() {
+                role = role_type.clone();
+            }
+            let role_kind = RoleKind::parse(&role_type);
+
+            Celebrity {
+                id,
+                img,
+                name,
+                role_type,
+                role_kind,
+                role,
+                birthplace: None,
+                works: None,
+                info: None,
+            }
+        })
+    }
+
+    /// 下载封面小图提取主色调，失败时不输出palette
+    pub async fn add_palette(&self, info: &mut MovieInfo) {
+        if info.img.is_empty() {
+            return;
+        }
+        if let Some(palette) = PALETTE_CACHE.get(&info.img) {
+            info.palette = Some(palette);
+            return;
+        }
+        // 只需缩略图统计颜色，下载s尺寸的封面即可
+        let url = self.re_img_size.replace(&info.img, "/view/$1/s/").to_string();
+        let data = match self.client.get(&url).send().await {
+            Ok(res) => res.bytes().await,
+            Err(err) => Err(err),
+        };
+        // 图片解码与统计是CPU密集操作，放到阻塞线程池中执行
+        let palette = match data {
+            Ok(data) => parse_blocking(move || palette::extract(&data, PALETTE_SIZE)).await,
+            Err(err) => Err(err.into()),
+        };
+        match palette {
+            Ok(palette) => {
+                PALETTE_CACHE.insert(info.img.clone(), palette.clone()).await;
+                info.palette = Some(palette);
+            }
+            Err(err) => println!("提取封面主色调失败: {} {:?}", info.img, err),
+        }
+    }
+
+    /// 配置了OMDb api key时补充IMDb评分，失败时不输出imdb_rating/imdb_votes
+    pub async fn add_imdb_rating(&self, info: &mut MovieInfo) {
+        if self.omdb_api_key.is_empty() || info.imdb.is_empty() {
+            return;
+        }
+        let rating = match IMDB_RATING_CACHE.get(&info.imdb) {
+            Some(rating) => rating,
+            None => match self.get_omdb_rating(&info.imdb).await {
+                Ok(rating) => {
+                    IMDB_RATING_CACHE.insert(info.imdb.clone(), rating.clone()).await;
+                    rating
+                }
+                Err(err) => {
+                    println!("获取IMDb评分失败: {} {:?}", info.imdb, err);
+                    return;
+                }
+            },
+        };
+        info.imdb_rating = rating.rating;
+        info.imdb_votes = rating.votes;
+    }
+
+    async fn get_omdb_rating(&self, imdb: &str) -> Result<ImdbRating> {
+        let res = self
+            .client
+            .get(OMDB_URL)
+            .query(&[("i", imdb), ("apikey", self.omdb_api_key.as_str())])
+            .timeout(OMDB_TIMEOUT)
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<serde_json::Value>()
+            .await?;
+        if res["Response"].as_str() != Some("True") {
+            let err = res["Error"].as_str().unwrap_or("unknown error");
+            return Err(anyhow::anyhow!("{}", err));
+        }
+        // 没有评分时为N/A
+        let rating = res["imdbRating"].as_str().and_then(|x| x.parse::<f32>().ok());
+        let votes = res["imdbVotes"]
+            .as_str()
+            .and_then(|x| x.replace(',', "").parse::<u32>().ok());
+
+        Ok(ImdbRating { rating, votes })
+    }
+
+    /// 详情页无法区分分类，按季数与搜索结果中的分类判断是否为剧集
+    pub fn is_tv(&self, info: &MovieInfo) -> bool {
+        info.season_number > 0
+            || LOCAL_INDEX
+                .read()
+                .unwrap()
+                .get(&info.sid)
+                .map(|x| x.cat == "电视剧")
+                .unwrap_or(false)
+    }
+
+    /// 并发获取前几位演职员详情，内联出生地与代表作
+    pub async fn expand_celebrities(&self, info: &mut MovieInfo) {
+        let size = info.celebrities.len().min(EXPAND_CELEBRITY_SIZE);
+        let ids: Vec<String> = info.celebrities[..size]
+            .iter()
+            .map(|x| x.id.clone())
+            .collect();
+        let details = self.get_celebrity_batch(&ids, None).await.data;
+        for (celebrity, detail) in info.celebrities.iter_mut().zip(details) {
+            if let Some(detail) = detail {
+                celebrity.birthplace = Some(detail.birthplace);
+                celebrity.works = Some(detail.works);
+            }
+        }
+    }
+
+    /// 并发获取前size位影人详情并内联，获取失败的不输出info
+    pub async fn inline_celebrity_info(&self, celebrities: &mut [Celebrity], size: usize) {
+        let size = celebrities.len().min(size);
+        let ids: Vec<String> = celebrities[..size].iter().map(|x| x.id.clone()).collect();
+        let details = self.get_celebrity_batch(&ids, None).await.data;
+        for (celebrity, detail) in celebrities.iter_mut().zip(details) {
+            celebrity.info = detail;
+        }
+    }
+
+    /// 受控并发批量获取影人信息，结果按请求顺序返回，获取失败或超出预算的为null
+    pub async fn get_celebrity_batch(
+        &self,
+        ids: &[String],
+        deadline: Option<Instant>,
+    ) -> Partial<Option<CelebrityInfo>> {
+        let list = futures::stream::iter(ids.iter())
+            .map(|id| async move {
+                match within(deadline, self.get_celebrity(id)).await {
+                    Some(Ok(info)) => (Some(info), None),
+                    Some(Err(err)) => {
+                        println!("获取影人信息失败: {} {:?}", id, err);
+                        (None, None)
+                    }
+                    None => (None, Some(id.clone())),
+                }
+            })
+            .buffered(BATCH_CONCURRENCY)
+            .collect::<Vec<(Option<CelebrityInfo>, Option<String>)>>()
+            .await;
+        let mut result = Partial::default();
+        for (info, missing) in list {
+            result.data.push(info);
+            if let Some(id) = missing {
+                result.incomplete = true;
+                result.missing.push(id);
+            }
+        }
+
+        result
+    }
+
+    pub async fn get_celebrity(&self, id: &str) -> Result<CelebrityInfo> {
+        let cache_key = id.to_string();
+        if CELEBRITY_CACHE.get(&cache_key).is_some() {
+            return Ok(CELEBRITY_CACHE.get(&cache_key).unwrap());
+        }
+        let url = format!("{}/celebrity/{}/", self.client.movie_base(), id);
+        let res = self.client.get_html(&url).await?.body;
+        let info = {
+            let douban = self.clone();
+            let id = id.to_string();
+            parse_blocking(move || douban.parse_celebrity_html(&id, &res)).await?
+        };
+        metrics::validate("celebrity", &info);
+        add_birthday_index(&info);
+        CELEBRITY_CACHE.insert(cache_key, info.clone()).await;
+
+        Ok(info)
+    }
+
+    /// 按年份聚合影人的全部作品(按时间排序，最多200部)，标注每部作品中的职责
+    pub async fn get_celebrity_timeline(&self, id: &str) -> Result<CelebrityTimeline> {
+        if let Some(timeline) = TIMELINE_CACHE.get(&id.to_string()) {
+            return Ok(timeline);
+        }
+        let re_year = Regex::new(r"\((\d{4})\)").unwrap();
+        let re_roles = Regex::new(r"\[\s*(.+?)\s*\]").unwrap();
+        let mut works: Vec<(String, TimelineWork)> = Vec::new();
+        for page in 0..TIMELINE_MAX_PAGES {
+            let url = format!(
+                "{}/celebrity/{}/movies?start={}&format=pic&sortby=time",
+                self.client.movie_base(),
+                id,
+                page * TIMELINE_PAGE_SIZE
+            );
+            let res = self.client.get_html(&url).await?.body;
+            let document = Vis::load(&res).unwrap();
+            let list = document.find(".grid_view > ul > li h6").map(|_index, x| {
+                let x = Vis::dom(x);
+                let href = x.find("a").attr("href").map(|x| x.to_string());
+                let text = x.text();
+                let year = match re_year.captures(text) {
+                    Some(cs) => cs[1].to_string(),
+                    None => String::new(),
+                };
+                let roles = match re_roles.captures(text) {
+                    Some(cs) => cs[1]
+                        .split('/')
+                        .map(|x| x.trim().to_string())
+                        .filter(|x| !x.is_empty())
+                        .collect::<Vec<String>>(),
+                    None => Vec::new(),
+                };
+                let work = TimelineWork {
+                    sid: self.parse_id(&href.unwrap_or_default()),
+                    name: x.find("a").text().trim().to_string(),
+                    role_kinds: roles.iter().filter_map(|x| RoleKind::parse(x)).collect(),
+                    roles,
+                };
+                (year, work)
+            });
+            let done = list.len() < TIMELINE_PAGE_SIZE;
+            works.extend(list.into_iter().filter(|(_, x)| !x.sid.is_empty()));
+            if done {
+                break;
+            }
+        }
+
+        // 按年份从新到旧，没有年份的排在最后
+        let mut years: Vec<TimelineYear> = Vec::new();
+        for (year, work) in works.into_iter() {
+            match years.iter_mut().find(|x| x.year == year) {
+                Some(x) => x.works.push(work),
+                None => years.push(TimelineYear {
+                    year,
+                    works: vec![work],
+                }),
+            }
+        }
+        years.sort_by(|a, b| match (a.year.is_empty(), b.year.is_empty()) {
+            (true, false) => std::cmp::Ordering::Greater,
+            (false, true) => std::cmp::Ordering::Less,
+            _ => b.year.cmp(&a.year),
+        });
+        let timeline = CelebrityTimeline {
+            id: id.to_string(),
+            total: years.iter().map(|x| x.works.len()).sum(),
+            years,
+        };
+        TIMELINE_CACHE.insert(id.to_string(), timeline.clone()).await;
+
+        Ok(timeline)
+    }
+
+    /// 解析影人详情页html，不发起网络请求
+    pub fn parse_celebrity_html(&self, id: &str, html: &str) -> Result<CelebrityInfo> {
+        let document = Vis::load(html).map_err(|e| anyhow::anyhow!("解析页面失败: {}", e))?;
+        let x = document.find("#content");
+        let id = id.to_string();
+        let img = x
+            .find("#headline .nbg img")
+            .attr("src")
+            .map(|x| x.to_string())
+            .unwrap_or_default();
+        let name = x.find("h1").text().to_string();
+        let mut intro = x.find("#intro span.all").text().trim().to_string();
+        if intro.is_empty() {
+            intro = x.find("#intro div.bd").text().trim().to_string();
+        }
+
+        let info = x.find("div.info").text().to_string();
+        let (gender, constellation, birthdate, birthplace, role, nickname, family, imdb) =
+            self.parse_celebrity_info(&info);
+
+        // 代表作(最受好评的作品)
+        let works: Vec<CelebrityWork> = x
+            .find("#best_movies li div.info a")
+            .map(|_index, x| {
+                let x = Vis::dom(x);
+                let sid_str = x.attr("href").map(|x| x.to_string()).unwrap_or_default();
+                CelebrityWork {
+                    sid: self.parse_id(&sid_str),
+                    name: x.text().trim().to_string(),
+                }
+            })
+            .into_iter()
+            .filter(|x| !x.sid.is_empty())
+            .collect();
+
+        Ok(CelebrityInfo {
+            id,
+            img,
+            name,
+            role,
+            intro,
+            gender,
+            constellation,
+            birthdate_normalized: normalize_date(&birthdate),
             birthdate,
             birthplace,
             nickname,
             imdb,
             family,
+            works,
+        })
+    }
+
+    /// 获取壁纸，没有壁纸或需要登录查看时返回带原因的空结果
+    /// photo_type: W-壁纸 R-海报 S-剧照，count为0时只返回第一页，否则自动翻页直到取够数量
+    pub async fn get_wallpaper(
+        &self,
+        sid: &str,
+        photo_type: &str,
+        start: usize,
+        count: usize,
+    ) -> Result<Wallpaper> {
+        let mut photos = Vec::new();
+        let mut page_start = if count == 0 {
+            0
+        } else {
+            start / PHOTO_PAGE_SIZE * PHOTO_PAGE_SIZE
+        };
+        let mut has_more = loop {
+            let page = match self.get_photos_page(sid, photo_type, page_start).await {
+                Ok(page) => page,
+                Err(err) if page_start == 0 && is_need_login(&err) => {
+                    return Ok(Wallpaper {
+                        photos,
+                        start,
+                        has_more: false,
+                        reason: Some("need_login"),
+                    })
+                }
+                Err(err) => return Err(err),
+            };
+            let done = page.len() < PHOTO_PAGE_SIZE;
+            photos.extend(page);
+            page_start += PHOTO_PAGE_SIZE;
+            if count == 0 || done || page_start >= start + count {
+                // 最后一页已满时豆瓣可能还有下一页
+                break count > 0 && !done;
+            }
+        };
+        if count > 0 {
+            let skip = start % PHOTO_PAGE_SIZE;
+            has_more = has_more || photos.len() > skip + count;
+            photos = photos.into_iter().skip(skip).take(count).collect();
+        }
+        let reason = if photos.is_empty() && start == 0 {
+            Some("no_photos")
+        } else {
+            None
+        };
+
+        Ok(Wallpaper {
+            photos,
+            start,
+            has_more,
+            reason,
+        })
+    }
+
+    pub async fn get_images(&self, sid: &str, limit: usize) -> Result<MovieImages> {
+        let (mut posters, mut stills, mut backdrops) = futures::try_join!(
+            self.get_photos(sid, "R"),
+            self.get_photos(sid, "S"),
+            self.get_photos(sid, "W")
+        )?;
+        if limit > 0 {
+            posters.truncate(limit);
+            stills.truncate(limit);
+            backdrops.truncate(limit);
+        }
+
+        Ok(MovieImages {
+            posters,
+            stills,
+            backdrops,
+        })
+    }
+
+    /// 获取剧集指定季条目的海报列表，sid可为任意一季的条目
+    pub async fn get_season_posters(
+        &self,
+        sid: &str,
+        season: u32,
+        limit: usize,
+    ) -> Result<SeasonPosters> {
+        let season_sid = self.get_season_sid(sid, season).await?;
+        let mut posters = self.get_photos(&season_sid, "R").await?;
+        if limit > 0 {
+            posters.truncate(limit);
+        }
+
+        Ok(SeasonPosters {
+            sid: season_sid,
+            season,
+            posters,
+        })
+    }
+
+    /// 从详情页的季选择框(#season)中查找指定季的条目id
+    async fn get_season_sid(&self, sid: &str, season: u32) -> Result<String> {
+        let url = format!("{}/subject/{}/", self.client.movie_base(), sid);
+        let res = self.client.get_html(&url).await?.body;
+        let document = Vis::load(&res).unwrap();
+        let options = document.find("select#season option");
+        if options.is_empty() {
+            // 只有一季的剧集没有季选择框
+            let (_, number) =
+                self.parse_season(document.find("#content h1>span:first-child").text());
+            if season == number.max(1) {
+                return Ok(sid.to_string());
+            }
+        }
+        let mut season_sid = None;
+        options.map(|_index, x| {
+            let x = Vis::dom(x);
+            if x.text().trim().parse::<u32>().ok() == Some(season) {
+                season_sid = x.attr("value").map(|v| v.to_string());
+            }
+        });
+
+        season_sid.ok_or_else(|| {
+            ApiError::with_detail(ErrorCode::NotFound, format!("season: {}", season)).into()
         })
     }
 
-    pub async fn get_wallpaper(&self, sid: &str) -> Result<Vec<Photo>> {
-        let cache_key = sid.to_string();
+    /// photo_type: R-海报 S-剧照 W-壁纸
+    async fn get_photos(&self, sid: &str, photo_type: &str) -> Result<Vec<Photo>> {
+        self.get_photos_page(sid, photo_type, 0).await
+    }
+
+    /// 获取一页(30张)图片，第一页的缓存key与导出的缓存保持一致
+    async fn get_photos_page(
+        &self,
+        sid: &str,
+        photo_type: &str,
+        start: usize,
+    ) -> Result<Vec<Photo>> {
+        let cache_key = if start == 0 {
+            format!("{}_{}", sid, photo_type)
+        } else {
+            format!("{}_{}_{}", sid, photo_type, start)
+        };
         if PHOTO_CACHE.get(&cache_key).is_some() {
             return Ok(PHOTO_CACHE.get(&cache_key).unwrap());
         }
-        let url = format!("https://movie.douban.com/subject/{}/photos?type=W&start=0&sortby=size&size=a&subtype=a", sid);
-        let res = self
-            .client
-            .get(url)
-            .send()
-            .await?
-            .error_for_status()
-            .unwrap();
-
-        let res = res.text().await?;
+        let url = format!(
+            "{}/subject/{}/photos?type={}&start={}&sortby=size&size=a&subtype=a",
+            self.client.movie_base(),
+            sid,
+            photo_type,
+            start
+        );
+        let res = self.client.get_html(&url).await?.body;
         let document = Vis::load(&res).unwrap();
-        let wallpapers: Vec<Photo> = document.find(".poster-col3>li").map(|_index, x| {
+        // 没有图片列表而只有登录提示时，不能当作没有图片缓存
+        if document.find(".poster-col3").is_empty()
+            && !document.find("form[action*='login']").is_empty()
+        {
+            return Err(ApiError::with_detail(ErrorCode::NeedLogin, url).into());
+        }
+        let img_host = self.client.img_host();
+        let photos: Vec<Photo> = document.find(".poster-col3>li").map(|_index, x| {
             let x = Vis::dom(x);
 
             let id = x.attr("data-id").unwrap().to_string();
-            let small = format!("https://img2.doubanio.com/view/photo/s/public/p{}.jpg", id);
-            let medium = format!("https://img2.doubanio.com/view/photo/m/public/p{}.jpg", id);
-            let large = format!("https://img2.doubanio.com/view/photo/l/public/p{}.jpg", id);
+            let small = photo_url(&img_host, &id, "s");
+            let medium = photo_url(&img_host, &id, "m");
+            let large = photo_url(&img_host, &id, "l");
+            let raw = photo_url(&img_host, &id, "raw");
             let size = x.find("div.prop").text().trim().to_string();
             let mut width = String::new();
             let mut height = String::new();
@@ -423,22 +2565,23 @@ impl Douban {
                 small,
                 medium,
                 large,
+                raw,
                 size,
                 width,
                 height,
             }
         });
 
-        PHOTO_CACHE.insert(cache_key, wallpapers.clone()).await;
-        Ok(wallpapers)
+        PHOTO_CACHE.insert(cache_key, photos.clone()).await;
+        Ok(photos)
     }
 
     pub async fn proxy_img(&self, url: &str) -> Result<reqwest::Response> {
-        Ok(self.client.get(url).send().await.unwrap())
+        Ok(self.client.get(url).send().await?)
     }
 
     fn parse_year(&self, text: String) -> String {
-        text.split('/').last().unwrap().trim().to_string()
+        text.split('/').next_back().unwrap().trim().to_string()
     }
 
     fn parse_year_for_detail(&self, text: &str) -> String {
@@ -450,6 +2593,61 @@ impl Douban {
         year
     }
 
+    fn parse_votes(&self, text: &str) -> u32 {
+        match self.re_votes.captures(text) {
+            Some(x) => x[1].parse::<u32>().unwrap_or(0),
+            None => 0,
+        }
+    }
+
+    /// 尽力解析info中的出品/制片/发行公司，去重后按出现顺序返回
+    fn parse_companies(&self, text: &str) -> Vec<String> {
+        let mut companies: Vec<String> = Vec::new();
+        for cs in self.re_company.captures_iter(text) {
+            for name in cs[1].split('/').map(|x| x.trim()) {
+                if !name.is_empty() && !companies.iter().any(|x| x == name) {
+                    companies.push(name.to_string());
+                }
+            }
+        }
+
+        companies
+    }
+
+    /// 解析上映日期为ISO格式(YYYY-MM-DD，缺少日/月时为YYYY-MM/YYYY)，跳过不合法的日期
+    fn parse_release_dates(&self, screen: &str) -> Vec<ReleaseDate> {
+        screen
+            .split('/')
+            .filter_map(|part| {
+                let cs = self.re_release_date.captures(part.trim())?;
+                Some(ReleaseDate {
+                    date: parse_date(&cs[1])?,
+                    region: cs
+                        .get(2)
+                        .map(|x| x.as_str().trim().to_string())
+                        .unwrap_or_default(),
+                })
+            })
+            .collect()
+    }
+
+    fn parse_content_rating(&self, text: &str) -> Option<String> {
+        self.re_content_rating
+            .captures(text)
+            .map(|x| x.get(2).unwrap().as_str().trim().to_string())
+            .filter(|x| !x.is_empty())
+    }
+
+    fn parse_season(&self, name: &str) -> (String, u32) {
+        match self.re_season.captures(name) {
+            Some(x) => (
+                x.get(1).unwrap().as_str().trim().to_string(),
+                parse_chinese_number(x.get(2).unwrap().as_str()),
+            ),
+            None => (name.trim().to_string(), 0),
+        }
+    }
+
     fn parse_sid(&self, text: &str) -> String {
         let mut sid = String::new();
         for cap in self.re_sid.captures_iter(text) {
@@ -581,87 +2779,787 @@ impl Douban {
             None => String::new(),
         };
 
-        let re_constellation = Regex::new(r"星座: \n(.+?)\n").unwrap();
-        let constellation = match re_constellation.captures(text) {
-            Some(x) => x.get(1).unwrap().as_str().trim().to_string(),
-            None => String::new(),
-        };
+        let re_constellation = Regex::new(r"星座: \n(.+?)\n").unwrap();
+        let constellation = match re_constellation.captures(text) {
+            Some(x) => x.get(1).unwrap().as_str().trim().to_string(),
+            None => String::new(),
+        };
+
+        let re_birthdate = Regex::new(r"出生日期: \n(.+?)\n").unwrap();
+        let mut birthdate = match re_birthdate.captures(text) {
+            Some(x) => x.get(1).unwrap().as_str().trim().to_string(),
+            None => String::new(),
+        };
+
+        let re_lifedate = Regex::new(r"生卒日期: \n(.+?) 至").unwrap();
+        let lifedate = match re_lifedate.captures(text) {
+            Some(x) => x.get(1).unwrap().as_str().trim().to_string(),
+            None => String::new(),
+        };
+        if birthdate.is_empty() {
+            birthdate = lifedate.clone();
+        }
+
+        let re_birthplace = Regex::new(r"出生地: \n(.+?)\n").unwrap();
+        let birthplace = match re_birthplace.captures(text) {
+            Some(x) => x.get(1).unwrap().as_str().trim().to_string(),
+            None => String::new(),
+        };
+
+        let re_role = Regex::new(r"职业: \n(.+?)\n").unwrap();
+        let role = match re_role.captures(text) {
+            Some(x) => x.get(1).unwrap().as_str().trim().to_string(),
+            None => String::new(),
+        };
+
+        let re_nickname = Regex::new(r"更多外文名: \n(.+?)\n").unwrap();
+        let nickname = match re_nickname.captures(text) {
+            Some(x) => x.get(1).unwrap().as_str().trim().to_string(),
+            None => String::new(),
+        };
+
+        let re_family = Regex::new(r"家庭成员: \n(.+?)\n").unwrap();
+        let family = match re_family.captures(text) {
+            Some(x) => x.get(1).unwrap().as_str().trim().to_string(),
+            None => String::new(),
+        };
+
+        let re_imdb = Regex::new(r"imdb编号: \n(.+?)\n").unwrap();
+        let imdb = match re_imdb.captures(text) {
+            Some(x) => x.get(1).unwrap().as_str().trim().to_string(),
+            None => String::new(),
+        };
+
+        (
+            gender,
+            constellation,
+            birthdate,
+            birthplace,
+            role,
+            nickname,
+            family,
+            imdb,
+        )
+    }
+
+    fn get_img_by_size(&self, url: &str, image_size: &str) -> String {
+        // 替换为当前最快的图片域名
+        let mut img_url = self
+            .re_img_host
+            .replace(url, self.client.img_host().as_str())
+            .to_string();
+
+        // 改变图片大小，s或不传时保持原图地址
+        if let Some(size) = normalize_image_size(image_size) {
+            if size != "s" {
+                img_url = self
+                    .re_img_size
+                    .replace(&img_url, format!("/view/$1/{}/", size).as_str())
+                    .to_string();
+            }
+        }
+
+        img_url
+    }
+}
+
+/// 豆瓣图床尺寸档位 s/m/l/raw，传入数字时按宽度映射到能满足的最小档位
+fn normalize_image_size(image_size: &str) -> Option<&'static str> {
+    match image_size.trim() {
+        "s" => Some("s"),
+        "m" => Some("m"),
+        "l" => Some("l"),
+        "raw" => Some("raw"),
+        x => match x.trim_end_matches("px").parse::<u32>() {
+            Ok(0) | Err(_) => None,
+            Ok(1..=200) => Some("s"),
+            Ok(201..=600) => Some("m"),
+            Ok(601..=1500) => Some("l"),
+            Ok(_) => Some("raw"),
+        },
+    }
+}
+
+/// 豆瓣跳转链接 https://www.douban.com/link2/?url=xxx 还原为原始地址
+fn parse_link2(href: &str) -> String {
+    match href.split_once("url=") {
+        Some((_, url)) if href.contains("/link2/") => {
+            let url = url.split('&').next().unwrap_or("");
+            urlencoding::decode(url)
+                .map(|x| x.to_string())
+                .unwrap_or_else(|_| url.to_string())
+        }
+        _ => href.to_string(),
+    }
+}
+
+fn photo_url(img_host: &str, id: &str, size: &str) -> String {
+    format!(
+        "https://{}/view/photo/{}/public/p{}.jpg",
+        img_host, size, id
+    )
+}
+
+/// 记录票房榜历史，并按上一期排名填充last_rank与change
+fn add_box_office_history(period: &str, items: &mut [BoxOfficeItem]) {
+    let mut history = BOX_OFFICE_HISTORY.write().unwrap();
+    // 榜单周期变化时，当前一期成为上一期
+    if history.last().map(|x| x.period != period).unwrap_or(true) {
+        history.push(BoxOffice {
+            period: period.to_string(),
+            items: items.to_vec(),
+        });
+        if history.len() > 2 {
+            history.remove(0);
+        }
+    }
+    if history.len() == 2 {
+        for item in items.iter_mut() {
+            item.last_rank = history[0]
+                .items
+                .iter()
+                .find(|x| x.sid == item.sid)
+                .map(|x| x.rank);
+            item.change = item.last_rank.map(|x| x as i32 - item.rank as i32);
+        }
+    }
+}
+
+fn add_birthday_index(info: &CelebrityInfo) {
+    // 只记录完整的年月日
+    let birthdate = match info.birthdate.get(..10) {
+        Some(x) if x.len() == 10 && x.as_bytes()[4] == b'-' && x.as_bytes()[7] == b'-' => x,
+        _ => return,
+    };
+    let mut index = BIRTHDAY_INDEX.write().unwrap();
+    if index.len() >= LOCAL_INDEX_SIZE && !index.contains_key(&info.id) {
+        return;
+    }
+    index.insert(
+        info.id.clone(),
+        CelebrityBirthday {
+            id: info.id.clone(),
+            name: info.name.clone(),
+            img: info.img.clone(),
+            birthdate: birthdate.to_string(),
+        },
+    );
+}
+
+fn add_local_index(list: &[Movie]) {
+    let mut index = LOCAL_INDEX.write().unwrap();
+    for item in list.iter() {
+        if index.len() >= LOCAL_INDEX_SIZE && !index.contains_key(&item.sid) {
+            break;
+        }
+        // 详情页与移动站无法区分分类，不带评价人数的数据源也不应覆盖已有人数，保留已索引的值
+        let prev = index.get(&item.sid);
+        let cat = match prev {
+            Some(x) if item.cat.is_empty() => x.cat.clone(),
+            _ => item.cat.clone(),
+        };
+        let votes = match prev {
+            Some(x) if item.votes == 0 => x.votes,
+            _ => item.votes,
+        };
+        index.insert(
+            item.sid.clone(),
+            Movie {
+                cat_kind: CatKind::parse(&cat),
+                cat,
+                votes,
+                ..item.clone()
+            },
+        );
+    }
+}
+
+fn add_alias_index(aliases: &MovieAliases) {
+    let mut index = ALIAS_INDEX.write().unwrap();
+    for name in aliases.all().iter() {
+        index.insert(name.to_lowercase(), aliases.sid.clone());
+    }
+}
+
+/// 评分区间，8.5 => 8-9，无评分为unrated
+fn rating_bucket(rating: &str) -> String {
+    match rating.trim().parse::<f32>() {
+        Ok(x) if x > 0.0 => {
+            let from = (x.floor() as u32).min(9);
+            format!("{}-{}", from, from + 1)
+        }
+        _ => "unrated".to_string(),
+    }
+}
+
+/// 按/分隔的名字逐个计数
+fn count_names(counts: &mut HashMap<String, usize>, names: &str) {
+    for name in names.split('/').map(|x| x.trim()).filter(|x| !x.is_empty()) {
+        *counts.entry(name.to_string()).or_insert(0) += 1;
+    }
+}
+
+/// by_count为true时按数量从多到少排序，否则按名称排序
+fn sorted_counts(counts: HashMap<String, usize>, by_count: bool, top: usize) -> Vec<StatCount> {
+    let mut list = counts
+        .into_iter()
+        .map(|(name, count)| StatCount { name, count })
+        .collect::<Vec<StatCount>>();
+    if by_count {
+        list.sort_by(|a, b| b.count.cmp(&a.count).then(a.name.cmp(&b.name)));
+    } else {
+        list.sort_by(|a, b| a.name.cmp(&b.name));
+    }
+    list.truncate(top);
+
+    list
+}
+
+/// 是否为豆瓣返回的404
+fn is_not_found(err: &anyhow::Error) -> bool {
+    err.downcast_ref::<reqwest::Error>()
+        .and_then(|x| x.status())
+        .map(|x| x.as_u16() == 404)
+        .unwrap_or(false)
+}
+
+/// 是否为豆瓣要求登录的错误
+fn is_need_login(err: &anyhow::Error) -> bool {
+    err.downcast_ref::<ApiError>()
+        .map(|x| x.code == ErrorCode::NeedLogin)
+        .unwrap_or(false)
+}
+
+/// 解析年代，支持 1990s、90s、1990，两位数的00s/10s/20s视为2000年后，返回起止年份
+fn parse_decade(text: &str) -> Option<(u32, u32)> {
+    let text = text.trim().trim_end_matches(['s', '年', '代']);
+    let year = text.parse::<u32>().ok()?;
+    let from = match year {
+        0..=29 if year % 10 == 0 => 2000 + year,
+        30..=99 if year % 10 == 0 => 1900 + year,
+        1000..=9999 => year / 10 * 10,
+        _ => return None,
+    };
+
+    Some((from, from + 9))
+}
+
+/// 在阻塞线程池中解析大页面，避免CPU密集的html解析占用异步worker
+async fn parse_blocking<T, F>(parse: F) -> Result<T>
+where
+    F: FnOnce() -> Result<T> + Send + 'static,
+    T: Send + 'static,
+{
+    tokio::task::spawn_blocking(parse).await?
+}
+
+/// 在截止时间前执行，超时返回None
+async fn within<F: Future>(deadline: Option<Instant>, fut: F) -> Option<F::Output> {
+    match deadline {
+        Some(deadline) => tokio::time::timeout_at(deadline, fut).await.ok(),
+        None => Some(fut.await),
+    }
+}
+
+/// 解析逗号分隔的职责参数，如director,actor,writer，有无法识别的职责时返回None
+pub fn parse_celebrity_roles(text: &str) -> Option<Vec<&'static str>> {
+    text.split(',')
+        .map(|x| x.trim().to_lowercase())
+        .filter(|x| !x.is_empty())
+        .map(|x| {
+            CELEBRITY_ROLES
+                .iter()
+                .find(|(name, _)| *name == x)
+                .map(|(_, role)| *role)
+        })
+        .collect()
+}
+
+/// 解析降级链配置，格式: source[:timeout_secs],...
+fn parse_search_strategy(text: &str) -> Vec<(SearchSource, Duration)> {
+    let mut strategy = Vec::new();
+    for item in text.split(',') {
+        let mut parts = item.trim().splitn(2, ':');
+        let name = parts.next().unwrap_or("").trim();
+        let source = match name {
+            "suggest" => SearchSource::Suggest,
+            "web" => SearchSource::Web,
+            "mobile" => SearchSource::Mobile,
+            "local" => SearchSource::Local,
+            _ => {
+                println!("忽略未知的搜索数据源: {}", name);
+                continue;
+            }
+        };
+        let timeout = parts
+            .next()
+            .and_then(|x| x.trim().parse::<u64>().ok())
+            .unwrap_or(DEFAULT_SEARCH_TIMEOUT);
+        strategy.push((source, Duration::from_secs(timeout)));
+    }
+    if strategy.is_empty() {
+        strategy.push((
+            SearchSource::Web,
+            Duration::from_secs(DEFAULT_SEARCH_TIMEOUT),
+        ));
+    }
+
+    strategy
+}
+
+/// 又名按字符集分为中文、英文与其它语言
+fn classify_aka(subname: &str) -> (Vec<String>, Vec<String>, Vec<String>) {
+    let mut cn = Vec::new();
+    let mut en = Vec::new();
+    let mut other = Vec::new();
+    for name in subname
+        .split('/')
+        .map(|x| x.trim())
+        .filter(|x| !x.is_empty())
+    {
+        // 日文假名、韩文
+        let is_other = name.chars().any(|c| {
+            ('\u{3040}'..='\u{30ff}').contains(&c) || ('\u{ac00}'..='\u{d7af}').contains(&c)
+        });
+        let is_cn = name.chars().any(|c| ('\u{4e00}'..='\u{9fff}').contains(&c));
+        if is_other {
+            other.push(name.to_string());
+        } else if is_cn {
+            cn.push(name.to_string());
+        } else if name.chars().all(|c| c < '\u{0250}') {
+            en.push(name.to_string());
+        } else {
+            other.push(name.to_string());
+        }
+    }
+
+    (cn, en, other)
+}
+
+/// 解析中文数字，如 五、十二、二十三，也兼容阿拉伯数字
+pub fn parse_chinese_number(text: &str) -> u32 {
+    if let Ok(n) = text.parse::<u32>() {
+        return n;
+    }
+
+    let mut total = 0;
+    let mut current = 0;
+    for c in text.chars() {
+        match c {
+            '十' => {
+                total += current.max(1) * 10;
+                current = 0;
+            }
+            '百' => {
+                total += current.max(1) * 100;
+                current = 0;
+            }
+            _ => {
+                current = match c {
+                    '一' => 1,
+                    '二' | '两' => 2,
+                    '三' => 3,
+                    '四' => 4,
+                    '五' => 5,
+                    '六' => 6,
+                    '七' => 7,
+                    '八' => 8,
+                    '九' => 9,
+                    _ => 0,
+                }
+            }
+        }
+    }
+
+    total + current
+}
+
+/// 超时预算内的部分结果，incomplete为true时missing为未完成的id
+#[derive(Debug, Clone, Serialize)]
+pub struct Partial<T> {
+    pub data: Vec<T>,
+    pub incomplete: bool,
+    pub missing: Vec<String>,
+}
+
+impl<T> Default for Partial<T> {
+    fn default() -> Self {
+        Partial {
+            data: Vec::new(),
+            incomplete: false,
+            missing: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SearchFilter {
+    pub min_rating: f32,
+    pub min_votes: u32,
+    /// 保留预告片/花絮等噪音条目
+    pub keep_noise: bool,
+    /// 只保留指定分类(电影/电视剧)，空为不限
+    pub cat: &'static str,
+}
+
+impl SearchFilter {
+    pub fn is_empty(&self) -> bool {
+        self.min_rating <= 0.0 && self.min_votes == 0
+    }
+
+    /// votes为None表示数据源不提供评价人数，此时不按人数过滤
+    fn matches(&self, rating: &str, votes: Option<u32>) -> bool {
+        rating.trim().parse::<f32>().unwrap_or(0.0) >= self.min_rating
+            && votes.is_none_or(|x| x >= self.min_votes)
+    }
+
+    /// 指定了分类时，分类未知的条目一律排除
+    fn matches_cat(&self, cat: &str) -> bool {
+        self.cat.is_empty() || self.cat == cat
+    }
+}
+
+/// 搜索结果的分类
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CatKind {
+    Movie,
+    Tv,
+}
+
+impl CatKind {
+    fn parse(cat: &str) -> Option<CatKind> {
+        match cat {
+            "电影" => Some(CatKind::Movie),
+            "电视剧" => Some(CatKind::Tv),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SearchSource {
+    Suggest,
+    Web,
+    Mobile,
+    Local,
+    None,
+}
+
+impl SearchSource {
+    /// 搜索结果是否带评价人数，联想/移动站/本地索引的结果评价人数恒为0
+    fn has_votes(&self) -> bool {
+        *self == SearchSource::Web
+    }
+
+    /// 搜索结果是否带分类，移动站无法区分电影与电视剧
+    fn has_cat(&self) -> bool {
+        *self != SearchSource::Mobile
+    }
+}
+
+impl std::fmt::Display for SearchSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let name = match self {
+            SearchSource::Suggest => "suggest",
+            SearchSource::Web => "web",
+            SearchSource::Mobile => "mobile",
+            SearchSource::Local => "local",
+            SearchSource::None => "none",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// 搜索结果状态: ok(有结果)/rate_limited(被限流、要求登录或上游超时)/parse_error(请求或解析失败)/empty(确实无结果)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SearchStatus {
+    Ok,
+    RateLimited,
+    ParseError,
+    Empty,
+}
+
+impl std::fmt::Display for SearchStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let name = match self {
+            SearchStatus::Ok => "ok",
+            SearchStatus::RateLimited => "rate_limited",
+            SearchStatus::ParseError => "parse_error",
+            SearchStatus::Empty => "empty",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Suggestion {
+    sid: String,
+    title: String,
+    year: String,
+    #[serde(rename = "type")]
+    kind: &'static str, //movie或tv
+    img: String,        //小图
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct SuggestItem {
+    #[serde(default)]
+    episode: String,
+    #[serde(default)]
+    img: String,
+    #[serde(default)]
+    title: String,
+    #[serde(default)]
+    r#type: String,
+    #[serde(default)]
+    year: String,
+    #[serde(default)]
+    id: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Collection {
+    id: String,
+    title: String,
+    intro: String,
+    start: u32,
+    has_more: bool,
+    items: Vec<Movie>,
+}
+
+impl Collection {
+    pub fn into_page(self) -> Page<Movie> {
+        Page::new(self.items, self.start as usize, None, self.has_more)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OnThisDay {
+    year: i32,
+    date: String,
+    movie: Movie,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Quote {
+    text: String,
+    votes: u32, //点赞数
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LibraryStats {
+    total: usize,    //本地索引中的条目数
+    detailed: usize, //详情仍在缓存中、参与类型与导演/演员统计的条目数
+    by_year: Vec<StatCount>,
+    by_cat: Vec<StatCount>,
+    by_rating: Vec<StatCount>, //评分区间，如 8-9，无评分为unrated
+    by_genre: Vec<StatCount>,
+    top_directors: Vec<StatCount>,
+    top_actors: Vec<StatCount>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct StatCount {
+    name: String,
+    count: usize,
+}
 
-        let re_birthdate = Regex::new(r"出生日期: \n(.+?)\n").unwrap();
-        let mut birthdate = match re_birthdate.captures(text) {
-            Some(x) => x.get(1).unwrap().as_str().trim().to_string(),
-            None => String::new(),
-        };
+#[derive(Debug, Clone, Serialize)]
+pub struct Episodes {
+    sid: String,
+    name: String,
+    episode_count: u32,       //集数
+    episode_duration: String, //单集片长
+    episodes: Vec<Episode>,
+}
 
-        let re_lifedate = Regex::new(r"生卒日期: \n(.+?) 至").unwrap();
-        let lifedate = match re_lifedate.captures(text) {
-            Some(x) => x.get(1).unwrap().as_str().trim().to_string(),
-            None => String::new(),
-        };
-        if birthdate.is_empty() {
-            birthdate = lifedate.clone();
+#[derive(Debug, Clone, Serialize)]
+pub struct Episode {
+    number: u32,
+    title: String,          //本集中文名
+    original_title: String, //本集原名
+    air_date: String,       //播放时间
+    intro: String,          //剧情简介
+}
+
+impl Episode {
+    fn new(number: u32) -> Episode {
+        Episode {
+            number,
+            title: String::new(),
+            original_title: String::new(),
+            air_date: String::new(),
+            intro: String::new(),
         }
+    }
+}
 
-        let re_birthplace = Regex::new(r"出生地: \n(.+?)\n").unwrap();
-        let birthplace = match re_birthplace.captures(text) {
-            Some(x) => x.get(1).unwrap().as_str().trim().to_string(),
-            None => String::new(),
-        };
+#[derive(Debug, Clone, Serialize)]
+pub struct CommentKeywords {
+    sid: String,
+    comments: usize, //参与统计的短评条数
+    keywords: Vec<Keyword>,
+}
 
-        let re_role = Regex::new(r"职业: \n(.+?)\n").unwrap();
-        let role = match re_role.captures(text) {
-            Some(x) => x.get(1).unwrap().as_str().trim().to_string(),
-            None => String::new(),
-        };
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Daily {
+    date: String,
+    sid: String,
+    name: String,
+    rating: String,
+    img: String,
+    year: String,
+    quote: String,
+    quote_source: String,
+}
 
-        let re_nickname = Regex::new(r"更多外文名: \n(.+?)\n").unwrap();
-        let nickname = match re_nickname.captures(text) {
-            Some(x) => x.get(1).unwrap().as_str().trim().to_string(),
-            None => String::new(),
-        };
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BoxOffice {
+    period: String,
+    items: Vec<BoxOfficeItem>,
+}
 
-        let re_family = Regex::new(r"家庭成员: \n(.+?)\n").unwrap();
-        let family = match re_family.captures(text) {
-            Some(x) => x.get(1).unwrap().as_str().trim().to_string(),
-            None => String::new(),
-        };
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BoxOfficeItem {
+    rank: u32,
+    name: String,
+    sid: String,
+    box_office: String,
+    last_rank: Option<u32>,
+    change: Option<i32>, //排名变化，正数为上升
+}
 
-        let re_imdb = Regex::new(r"imdb编号: \n(.+?)\n").unwrap();
-        let imdb = match re_imdb.captures(text) {
-            Some(x) => x.get(1).unwrap().as_str().trim().to_string(),
-            None => String::new(),
-        };
+/// 缓存导出/导入的记录，kind 标识记录类型
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum CacheRecord {
+    Index { movie: Movie },
+    Release { sid: String, dates: Vec<String> },
+    Alias { name: String, sid: String },
+    Movie { key: String, value: Box<MovieInfo> },
+    Celebrity { key: String, value: Box<CelebrityInfo> },
+    Photo { key: String, value: Vec<Photo> },
+    Subscription { value: Subscription },
+    Change { key: String, value: Change },
+}
 
-        (
-            gender,
-            constellation,
-            birthdate,
-            birthplace,
-            role,
-            nickname,
-            family,
-            imdb,
-        )
-    }
+#[derive(Debug, Clone, Serialize)]
+pub struct ScrapeResult {
+    series: Vec<SeriesMatch>,
+    unmatched: Vec<String>,
+}
 
-    fn get_img_by_size(&self, url: &str, image_size: &str) -> String {
-        let mut img_url = url.to_string();
+#[derive(Debug, Clone, Serialize)]
+pub struct SeriesMatch {
+    title: String,
+    season: u32,
+    year: String,
+    matched: bool,
+    sid: String,
+    name: String,
+    rating: String,
+    episodes: Vec<EpisodeMatch>,
+}
 
-        // 改变图片大小
-        if image_size == "m" || image_size == "l" {
-            img_url = img_url.replace("s_ratio_poster", image_size);
-        }
+#[derive(Debug, Clone, Serialize)]
+pub struct EpisodeMatch {
+    file: String,
+    episode: u32,
+}
 
-        return img_url;
+#[derive(Debug, Clone, Default)]
+pub struct ExploreFilter {
+    pub decade: String,
+    pub region: String,
+    pub genre: String,
+    pub tag: String,
+    pub sort: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Explore {
+    start: u32,
+    has_more: bool,
+    items: Vec<Movie>,
+}
+
+impl Explore {
+    pub fn into_page(self) -> Page<Movie> {
+        Page::new(self.items, self.start as usize, None, self.has_more)
     }
 }
 
+#[derive(Debug, Deserialize)]
+struct ExploreResponse {
+    #[serde(default)]
+    data: Vec<ExploreItem>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ExploreItem {
+    #[serde(default)]
+    id: String,
+    #[serde(default)]
+    title: String,
+    #[serde(default)]
+    rate: String,
+    #[serde(default)]
+    cover: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ParentalGuide {
+    sid: String,
+    imdb: String,
+    certificate: String,
+    categories: Vec<ParentalGuideItem>,
+}
+
+/// severity 为 None/Mild/Moderate/Severe，未评定时为空
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ParentalGuideItem {
+    category: String,
+    severity: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Subscription {
+    id: String,
+    sid: String,
+    name: String,
+    webhook: String,
+    rating: String,
+    playable: bool,
+    created_at: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SubscriptionEvent {
+    id: String,
+    sid: String,
+    name: String,
+    events: Vec<&'static str>,
+    rating: String,
+    votes: u32,
+    playable: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NewReleases {
+    updated_at: u64,
+    movies: Vec<Movie>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Movie {
     cat: String,
+    #[serde(default)]
+    cat_kind: Option<CatKind>, //分类的英文枚举movie/tv，无法区分时为null
     sid: String,
     name: String,
     rating: String,
+    votes: u32,
     img: String,
     year: String,
 }
@@ -672,7 +3570,12 @@ pub struct MovieInfo {
     name: String,
     #[serde(rename = "originalName")]
     original_name: String,
+    #[serde(rename = "baseName")]
+    base_name: String,
+    #[serde(rename = "seasonNumber")]
+    season_number: u32,
     rating: String,
+    votes: u32,
     img: String,
     year: String,
     intro: String,
@@ -684,10 +3587,247 @@ pub struct MovieInfo {
     country: String,
     language: String,
     screen: String,
+    #[serde(default)]
+    screen_normalized: String, //首个上映日期的ISO 8601格式，无法解析时同原文
+    #[serde(default)]
+    release_dates: Vec<ReleaseDate>,
+    #[serde(default)]
+    production_companies: Vec<String>,
     duration: String,
     subname: String,
+    #[serde(default)]
+    aka_cn: Vec<String>,
+    #[serde(default)]
+    aka_en: Vec<String>,
+    #[serde(default)]
+    aka_other: Vec<String>,
     imdb: String,
+    content_rating: Option<String>,
+    playable: bool,
+    play_sources: Vec<PlaySource>,
+    #[serde(default)]
+    wish_count: u32, //想看人数
+    #[serde(default)]
+    doing_count: u32, //在看人数
     pub celebrities: Vec<Celebrity>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    palette: Option<Vec<String>>, //封面主色调，?palette=true时输出
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    imdb_rating: Option<f32>, //IMDb评分，配置OMDb api key时输出
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    imdb_votes: Option<u32>, //IMDb评分人数
+}
+
+#[derive(Debug, Clone)]
+struct ImdbRating {
+    rating: Option<f32>,
+    votes: Option<u32>,
+}
+
+impl MovieInfo {
+    fn aliases(&self) -> MovieAliases {
+        MovieAliases {
+            sid: self.sid.clone(),
+            name: self.name.clone(),
+            original_name: self.original_name.clone(),
+            aliases: self
+                .subname
+                .split('/')
+                .map(|x| x.trim().to_string())
+                .filter(|x| !x.is_empty())
+                .collect(),
+        }
+    }
+}
+
+impl MovieInfo {
+    /// 输出 kodi 使用的 movie.nfo，tvshow为true时输出 tvshow.nfo
+    pub fn to_nfo(&self, tvshow: bool) -> String {
+        let root = if tvshow { "tvshow" } else { "movie" };
+        let split = |text: &str| -> Vec<String> {
+            text.split('/')
+                .map(|x| x.trim().to_string())
+                .filter(|x| !x.is_empty())
+                .collect()
+        };
+        // 片长取开头的分钟数，如"142分钟"、"45分钟(台湾)"
+        let runtime: String = self
+            .duration
+            .trim()
+            .chars()
+            .take_while(|c| c.is_ascii_digit())
+            .collect();
+        let premiered = if self.screen_normalized.len() == 10 {
+            self.screen_normalized.clone()
+        } else {
+            String::new()
+        };
+        let mut directors = self
+            .celebrities
+            .iter()
+            .filter(|x| x.role_kind == Some(RoleKind::Director))
+            .map(|x| x.name.clone())
+            .collect::<Vec<String>>();
+        if directors.is_empty() {
+            directors = split(&self.director);
+        }
+
+        let mut nfo = String::new();
+        nfo.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"yes\" ?>\n");
+        nfo.push_str(&format!("<{}>\n", root));
+        let content_rating = self.content_rating.clone().unwrap_or_default();
+        let fields = [
+            ("title", &self.name),
+            ("originaltitle", &self.original_name),
+            ("year", &self.year),
+            ("plot", &self.intro),
+            ("runtime", &runtime),
+            ("mpaa", &content_rating),
+            ("premiered", &premiered),
+        ];
+        for (tag, value) in fields.iter() {
+            if !value.is_empty() {
+                nfo.push_str(&format!("  <{0}>{1}</{0}>\n", tag, xml_escape(value)));
+            }
+        }
+        if !self.rating.is_empty() {
+            nfo.push_str(&format!(
+                "  <ratings>\n    <rating name=\"douban\" max=\"10\" default=\"true\">\n      <value>{}</value>\n      <votes>{}</votes>\n    </rating>\n  </ratings>\n",
+                xml_escape(&self.rating),
+                self.votes
+            ));
+        }
+        let lists = [
+            ("genre", split(&self.genre)),
+            ("country", split(&self.country)),
+            ("studio", self.production_companies.clone()),
+            ("director", directors),
+            ("credits", split(&self.writer)),
+        ];
+        for (tag, values) in lists.iter() {
+            for value in values.iter() {
+                nfo.push_str(&format!("  <{0}>{1}</{0}>\n", tag, xml_escape(value)));
+            }
+        }
+        if !self.img.is_empty() {
+            nfo.push_str(&format!(
+                "  <thumb aspect=\"poster\">{}</thumb>\n",
+                xml_escape(&self.img)
+            ));
+        }
+        nfo.push_str(&format!(
+            "  <uniqueid type=\"douban\" default=\"true\">{}</uniqueid>\n",
+            xml_escape(&self.sid)
+        ));
+        if !self.imdb.is_empty() {
+            nfo.push_str(&format!(
+                "  <uniqueid type=\"imdb\">{}</uniqueid>\n",
+                xml_escape(&self.imdb)
+            ));
+        }
+        let actors = self
+            .celebrities
+            .iter()
+            .filter(|x| matches!(x.role_kind, Some(RoleKind::Actor) | Some(RoleKind::Voice)));
+        for (order, actor) in actors.enumerate() {
+            // 去掉"饰 "、"配 "前缀，只保留角色名
+            let role = actor
+                .role
+                .trim_start_matches('饰')
+                .trim_start_matches('配')
+                .trim();
+            nfo.push_str("  <actor>\n");
+            nfo.push_str(&format!("    <name>{}</name>\n", xml_escape(&actor.name)));
+            if !role.is_empty() && role != "演员" && role != "配音" {
+                nfo.push_str(&format!("    <role>{}</role>\n", xml_escape(role)));
+            }
+            nfo.push_str(&format!("    <order>{}</order>\n", order));
+            if !actor.img.is_empty() {
+                nfo.push_str(&format!("    <thumb>{}</thumb>\n", xml_escape(&actor.img)));
+            }
+            nfo.push_str("  </actor>\n");
+        }
+        nfo.push_str(&format!("</{}>\n", root));
+
+        nfo
+    }
+
+    /// 生成可嵌入网页的自包含电影卡片，图片不带Referer以绕过防盗链
+    pub fn to_widget(&self, dark: bool) -> String {
+        let (bg, fg, sub, border) = if dark {
+            ("#1f1f1f", "#e8e8e8", "#a0a0a0", "#333")
+        } else {
+            ("#fff", "#111", "#666", "#e5e5e5")
+        };
+        let mut intro: String = self.intro.chars().take(WIDGET_INTRO_SIZE).collect();
+        if self.intro.chars().count() > WIDGET_INTRO_SIZE {
+            intro.push('…');
+        }
+        let rating = if self.rating.is_empty() {
+            "暂无评分".to_string()
+        } else {
+            format!("★ {}", self.rating)
+        };
+        let url = format!("https://movie.douban.com/subject/{}/", self.sid);
+
+        format!(
+            r#"<div class="douban-card" style="display:flex;gap:12px;max-width:520px;padding:12px;border:1px solid {border};border-radius:8px;background:{bg};color:{fg};font:14px/1.6 -apple-system,'PingFang SC','Microsoft YaHei',sans-serif;">
+<a href="{url}" target="_blank" rel="noopener" style="flex:none;"><img src="{img}" alt="{name}" referrerpolicy="no-referrer" loading="lazy" style="width:96px;border-radius:4px;"></a>
+<div style="min-width:0;">
+<a href="{url}" target="_blank" rel="noopener" style="color:{fg};font-size:16px;font-weight:bold;text-decoration:none;">{name}</a> <span style="color:{sub};">({year})</span>
+<div style="color:#e09015;">{rating}</div>
+<div style="color:{sub};">{genre}</div>
+<p style="margin:4px 0 0;color:{sub};">{intro}</p>
+</div>
+</div>"#,
+            border = border,
+            bg = bg,
+            fg = fg,
+            sub = sub,
+            url = url,
+            img = xml_escape(&self.img),
+            name = xml_escape(&self.name),
+            year = xml_escape(&self.year),
+            rating = xml_escape(&rating),
+            genre = xml_escape(&self.genre),
+            intro = xml_escape(&intro),
+        )
+    }
+}
+
+/// 上映日期与地区
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReleaseDate {
+    date: String,
+    region: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MovieAliases {
+    sid: String,
+    name: String,
+    #[serde(rename = "originalName")]
+    original_name: String,
+    aliases: Vec<String>,
+}
+
+impl MovieAliases {
+    fn all(&self) -> Vec<&String> {
+        let mut names = vec![&self.name, &self.original_name];
+        names.extend(self.aliases.iter());
+        names.into_iter().filter(|x| !x.is_empty()).collect()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SubtitleInfo {
+    sid: String,
+    imdb: String,
+    year: String,
+    #[serde(rename = "seasonNumber")]
+    season_number: u32,
+    aliases: Vec<String>,
+    keywords: Vec<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -695,9 +3835,111 @@ pub struct Celebrity {
     id: String,
     img: String,
     name: String,
-    #[serde(skip_serializing)]
+    #[serde(skip_serializing, default)]
     role_type: String,
+    #[serde(default)]
+    role_kind: Option<RoleKind>,
     role: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    birthplace: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    works: Option<Vec<CelebrityWork>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    info: Option<CelebrityInfo>, //影人详情，?inline_info=true时输出
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CelebrityTimeline {
+    id: String,
+    total: usize, //作品总数
+    years: Vec<TimelineYear>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TimelineYear {
+    year: String, //没有年份时为空
+    works: Vec<TimelineWork>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TimelineWork {
+    sid: String,
+    name: String,
+    roles: Vec<String>,        //职责，如 导演、编剧、演员
+    role_kinds: Vec<RoleKind>, //可识别的职责类型(导演/演员/配音)
+}
+
+/// 演职员类型，不属于导演/演员/配音时为null
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum RoleKind {
+    Director,
+    Actor,
+    Voice,
+}
+
+impl RoleKind {
+    /// 按职责(导演/演员/配音)或详情页的角色(饰 xx/配 xx)映射
+    fn parse(role: &str) -> Option<RoleKind> {
+        let role = role.trim();
+        if role.starts_with("导演") {
+            Some(RoleKind::Director)
+        } else if role.starts_with("演员") || role.starts_with('饰') {
+            Some(RoleKind::Actor)
+        } else if role.starts_with("配音") || role.starts_with('配') {
+            Some(RoleKind::Voice)
+        } else {
+            None
+        }
+    }
+}
+
+/// 正版在线播放平台
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlaySource {
+    name: String,
+    url: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CelebrityGroup {
+    name: String,
+    celebrities: Vec<Celebrity>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CelebrityPage {
+    sid: String,
+    start: usize,
+    limit: usize,
+    total: usize,
+    groups: Vec<CelebrityGroup>,
+}
+
+impl CelebrityPage {
+    /// 展开分组，演职员的区块可通过role区分
+    pub fn into_page(self) -> Page<Celebrity> {
+        let items: Vec<Celebrity> = self
+            .groups
+            .into_iter()
+            .flat_map(|x| x.celebrities.into_iter())
+            .collect();
+        let has_more = self.start + items.len() < self.total;
+        Page::new(items, self.start, Some(self.total), has_more)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CelebrityWork {
+    sid: String,
+    name: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CelebrityBirthday {
+    id: String,
+    name: String,
+    img: String,
+    birthdate: String,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -710,10 +3952,55 @@ pub struct CelebrityInfo {
     gender: String,
     constellation: String,
     birthdate: String,
+    #[serde(default)]
+    birthdate_normalized: String, //ISO 8601格式的出生日期，无法解析时同原文
     birthplace: String,
     nickname: String,
     imdb: String,
     family: String,
+    works: Vec<CelebrityWork>,
+}
+
+impl CelebrityInfo {
+    /// 输出 kodi 使用的演员 nfo
+    pub fn to_nfo(&self) -> String {
+        let mut nfo = String::new();
+        nfo.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"yes\" ?>\n");
+        nfo.push_str("<person>\n");
+        let fields = [
+            ("name", &self.name),
+            ("biography", &self.intro),
+            ("born", &self.birthdate),
+            ("birthplace", &self.birthplace),
+            ("gender", &self.gender),
+            ("thumb", &self.img),
+        ];
+        for (tag, value) in fields.iter() {
+            if !value.is_empty() {
+                nfo.push_str(&format!("  <{0}>{1}</{0}>\n", tag, xml_escape(value)));
+            }
+        }
+        nfo.push_str(&format!(
+            "  <uniqueid type=\"douban\" default=\"true\">{}</uniqueid>\n",
+            xml_escape(&self.id)
+        ));
+        if !self.imdb.is_empty() {
+            nfo.push_str(&format!(
+                "  <uniqueid type=\"imdb\">{}</uniqueid>\n",
+                xml_escape(&self.imdb)
+            ));
+        }
+        nfo.push_str("</person>\n");
+
+        nfo
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SeasonPosters {
+    sid: String,
+    season: u32,
+    posters: Vec<Photo>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -722,7 +4009,173 @@ pub struct Photo {
     small: String,
     medium: String,
     large: String,
+    raw: String,
     size: String,
     width: String,
     height: String,
 }
+
+/// reason 为空结果的原因: no_photos(条目没有壁纸)/need_login(需要登录查看)
+#[derive(Debug, Clone, Serialize)]
+pub struct Wallpaper {
+    pub photos: Vec<Photo>,
+    pub start: usize,
+    pub has_more: bool,
+    pub reason: Option<&'static str>,
+}
+
+impl Wallpaper {
+    pub fn into_page(self) -> Page<Photo> {
+        Page::new(self.photos, self.start, None, self.has_more)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MovieImages {
+    posters: Vec<Photo>,
+    stills: Vec<Photo>,
+    backdrops: Vec<Photo>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::redact;
+    use clap::Parser;
+
+    fn douban() -> Douban {
+        let opt = Opt::parse_from(["douban-api-rs"]);
+        Douban::new(Arc::new(HttpClient::new(opt.clone())), &opt)
+    }
+
+    #[test]
+    fn parse_movie_html_fixture() {
+        let html = include_str!("../tests/fixtures/movie_1292052.html");
+        let info = douban().parse_movie_html("1292052", html, "").unwrap();
+
+        assert_eq!(info.sid, "1292052");
+        assert_eq!(info.name, "肖申克的救赎");
+        assert_eq!(info.original_name, "The Shawshank Redemption");
+        assert_eq!(info.base_name, "肖申克的救赎");
+        assert_eq!(info.season_number, 0);
+        assert_eq!(info.year, "1994");
+        assert_eq!(info.rating, "9.7");
+        assert_eq!(info.votes, 3056431);
+        assert_eq!(
+            info.img,
+            "https://img2.doubanio.com/view/photo/s_ratio_poster/public/p480747492.webp"
+        );
+        assert!(info.intro.starts_with("一场谋杀案使银行家安迪"));
+        assert!(!info.intro.contains("©豆瓣"));
+        assert_eq!(info.director, "弗兰克·德拉邦特");
+        assert_eq!(info.writer, "弗兰克·德拉邦特 / 斯蒂芬·金");
+        assert_eq!(info.actor, "蒂姆·罗宾斯 / 摩根·弗里曼");
+        assert_eq!(info.genre, "剧情 / 犯罪");
+        assert_eq!(info.country, "美国");
+        assert_eq!(info.language, "英语");
+        assert_eq!(info.screen, "1994-09-10(多伦多电影节) / 1994-10-14(美国)");
+        assert_eq!(info.screen_normalized, "1994-09-10");
+        assert_eq!(info.release_dates.len(), 2);
+        assert_eq!(info.release_dates[1].date, "1994-10-14");
+        assert_eq!(info.release_dates[1].region, "美国");
+        assert_eq!(info.production_companies, vec!["Castle Rock Entertainment"]);
+        assert_eq!(info.duration, "142分钟");
+        assert_eq!(info.aka_cn, vec!["月黑高飞(港)", "刺激1995(台)"]);
+        assert_eq!(info.aka_en, vec!["The Shawshank Redemption"]);
+        assert_eq!(info.aka_other, vec!["ショーシャンクの空に"]);
+        assert_eq!(info.imdb, "tt0111161");
+        assert_eq!(info.content_rating.as_deref(), Some("R"));
+        assert!(info.playable);
+        assert_eq!(info.play_sources[0].name, "腾讯视频");
+        assert_eq!(
+            info.play_sources[0].url,
+            "https://v.qq.com/x/cover/7s4bbx4pjjn0u4q.html"
+        );
+        assert_eq!(info.wish_count, 513820);
+        assert_eq!(info.doing_count, 15302);
+        assert_eq!(info.celebrities[0].id, "1047973");
+        assert_eq!(info.celebrities[0].name, "弗兰克·德拉邦特");
+        assert_eq!(info.celebrities[0].role, "导演");
+        assert_eq!(
+            info.celebrities[0].img,
+            "https://img2.doubanio.com/view/celebrity/raw/public/p230.jpg"
+        );
+    }
+
+    #[test]
+    fn hidden_fields_before_nfo() {
+        let html = include_str!("../tests/fixtures/movie_1292052.html");
+        let mut info = douban().parse_movie_html("1292052", html, "").unwrap();
+        redact::apply(&mut info, &redact::parse_fields("intro"));
+
+        assert!(info.intro.is_empty());
+        assert_eq!(info.celebrities[0].name, "弗兰克·德拉邦特");
+        assert!(!info.to_nfo(false).contains("一场谋杀案"));
+    }
+
+    #[test]
+    fn parse_movie_html_malformed() {
+        let douban = douban();
+        // 缺少标题时返回错误
+        assert!(douban.parse_movie_html("1", "", "").is_err());
+        assert!(douban
+            .parse_movie_html("1", "<div id=\"content\"><h1>", "")
+            .is_err());
+        // 缺少海报、影人链接与头像时不panic
+        let html = r#"<div id="content"><h1><span>电影</span></h1>
+            <div id="celebrities"><li class="celebrity"><div class="info"></div></li></div>
+            </div>"#;
+        let info = douban.parse_movie_html("1", html, "").unwrap();
+        assert_eq!(info.name, "电影");
+        assert_eq!(info.img, "");
+        assert_eq!(info.rating, "0");
+        assert_eq!(info.celebrities[0].id, "");
+        assert!(!info.playable);
+    }
+
+    #[test]
+    fn parse_celebrity_html_fixture() {
+        let html = include_str!("../tests/fixtures/celebrity_1054521.html");
+        let info = douban().parse_celebrity_html("1054521", html).unwrap();
+
+        assert_eq!(info.id, "1054521");
+        assert_eq!(info.name, "蒂姆·罗宾斯 Tim Robbins");
+        assert_eq!(
+            info.img,
+            "https://img9.doubanio.com/view/celebrity/raw/public/p17525.jpg"
+        );
+        assert!(info.intro.contains("奥斯卡最佳男配角奖"));
+        assert_eq!(info.gender, "男");
+        assert_eq!(info.constellation, "天秤座");
+        assert_eq!(info.birthdate, "1958-10-16");
+        assert_eq!(info.birthdate_normalized, "1958-10-16");
+        assert_eq!(info.birthplace, "美国,加利福尼亚州,西科维纳");
+        assert_eq!(info.role, "演员 / 导演 / 编剧 / 制片人");
+        assert_eq!(info.nickname, "Timothy Francis Robbins (本名)");
+        assert_eq!(info.family, "苏珊·萨兰登(前任伴侣)");
+        assert_eq!(info.imdb, "nm0000209");
+        assert_eq!(info.works.len(), 2);
+        assert_eq!(info.works[1].sid, "1297192");
+        assert_eq!(info.works[1].name, "神秘河");
+    }
+
+    #[test]
+    fn parse_celebrity_html_malformed() {
+        let info = douban().parse_celebrity_html("1", "<div><h1>").unwrap();
+        assert_eq!(info.id, "1");
+        assert_eq!(info.img, "");
+        assert!(info.works.is_empty());
+    }
+
+    #[test]
+    fn parse_decade_two_digits() {
+        assert_eq!(parse_decade("1990s"), Some((1990, 1999)));
+        assert_eq!(parse_decade("90s"), Some((1990, 1999)));
+        assert_eq!(parse_decade("30年代"), Some((1930, 1939)));
+        assert_eq!(parse_decade("00s"), Some((2000, 2009)));
+        assert_eq!(parse_decade("10s"), Some((2010, 2019)));
+        assert_eq!(parse_decade("20s"), Some((2020, 2029)));
+        assert_eq!(parse_decade("2015"), Some((2010, 2019)));
+        assert_eq!(parse_decade("95s"), None);
+    }
+}
@@ -0,0 +1,95 @@
+use crate::util::{now_secs, today};
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+/// 归档的页面原文，url 为请求地址，final_url 为跳转后的地址
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArchiveEntry {
+    pub url: String,
+    pub final_url: String,
+    pub fetched_at: u64,
+    pub body: String,
+}
+
+/// gzip 后保存到 {dir}/{YYYY-MM-DD}/{kind}/，同一页面的每次抓取都单独保存
+pub async fn save(dir: &str, kind: &str, url: &str, final_url: &str, body: &str) {
+    let entry = ArchiveEntry {
+        url: url.to_string(),
+        final_url: final_url.to_string(),
+        fetched_at: now_secs(),
+        body: body.to_string(),
+    };
+    let (year, month, day) = today();
+    let dir = PathBuf::from(dir)
+        .join(format!("{}-{:02}-{:02}", year, month, day))
+        .join(kind);
+    let mut hasher = DefaultHasher::new();
+    url.hash(&mut hasher);
+    let path = dir.join(format!(
+        "{:016x}_{}.json.gz",
+        hasher.finish(),
+        entry.fetched_at
+    ));
+
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    let data = encoder
+        .write_all(&serde_json::to_vec(&entry).unwrap())
+        .and_then(|_| encoder.finish());
+    let data = match data {
+        Ok(data) => data,
+        Err(err) => {
+            println!("压缩归档页面失败: {:?}", err);
+            return;
+        }
+    };
+    if let Err(err) = tokio::fs::create_dir_all(&dir).await {
+        println!("创建归档目录失败: {:?}", err);
+        return;
+    }
+    if let Err(err) = tokio::fs::write(path, data).await {
+        println!("写入归档页面失败: {:?}", err);
+    }
+}
+
+/// 读取归档目录下的全部页面，按抓取时间排序，无法解析的文件跳过
+pub fn load_all(dir: &str) -> Vec<ArchiveEntry> {
+    let mut entries = Vec::new();
+    walk(Path::new(dir), &mut entries);
+    entries.sort_by_key(|x| x.fetched_at);
+
+    entries
+}
+
+fn walk(dir: &Path, entries: &mut Vec<ArchiveEntry>) {
+    let list = match std::fs::read_dir(dir) {
+        Ok(list) => list,
+        Err(err) => {
+            println!("读取归档目录失败: {} {:?}", dir.display(), err);
+            return;
+        }
+    };
+    for item in list.flatten() {
+        let path = item.path();
+        if path.is_dir() {
+            walk(&path, entries);
+        } else if path.to_string_lossy().ends_with(".json.gz") {
+            match read(&path) {
+                Ok(entry) => entries.push(entry),
+                Err(err) => println!("解析归档文件失败: {} {:?}", path.display(), err),
+            }
+        }
+    }
+}
+
+fn read(path: &Path) -> anyhow::Result<ArchiveEntry> {
+    let data = std::fs::read(path)?;
+    let mut json = Vec::new();
+    GzDecoder::new(&data[..]).read_to_end(&mut json)?;
+    Ok(serde_json::from_slice(&json)?)
+}
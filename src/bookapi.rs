@@ -1,21 +1,46 @@
+use crate::changes;
 use crate::http::HttpClient;
+use crate::metrics;
+use crate::util::{html_to_text, normalize_date, now_secs, xml_escape, CacheMeta, Page};
 use anyhow::Result;
 use lazy_static::*;
 use moka::future::{Cache, CacheBuilder};
 use regex::Regex;
+use reqwest::Url;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use std::sync::Arc;
+use std::sync::{Arc, RwLock};
 use std::time::Duration;
 use visdom::Vis;
 
 lazy_static! {
     static ref BOOK_CACHE: Cache<String, DoubanBook> = CacheBuilder::new(CACHE_SIZE)
-        .time_to_live(Duration::from_secs(10 * 60))
+        .time_to_live(BOOK_CACHE_TTL)
         .build();
+    // 图书详情的抓取时间
+    static ref BOOK_FETCHED_AT: Cache<String, u64> = CacheBuilder::new(CACHE_SIZE)
+        .time_to_live(BOOK_CACHE_TTL)
+        .build();
+    // 搜索结果缓存去重、截取前的完整列表，不同count共用，key 为小写搜索词
+    static ref SEARCH_CACHE: Cache<String, Vec<DoubanBook>> = CacheBuilder::new(CACHE_SIZE)
+        .time_to_live(BOOK_CACHE_TTL)
+        .build();
+    // 热门新书榜，按榜单类型缓存
+    static ref RECENT_HOT_CACHE: Cache<&'static str, Vec<HotBook>> = CacheBuilder::new(2)
+        .time_to_live(RECENT_HOT_CACHE_TTL)
+        .build();
+    // 关注标签的榜单快照，按抓取时间排序
+    static ref TAG_SNAPSHOTS: RwLock<Vec<TagSnapshot>> = RwLock::new(Vec::new());
 }
 
 const CACHE_SIZE: usize = 100;
+const AUTHOR_WORKS_PAGE_SIZE: usize = 15;
+const SEARCH_PAGE_SIZE: i32 = 20;
+const BOOK_CACHE_TTL: Duration = Duration::from_secs(10 * 60);
+const TAG_SNAPSHOT_LIMIT: usize = 500;
+const RECENT_HOT_CACHE_TTL: Duration = Duration::from_secs(60 * 60);
+// 热门新书榜类型与对应的榜单参数
+const RECENT_HOT_KINDS: [(&str, &str); 2] = [("fiction", "F"), ("nonfiction", "I")];
 
 #[derive(Clone)]
 pub struct DoubanBookApi {
@@ -23,6 +48,9 @@ pub struct DoubanBookApi {
     re_id: Regex,                 //id 正则
     re_info_pair: Regex,          //匹配:字符两边的信息
     re_remove_split_space: Regex, //去除/分隔符两边多余空格
+    re_num_raters: Regex,         //评价人数
+    re_price: Regex,              //价格
+    re_author_id: Regex,          //作者主页 id
 }
 
 impl DoubanBookApi {
@@ -30,16 +58,65 @@ impl DoubanBookApi {
         let re_id = Regex::new(r"sid: (\d+?),").unwrap();
         let re_remove_split_space = Regex::new(r"\s+?/\s+").unwrap();
         let re_info_pair = Regex::new(r"([^\s]+?):\s*([^\n]+)").unwrap();
+        let re_num_raters = Regex::new(r"(\d+)\s*人评价").unwrap();
+        let re_price = Regex::new(r"(\d+(\.\d+)?)\s*元").unwrap();
+        let re_author_id = Regex::new(r"/author/(\d+)").unwrap();
         Self {
             client,
             re_id,
             re_info_pair,
             re_remove_split_space,
+            re_num_raters,
+            re_price,
+            re_author_id,
         }
     }
 
-    pub async fn search(&self, q: &str, count: i32) -> Result<DoubanBookResult<DoubanBook>> {
-        let list = self.get_list(q, count).await.unwrap();
+    /// sort: relevance(默认，豆瓣搜索顺序)/rating(评分)/pubdate(出版年，新的在前)
+    pub async fn search(
+        &self,
+        q: &str,
+        count: i32,
+        dedupe: bool,
+        full: bool,
+        sort: &str,
+    ) -> Result<DoubanBookResult<DoubanBook>> {
+        let relevance = sort.is_empty() || sort == "relevance";
+        // 排序时取整页结果排序后再截取
+        let fetch_count = if relevance { count } else { SEARCH_PAGE_SIZE };
+        let mut list = self.get_list(q, fetch_count, dedupe).await.unwrap();
+        if sort == "pubdate" && !full {
+            // 列表中缺少出版年的条目取详情补全
+            for book in list.iter_mut().filter(|x| x.pubdate.is_empty()) {
+                match self.get_book_info(&book.id).await {
+                    Ok(info) => {
+                        book.pubdate_normalized = info.pubdate_normalized;
+                        book.pubdate = info.pubdate;
+                    }
+                    Err(err) => println!("获取书籍详情失败: {} {:?}", book.id, err),
+                }
+            }
+        }
+        match sort {
+            "rating" => list.sort_by(|a, b| {
+                b.rating
+                    .average
+                    .partial_cmp(&a.rating.average)
+                    .unwrap_or(std::cmp::Ordering::Equal)
+                    .then(b.rating.num_raters.cmp(&a.rating.num_raters))
+            }),
+            "pubdate" => list.sort_by_key(|x| std::cmp::Reverse(pubdate_key(&x.pubdate))),
+            _ => {}
+        }
+        list.truncate(count as usize);
+        if full {
+            for book in list.iter_mut() {
+                match self.get_book_info(&book.id).await {
+                    Ok(info) => *book = info,
+                    Err(err) => println!("获取书籍详情失败: {} {:?}", book.id, err),
+                }
+            }
+        }
         Ok(DoubanBookResult {
             code: 0,
             books: list,
@@ -47,87 +124,35 @@ impl DoubanBookApi {
         })
     }
 
-    async fn get_list(&self, q: &str, count: i32) -> Result<Vec<DoubanBook>> {
+    async fn get_list(&self, q: &str, count: i32, dedupe: bool) -> Result<Vec<DoubanBook>> {
         let mut vec = Vec::with_capacity(count as usize);
         if q.is_empty() {
             return Ok(vec);
         }
-        let url = "https://www.douban.com/search";
+        let cache_key = q.trim().to_lowercase();
+        if let Some(list) = SEARCH_CACHE.get(&cache_key) {
+            return Ok(self.limit_list(list, count, dedupe));
+        }
+        let url = format!("{}/search", self.client.www_base());
         let res = self
             .client
-            .get(url)
-            .query(&[("cat", "1001"), ("q", q)])
-            .send()
+            .send(self.client.get(url).query(&[("cat", "1001"), ("q", q)]))
             .await?
             .error_for_status();
         match res {
             Ok(res) => {
+                let final_url = res.url().to_string();
                 let res = res.text().await?;
-                let document = Vis::load(&res).unwrap();
-                vec = document
-                    .find("div.result-list")
-                    .first()
-                    .find(".result")
-                    .map(|_index, x| {
-                        let x = Vis::dom(x);
-                        let onclick = x.find("div.title a").attr("onclick").unwrap().to_string();
-                        let title = x.find("div.title a").text().trim().to_string();
-                        let summary = x.find("p").text().trim().to_string();
-                        let large = x.find(".pic img").attr("src").unwrap().to_string();
-                        let rate = x.find(".rating_nums").text().to_string();
-                        let sub_str = x.find(".subject-cast").text().to_string();
-                        let subjects: Vec<&str> = sub_str.split('/').collect();
-                        let len = subjects.len();
-                        let mut pubdate = String::from("");
-                        let mut publisher = String::from("");
-                        let mut author = Vec::new();
-                        if len >= 3 {
-                            pubdate = subjects[len - 1].trim().to_string();
-                            publisher = subjects[len - 2].trim().to_string();
-                            let mut i = 0;
-                            for elem in subjects {
-                                author.push(elem.trim().to_string());
-                                i += 1;
-                                if i == len - 2 {
-                                    break;
-                                }
-                            }
-                        } else if len == 2 {
-                            author.push(subjects[0].trim().to_string());
-                            match subjects[1].parse::<i32>() {
-                                Ok(_t) => pubdate = subjects[1].trim().to_string(),
-                                Err(_e) => publisher = subjects[1].trim().to_string(),
-                            }
-                        } else if len == 1 {
-                            author.push(subjects[0].trim().to_string());
-                        }
-
-                        let mut m_id = String::from("");
-                        for c in self.re_id.captures_iter(&onclick) {
-                            m_id = c[1].trim().to_string();
-                        }
-                        let id = m_id;
-
-                        let rating = if rate.is_empty() {
-                            Rating::new(0.0)
-                        } else {
-                            Rating::new(rate.parse::<f32>().unwrap())
-                        };
-                        let images = Image::new(large);
-                        DoubanBook::simple(SimpleDoubanBook {
-                            id,
-                            author,
-                            images,
-                            rating,
-                            pubdate,
-                            publisher,
-                            summary,
-                            title,
-                        })
-                    })
-                    .into_iter()
-                    .take(count as usize)
-                    .collect::<Vec<DoubanBook>>();
+                if is_login_wall(&final_url, &res) {
+                    println!("书籍搜索需要登录，改用suggest/移动端搜索: {}", q);
+                    vec = self.get_list_fallback(q).await?;
+                } else {
+                    vec = self.parse_list(&res);
+                }
+                if !vec.is_empty() {
+                    SEARCH_CACHE.insert(cache_key, vec.clone()).await;
+                }
+                vec = self.limit_list(vec, count, dedupe);
             }
             Err(err) => {
                 println!("错误: {:?}", err);
@@ -137,22 +162,187 @@ impl DoubanBookApi {
         Ok(vec)
     }
 
+    fn limit_list(&self, mut vec: Vec<DoubanBook>, count: i32, dedupe: bool) -> Vec<DoubanBook> {
+        if dedupe {
+            vec = self.dedupe(vec);
+        }
+        vec.truncate(count as usize);
+
+        vec
+    }
+
+    fn parse_list(&self, res: &str) -> Vec<DoubanBook> {
+        let document = Vis::load(res).unwrap();
+        document
+            .find("div.result-list")
+            .first()
+            .find(".result")
+            .map(|_index, x| {
+                let x = Vis::dom(x);
+                let onclick = x.find("div.title a").attr("onclick").unwrap().to_string();
+                let title = x.find("div.title a").text().trim().to_string();
+                let summary = x.find("p").text().trim().to_string();
+                let large = x.find(".pic img").attr("src").unwrap().to_string();
+                let rate = x.find(".rating_nums").text().to_string();
+                let num_raters = self.parse_num_raters(x.find(".rating-info").text());
+                let sub_str = x.find(".subject-cast").text().to_string();
+                let subjects: Vec<&str> = sub_str.split('/').collect();
+                let len = subjects.len();
+                let mut pubdate = String::from("");
+                let mut publisher = String::from("");
+                let mut author = Vec::new();
+                if len >= 3 {
+                    pubdate = subjects[len - 1].trim().to_string();
+                    publisher = subjects[len - 2].trim().to_string();
+                    let mut i = 0;
+                    for elem in subjects {
+                        author.push(elem.trim().to_string());
+                        i += 1;
+                        if i == len - 2 {
+                            break;
+                        }
+                    }
+                } else if len == 2 {
+                    author.push(subjects[0].trim().to_string());
+                    match subjects[1].parse::<i32>() {
+                        Ok(_t) => pubdate = subjects[1].trim().to_string(),
+                        Err(_e) => publisher = subjects[1].trim().to_string(),
+                    }
+                } else if len == 1 {
+                    author.push(subjects[0].trim().to_string());
+                }
+
+                let mut m_id = String::from("");
+                for c in self.re_id.captures_iter(&onclick) {
+                    m_id = c[1].trim().to_string();
+                }
+                let id = m_id;
+
+                let rating = if rate.is_empty() {
+                    Rating::new(0.0, num_raters)
+                } else {
+                    Rating::new(rate.parse::<f32>().unwrap(), num_raters)
+                };
+                let images = Image::new(large);
+                DoubanBook::simple(SimpleDoubanBook {
+                    id,
+                    author,
+                    images,
+                    rating,
+                    pubdate,
+                    publisher,
+                    summary,
+                    title,
+                })
+            })
+            .into_iter()
+            .collect::<Vec<DoubanBook>>()
+    }
+
+    /// 无cookie遇到登录墙时，依次使用suggest接口和移动端搜索获取基础书目信息
+    async fn get_list_fallback(&self, q: &str) -> Result<Vec<DoubanBook>> {
+        match self.search_suggest(q).await {
+            Ok(list) if !list.is_empty() => return Ok(list),
+            Ok(_) => println!("suggest 搜索无结果: {}", q),
+            Err(err) => println!("suggest 搜索失败: {:?}", err),
+        }
+        self.search_mobile(q).await
+    }
+
+    async fn search_suggest(&self, q: &str) -> Result<Vec<DoubanBook>> {
+        let url = format!("{}/j/subject_suggest", self.client.book_base());
+        let list = self
+            .client
+            .send(self.client.get(url).query(&[("q", q)]))
+            .await?
+            .error_for_status()?
+            .json::<Vec<BookSuggestItem>>()
+            .await?;
+
+        Ok(list
+            .into_iter()
+            .filter(|x| x.r#type == "b")
+            .map(|x| {
+                DoubanBook::simple(SimpleDoubanBook {
+                    id: x.id,
+                    author: x
+                        .author_name
+                        .split('/')
+                        .map(|x| x.trim().to_string())
+                        .filter(|x| !x.is_empty())
+                        .collect(),
+                    images: Image::new(x.pic),
+                    rating: Rating::new(0.0, 0),
+                    pubdate: x.year,
+                    publisher: String::new(),
+                    summary: String::new(),
+                    title: x.title,
+                })
+            })
+            .collect::<Vec<DoubanBook>>())
+    }
+
+    async fn search_mobile(&self, q: &str) -> Result<Vec<DoubanBook>> {
+        let url = format!("{}/search/", self.client.mobile_base());
+        let res = self
+            .client
+            .send(
+                self.client
+                    .get(url)
+                    .query(&[("query", q), ("type", "book")]),
+            )
+            .await?
+            .error_for_status()?
+            .text()
+            .await?;
+
+        let document = Vis::load(&res).unwrap();
+        let vec = document
+            .find("ul.search_results_subjects>li>a")
+            .map(|_index, x| {
+                let x = Vis::dom(x);
+                let href = x.attr("href").map(|x| x.to_string()).unwrap_or_default();
+                let id = href
+                    .split('/')
+                    .rfind(|x| !x.is_empty())
+                    .unwrap_or("")
+                    .to_string();
+                let large = match x.find("img").attr("src") {
+                    Some(src) => src.to_string(),
+                    None => String::new(),
+                };
+                let title = x.find("span.subject-title").text().trim().to_string();
+                let rate = x.find("p.rating span:last-child").text().trim().to_string();
+                DoubanBook::simple(SimpleDoubanBook {
+                    id,
+                    author: Vec::new(),
+                    images: Image::new(large),
+                    rating: Rating::new(rate.parse::<f32>().unwrap_or(0.0), 0),
+                    pubdate: String::new(),
+                    publisher: String::new(),
+                    summary: String::new(),
+                    title,
+                })
+            })
+            .into_iter()
+            .filter(|x| !x.id.is_empty() && x.id.chars().all(|c| c.is_ascii_digit()))
+            .collect::<Vec<DoubanBook>>();
+
+        Ok(vec)
+    }
+
     async fn get_book_internal(&self, url: String) -> Result<DoubanBook> {
-        let res = self.client.get(url).send().await?.error_for_status();
-        let result_text: String;
-        let id: String;
-        match res {
+        let (id, result_text) = match self.client.get_html(&url).await {
             Err(e) => {
                 println!("{}", e);
-                return Err(anyhow::Error::from(e));
+                return Err(e);
             }
             Ok(t) => {
-                let t_url = t.url().as_str();
-                let t_array = t_url.split('/').collect::<Vec<&str>>();
-                id = t_array[t_array.len() - 2].to_string();
-                result_text = t.text().await?
+                let t_array = t.url.split('/').collect::<Vec<&str>>();
+                let id = t_array[t_array.len() - 2].to_string();
+                (id, t.body)
             }
-        }
+        };
 
         let document = Vis::load(&result_text).unwrap();
         let x = document.find("#wrapper");
@@ -170,12 +360,11 @@ impl DoubanBookApi {
             .text()
             .trim()
             .to_string();
+        let num_raters = self.parse_num_raters(content.find("a.rating_people").text());
         let rating = if rating_str.is_empty() {
-            Rating { average: 0.0 }
+            Rating::new(0.0, num_raters)
         } else {
-            Rating {
-                average: rating_str.parse::<f32>().unwrap(),
-            }
+            Rating::new(rating_str.parse::<f32>().unwrap(), num_raters)
         };
         let mut summary = content
             .find("#link-report .hidden .intro")
@@ -206,6 +395,7 @@ impl DoubanBookApi {
         let info_text_map = self.parse_info_text(info.text().trim());
 
         let author = self.get_texts(&info_text_map, "作者");
+        let author_ids = self.parse_author_ids(&info.html());
         let translators = self.get_texts(&info_text_map, "译者");
         let producer = self.get_text(&info_text_map, "出品方");
         let serials = self.get_text(&info_text_map, "丛书");
@@ -217,17 +407,23 @@ impl DoubanBookApi {
         let binding = self.get_text(&info_text_map, "装帧");
         let subtitle = self.get_text(&info_text_map, "副标题");
         let isbn13 = self.get_text(&info_text_map, "ISBN");
+        let ebook_price = self.parse_ebook_price(&x);
+        let content_rating = info_text_map.get("分级").cloned();
         let category = String::from(""); //TODO 页面上是在找不到分类...
+        let raw = self.get_raw_cover(&large_img).await;
         let images = Image {
             medium: large_img.clone(),
             large: large_img,
             small: small_img,
+            raw,
         };
         let cache_key = id.clone();
         let cache_key1 = isbn13.clone();
         let info = DoubanBook {
             id,
             author,
+            author_ids,
+            author_intro_text: html_to_text(&author_intro),
             author_intro,
             translators,
             images,
@@ -237,28 +433,333 @@ impl DoubanBookApi {
             isbn13,
             pages,
             price,
+            ebook_price,
+            pubdate_normalized: normalize_date(&pubdate),
             pubdate,
             publisher,
             producer,
             serials,
             subtitle,
+            summary_text: html_to_text(&summary),
             summary,
             title,
             tags,
             origin,
+            content_rating,
         };
+        metrics::validate("book", &info);
+        changes::record("book", &cache_key, &info.rating.average.to_string());
+        let now = now_secs();
+        BOOK_FETCHED_AT.insert(cache_key.clone(), now).await;
+        BOOK_FETCHED_AT.insert(cache_key1.clone(), now).await;
         BOOK_CACHE.insert(cache_key, info.clone()).await;
         BOOK_CACHE.insert(cache_key1, info.clone()).await;
         Ok(info)
     }
 
+    /// 获取作者的其它作品
+    pub async fn get_author_works(
+        &self,
+        id: &str,
+        start: u32,
+    ) -> Result<DoubanBookResult<DoubanBook>> {
+        let url = format!(
+            "{}/author/{}/books?sortby=time&start={}",
+            self.client.book_base(),
+            id,
+            start
+        );
+        let res = self.client.get_html(&url).await?.body;
+        let books = self.parse_subject_items(&res);
+
+        Ok(DoubanBookResult {
+            code: 0,
+            books,
+            msg: "".to_string(),
+        })
+    }
+
+    /// 抓取标签页的综合排序榜单(首页20本)，追加快照并写入快照文件
+    pub async fn snapshot_tags(&self, tags: &[String], file: &str) {
+        for tag in tags.iter() {
+            let url = format!(
+                "{}/tag/{}?start=0&type=T",
+                self.client.book_base(),
+                urlencoding::encode(tag)
+            );
+            let res = match self.client.get_html(&url).await {
+                Ok(page) => page.body,
+                Err(err) => {
+                    println!("抓取图书标签榜单失败: {} {:?}", tag, err);
+                    continue;
+                }
+            };
+            let items = self
+                .parse_subject_items(&res)
+                .into_iter()
+                .map(|x| TagChartItem {
+                    id: x.id,
+                    title: x.title,
+                })
+                .collect::<Vec<TagChartItem>>();
+            if items.is_empty() {
+                println!("图书标签榜单为空: {}", tag);
+                continue;
+            }
+            let mut snapshots = TAG_SNAPSHOTS.write().unwrap();
+            snapshots.push(TagSnapshot {
+                tag: tag.clone(),
+                taken_at: now_secs(),
+                items,
+            });
+            // 每个标签只保留最近的快照
+            let count = snapshots.iter().filter(|x| &x.tag == tag).count();
+            if count > TAG_SNAPSHOT_LIMIT {
+                if let Some(i) = snapshots.iter().position(|x| &x.tag == tag) {
+                    snapshots.remove(i);
+                }
+            }
+        }
+
+        if file.is_empty() {
+            return;
+        }
+        let data = serde_json::to_vec(&*TAG_SNAPSHOTS.read().unwrap()).unwrap();
+        if let Err(err) = tokio::fs::write(file, data).await {
+            println!("写入图书标签快照失败: {:?}", err);
+        }
+    }
+
+    /// 加载快照文件，文件不存在时忽略
+    pub async fn load_tag_snapshots(&self, file: &str) {
+        if file.is_empty() {
+            return;
+        }
+        let data = match tokio::fs::read(file).await {
+            Ok(data) => data,
+            Err(_) => return,
+        };
+        match serde_json::from_slice::<Vec<TagSnapshot>>(&data) {
+            Ok(snapshots) => *TAG_SNAPSHOTS.write().unwrap() = snapshots,
+            Err(err) => println!("解析图书标签快照失败: {:?}", err),
+        }
+    }
+
+    /// 书籍在标签榜单各次快照中的排名，未上榜时rank为null
+    pub fn get_tag_history(&self, tag: &str, id: &str) -> TagHistory {
+        let snapshots = TAG_SNAPSHOTS.read().unwrap();
+        let mut title = String::new();
+        let history = snapshots
+            .iter()
+            .filter(|x| x.tag == tag)
+            .map(|x| {
+                let rank = x.items.iter().position(|item| item.id == id);
+                if let Some(i) = rank {
+                    title = x.items[i].title.clone();
+                }
+                TagRank {
+                    taken_at: x.taken_at,
+                    rank: rank.map(|i| i as u32 + 1),
+                }
+            })
+            .collect::<Vec<TagRank>>();
+
+        TagHistory {
+            tag: tag.to_string(),
+            id: id.to_string(),
+            title,
+            history,
+        }
+    }
+
+    /// 解析作者作品、标签页等页面的图书列表(li.subject-item)
+    fn parse_subject_items(&self, res: &str) -> Vec<DoubanBook> {
+        let document = Vis::load(res).unwrap();
+        document
+            .find("li.subject-item")
+            .map(|_index, x| {
+                let x = Vis::dom(x);
+                let href = x.find("div.info h2 a").attr("href").map(|x| x.to_string());
+                let id = href
+                    .unwrap_or_default()
+                    .split('/')
+                    .rfind(|x| !x.is_empty())
+                    .unwrap_or("")
+                    .to_string();
+                let title = x
+                    .find("div.info h2 a")
+                    .text()
+                    .split_whitespace()
+                    .collect::<Vec<&str>>()
+                    .join(" ");
+                let large = match x.find("div.pic img").attr("src") {
+                    Some(src) => src.to_string(),
+                    None => String::new(),
+                };
+                let rate = x.find("span.rating_nums").text().trim().to_string();
+                let num_raters = self.parse_num_raters(x.find("div.star").text());
+                let rating = Rating::new(rate.parse::<f32>().unwrap_or(0.0), num_raters);
+                // 作者 / 出版社 / 出版年 / 定价
+                let pub_str = x.find("div.pub").text().to_string();
+                let subjects: Vec<&str> = pub_str.split('/').map(|x| x.trim()).collect();
+                let len = subjects.len();
+                let (author, publisher, pubdate) = if len >= 4 {
+                    (
+                        subjects[..len - 3].iter().map(|x| x.to_string()).collect(),
+                        subjects[len - 3].to_string(),
+                        subjects[len - 2].to_string(),
+                    )
+                } else {
+                    (Vec::new(), String::new(), String::new())
+                };
+                let summary = x.find("div.info p").text().trim().to_string();
+                DoubanBook::simple(SimpleDoubanBook {
+                    id,
+                    author,
+                    images: Image::new(large),
+                    rating,
+                    pubdate,
+                    publisher,
+                    summary,
+                    title,
+                })
+            })
+            .into_iter()
+            .filter(|x| !x.id.is_empty())
+            .collect::<Vec<DoubanBook>>()
+    }
+
+    /// 热门新书榜(虚构类/非虚构类)，kind为空时返回两个榜单
+    pub async fn get_recent_hot(&self, kind: &str) -> Result<Vec<HotBook>> {
+        let mut list = Vec::new();
+        for (name, subcat) in RECENT_HOT_KINDS.iter() {
+            if !kind.is_empty() && kind != *name {
+                continue;
+            }
+            if let Some(books) = RECENT_HOT_CACHE.get(name) {
+                list.extend(books);
+                continue;
+            }
+            let url = format!("{}/chart?subcat={}", self.client.book_base(), subcat);
+            let res = self.client.get_html(&url).await?.body;
+            let books = self.parse_recent_hot(&res, name);
+            if !books.is_empty() {
+                RECENT_HOT_CACHE.insert(*name, books.clone()).await;
+            }
+            list.extend(books);
+        }
+
+        Ok(list)
+    }
+
+    fn parse_recent_hot(&self, res: &str, kind: &'static str) -> Vec<HotBook> {
+        let document = Vis::load(res).unwrap();
+        document
+            .find("ul.chart-dashed-list > li")
+            .map(|index, x| {
+                let x = Vis::dom(x);
+                let href = x.find("h2 a").attr("href").map(|x| x.to_string());
+                let id = href
+                    .unwrap_or_default()
+                    .split('/')
+                    .rfind(|x| !x.is_empty())
+                    .unwrap_or("")
+                    .to_string();
+                let title = x.find("h2 a").text().trim().to_string();
+                let large = match x.find("img").attr("src") {
+                    Some(src) => src.to_string(),
+                    None => String::new(),
+                };
+                let rate = x.find(".color-red").text().trim().to_string();
+                let num_raters = self.parse_num_raters(x.text());
+                let rating = Rating::new(rate.parse::<f32>().unwrap_or(0.0), num_raters);
+                // 作者 / 出版年 / 出版社 / 定价 / 装帧
+                let abstract_str = x.find("p.subject-abstract").text().to_string();
+                let subjects: Vec<&str> = abstract_str.split('/').map(|x| x.trim()).collect();
+                let date_index = subjects.iter().position(|x| {
+                    x.chars().take(4).filter(|c| c.is_ascii_digit()).count() == 4
+                });
+                let (author, pubdate, publisher) = match date_index {
+                    Some(i) => (
+                        subjects[..i].iter().map(|x| x.to_string()).collect(),
+                        subjects[i].to_string(),
+                        subjects.get(i + 1).unwrap_or(&"").to_string(),
+                    ),
+                    None => (Vec::new(), String::new(), String::new()),
+                };
+                let summary = x.find("p.detail").text().trim().to_string();
+                HotBook {
+                    kind,
+                    rank: index as u32 + 1,
+                    book: DoubanBook::simple(SimpleDoubanBook {
+                        id,
+                        author,
+                        images: Image::new(large),
+                        rating,
+                        pubdate,
+                        publisher,
+                        summary,
+                        title,
+                    }),
+                }
+            })
+            .into_iter()
+            .filter(|x| !x.book.id.is_empty())
+            .collect::<Vec<HotBook>>()
+    }
+
+    /// 获取试读章节，没有试读时返回None
+    pub async fn get_book_preview(&self, id: &str) -> Result<Option<BookPreview>> {
+        let url = format!("{}/subject/{}/", self.client.book_base(), id);
+        let page = self.client.get_html(&url).await?;
+        let href = {
+            let document = Vis::load(&page.body).unwrap();
+            match document.find("a[href*='/reading/']").first().attr("href") {
+                Some(href) => href.to_string(),
+                None => return Ok(None),
+            }
+        };
+        // 试读链接可能是相对地址，按条目页的最终地址解析
+        let preview_url = Url::parse(&page.url)?.join(&href)?.to_string();
+
+        let res = self.client.get_html(&preview_url).await?.body;
+        let document = Vis::load(&res).unwrap();
+        let x = document.find("#content");
+        let title = x.find("h1").first().text().trim().to_string();
+        let mut article = x.find(".book-content");
+        if article.is_empty() {
+            article = x.find(".article");
+        }
+        let html = article.html().trim().to_string();
+        let mut paragraphs = Vec::new();
+        article.find("p").map(|_index, p| {
+            let text = p.text().trim().to_string();
+            if !text.is_empty() {
+                paragraphs.push(text);
+            }
+        });
+        let text = if paragraphs.is_empty() {
+            article.text().trim().to_string()
+        } else {
+            paragraphs.join("\n")
+        };
+
+        Ok(Some(BookPreview {
+            id: id.to_string(),
+            title,
+            url: preview_url,
+            text,
+            html,
+        }))
+    }
+
     pub async fn get_book_info_by_isbn(&self, isbn: &str) -> Result<DoubanBook> {
         let cache_key = isbn.to_string();
         if BOOK_CACHE.get(&cache_key).is_some() {
             return Ok(BOOK_CACHE.get(&cache_key).unwrap());
         }
 
-        let url = format!("https://douban.com/isbn/{}/", isbn);
+        let url = format!("{}/isbn/{}/", self.client.book_base(), isbn);
         self.get_book_internal(url).await
     }
 
@@ -267,14 +768,97 @@ impl DoubanBookApi {
         if BOOK_CACHE.get(&cache_key).is_some() {
             return Ok(BOOK_CACHE.get(&cache_key).unwrap());
         }
-        let url = format!("https://book.douban.com/subject/{}/", id);
+        let url = format!("{}/subject/{}/", self.client.book_base(), id);
         self.get_book_internal(url).await
     }
 
+    /// 按id或isbn获取图书信息，同时返回是否命中缓存、抓取时间与剩余缓存时间
+    pub async fn get_book_info_meta(
+        &self,
+        key: &str,
+        by_isbn: bool,
+    ) -> Result<(DoubanBook, CacheMeta)> {
+        let hit = BOOK_CACHE.get(&key.to_string()).is_some();
+        let info = if by_isbn {
+            self.get_book_info_by_isbn(key).await?
+        } else {
+            self.get_book_info(key).await?
+        };
+        let meta = CacheMeta::new(hit, BOOK_FETCHED_AT.get(&key.to_string()), BOOK_CACHE_TTL);
+        Ok((info, meta))
+    }
+
+    /// 按豆瓣图床规则把封面大图(/view/subject/l/)推断为原图(/view/subject/raw/)，校验不可用时返回大图
+    async fn get_raw_cover(&self, large: &str) -> String {
+        if !large.contains("/view/subject/l/") {
+            return large.to_string();
+        }
+        let raw = large.replacen("/view/subject/l/", "/view/subject/raw/", 1);
+        match self.client.head(&raw).send().await {
+            Ok(res) if res.status().is_success() => raw,
+            _ => large.to_string(),
+        }
+    }
+
+    /// 按标题+作者去重，保留评价人数最多的版本
+    fn dedupe(&self, list: Vec<DoubanBook>) -> Vec<DoubanBook> {
+        let mut result: Vec<DoubanBook> = Vec::with_capacity(list.len());
+        for book in list.into_iter() {
+            let exists = result
+                .iter_mut()
+                .find(|x| x.title == book.title && x.author == book.author);
+            match exists {
+                Some(x) => {
+                    if book.rating.num_raters > x.rating.num_raters {
+                        *x = book;
+                    }
+                }
+                None => result.push(book),
+            }
+        }
+
+        result
+    }
+
+    /// 购买信息中豆瓣阅读的电子书价格
+    fn parse_ebook_price(&self, x: &visdom::types::Elements) -> String {
+        let mut price = String::new();
+        x.find("#buyinfo li").map(|_index, li| {
+            let li = Vis::dom(li);
+            if price.is_empty() && li.find("a[href*='read.douban.com']").length() > 0 {
+                if let Some(cap) = self.re_price.captures(li.text()) {
+                    price = cap[1].to_string();
+                }
+            }
+        });
+
+        price
+    }
+
+    fn parse_num_raters(&self, text: &str) -> u32 {
+        match self.re_num_raters.captures(text) {
+            Some(x) => x[1].parse::<u32>().unwrap_or(0),
+            None => 0,
+        }
+    }
+
     fn get_text(&self, info_text_map: &HashMap<String, String>, key: &str) -> String {
         info_text_map.get(key).unwrap_or(&String::new()).to_string()
     }
 
+    /// 只取“作者”到下一个字段之间的作者链接，排除译者等
+    fn parse_author_ids(&self, html: &str) -> Vec<String> {
+        let rest = match html.find("作者") {
+            Some(start) => &html[start..],
+            None => return Vec::new(),
+        };
+        let end = rest.find("<span class=\"pl\">").unwrap_or(rest.len());
+        self.re_author_id
+            .captures_iter(&rest[..end])
+            .map(|x| x[1].to_string())
+            .collect()
+    }
+
     fn get_texts(&self, info_text_map: &HashMap<String, String>, key: &str) -> Vec<String> {
         info_text_map
             .get(key)
@@ -298,6 +882,42 @@ impl DoubanBookApi {
     }
 }
 
+/// 出版年排序键，兼容 2019-7、2019年7月1日 等格式，缺少的部分为0
+fn pubdate_key(pubdate: &str) -> (u32, u32, u32) {
+    let mut parts = pubdate
+        .split(|c: char| !c.is_ascii_digit())
+        .filter(|x| !x.is_empty())
+        .map(|x| x.parse::<u32>().unwrap_or(0));
+    (
+        parts.next().unwrap_or(0),
+        parts.next().unwrap_or(0),
+        parts.next().unwrap_or(0),
+    )
+}
+
+/// 搜索结果页被重定向到登录页，或页面只有登录提示没有搜索结果
+pub(crate) fn is_login_wall(url: &str, body: &str) -> bool {
+    url.contains("accounts.douban.com")
+        || url.contains("sec.douban.com")
+        || (!body.contains("result-list") && body.contains("登录"))
+}
+
+#[derive(Debug, Deserialize)]
+struct BookSuggestItem {
+    #[serde(default)]
+    id: String,
+    #[serde(default)]
+    title: String,
+    #[serde(default)]
+    pic: String,
+    #[serde(default)]
+    author_name: String,
+    #[serde(default)]
+    year: String,
+    #[serde(default)]
+    r#type: String,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DoubanBookResult<T> {
     code: u32,
@@ -305,28 +925,77 @@ pub struct DoubanBookResult<T> {
     books: Vec<T>,
 }
 
+impl DoubanBookResult<DoubanBook> {
+    /// 作者作品列表每页15条，满页时视为还有下一页
+    pub fn into_author_works_page(self, start: usize) -> Page<DoubanBook> {
+        let has_more = self.books.len() >= AUTHOR_WORKS_PAGE_SIZE;
+        Page::new(self.books, start, None, has_more)
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DoubanBook {
-    id: String,               //id
-    author: Vec<String>,      //作者
-    author_intro: String,     //作者简介
-    translators: Vec<String>, //译者
-    images: Image,            //封面
-    binding: String,          //装帧方式
-    category: String,         //分类
-    rating: Rating,           //评分
-    isbn13: String,           //isbn
-    pages: String,            //页数
-    price: String,            //价格
-    pubdate: String,          //出版时间
-    publisher: String,        //出版社
-    producer: String,         //出品方
-    serials: String,          //丛书
-    subtitle: String,         //副标题
-    summary: String,          //简介
-    title: String,            //书名
-    tags: Vec<Tag>,           //标签
-    origin: String,           //原作名
+    id: String,                     //id
+    author: Vec<String>,            //作者
+    author_ids: Vec<String>,        //作者主页 id
+    author_intro: String,           //作者简介
+    author_intro_text: String,      //作者简介纯文本
+    translators: Vec<String>,       //译者
+    images: Image,                  //封面
+    binding: String,                //装帧方式
+    category: String,               //分类
+    rating: Rating,                 //评分
+    isbn13: String,                 //isbn
+    pages: String,                  //页数
+    price: String,                  //价格
+    ebook_price: String,            //电子书价格
+    pubdate: String,                //出版时间
+    pubdate_normalized: String,     //ISO 8601格式的出版时间，无法解析时同原文
+    publisher: String,              //出版社
+    producer: String,               //出品方
+    serials: String,                //丛书
+    subtitle: String,               //副标题
+    summary: String,                //简介
+    summary_text: String,           //简介纯文本
+    title: String,                  //书名
+    tags: Vec<Tag>,                 //标签
+    origin: String,                 //原作名
+    content_rating: Option<String>, //分级，没有时为null
+}
+
+/// 简化的MARC记录，tag为字段号，subfields为子字段(code为子字段标识)
+#[derive(Debug, Clone, Serialize)]
+pub struct Marc {
+    id: String,
+    fields: Vec<MarcField>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MarcField {
+    tag: &'static str,
+    subfields: Vec<MarcSubfield>,
+}
+
+impl MarcField {
+    fn new(tag: &'static str, subfields: &[(&'static str, &String)]) -> MarcField {
+        MarcField {
+            tag,
+            subfields: subfields
+                .iter()
+                .filter(|(_, value)| !value.trim().is_empty())
+                .map(|(code, value)| MarcSubfield {
+                    code,
+                    value: value.trim().to_string(),
+                })
+                .collect(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MarcSubfield {
+    code: &'static str,
+    value: String,
 }
 
 pub struct SimpleDoubanBook {
@@ -345,7 +1014,9 @@ impl DoubanBook {
         DoubanBook {
             id: info.id,
             author: info.author,
+            author_ids: Vec::new(),
             author_intro: String::new(),
+            author_intro_text: String::new(),
             translators: Vec::new(),
             images: info.images,
             binding: String::new(),
@@ -354,32 +1025,213 @@ impl DoubanBook {
             isbn13: String::new(),
             pages: String::new(),
             price: String::new(),
+            ebook_price: String::new(),
+            pubdate_normalized: normalize_date(&info.pubdate),
             pubdate: info.pubdate,
             publisher: info.publisher,
             producer: String::new(),
             serials: String::new(),
             subtitle: String::new(),
+            summary_text: html_to_text(&info.summary),
             summary: info.summary,
             title: info.title,
             tags: Vec::new(),
             origin: String::new(),
+            content_rating: None,
+        }
+    }
+
+    /// 映射为简化的CNMARC编目字段，空字段不输出
+    pub fn to_marc(&self) -> Marc {
+        let mut fields = vec![
+            MarcField::new(
+                "010",
+                &[
+                    ("a", &self.isbn13),
+                    ("b", &self.binding),
+                    ("d", &self.price),
+                ],
+            ),
+            MarcField::new(
+                "200",
+                &[
+                    ("a", &self.title),
+                    ("e", &self.subtitle),
+                    ("f", &self.author.join(", ")),
+                    ("g", &self.translators.join(", ")),
+                ],
+            ),
+            MarcField::new("210", &[("c", &self.publisher), ("d", &self.pubdate)]),
+            MarcField::new("215", &[("a", &self.pages)]),
+            MarcField::new("225", &[("a", &self.serials)]),
+            MarcField::new("330", &[("a", &self.summary)]),
+            MarcField::new("454", &[("a", &self.origin)]),
+        ];
+        fields.extend(
+            self.tags
+                .iter()
+                .map(|x| MarcField::new("610", &[("a", &x.name)])),
+        );
+        fields.push(MarcField::new("686", &[("a", &self.category)]));
+        fields.extend(
+            self.author
+                .iter()
+                .map(|x| MarcField::new("701", &[("a", x)])),
+        );
+        fields.extend(
+            self.translators
+                .iter()
+                .map(|x| MarcField::new("702", &[("a", x), ("4", &"译".to_string())])),
+        );
+        fields.retain(|x| !x.subfields.is_empty());
+
+        Marc {
+            id: self.id.clone(),
+            fields,
+        }
+    }
+
+    /// 输出 calibre 使用的 metadata.opf
+    pub fn to_opf(&self) -> String {
+        let mut meta = String::new();
+        meta.push_str(&format!(
+            "    <dc:identifier id=\"uuid_id\" opf:scheme=\"DOUBAN\">{}</dc:identifier>\n",
+            xml_escape(&self.id)
+        ));
+        if !self.isbn13.is_empty() {
+            meta.push_str(&format!(
+                "    <dc:identifier opf:scheme=\"ISBN\">{}</dc:identifier>\n",
+                xml_escape(&self.isbn13)
+            ));
+        }
+        meta.push_str(&format!(
+            "    <dc:title>{}</dc:title>\n",
+            xml_escape(&self.title)
+        ));
+        for author in self.author.iter() {
+            meta.push_str(&format!(
+                "    <dc:creator opf:role=\"aut\">{}</dc:creator>\n",
+                xml_escape(author)
+            ));
         }
+        for translator in self.translators.iter() {
+            meta.push_str(&format!(
+                "    <dc:contributor opf:role=\"trl\">{}</dc:contributor>\n",
+                xml_escape(translator)
+            ));
+        }
+        if !self.publisher.is_empty() {
+            meta.push_str(&format!(
+                "    <dc:publisher>{}</dc:publisher>\n",
+                xml_escape(&self.publisher)
+            ));
+        }
+        if !self.pubdate.is_empty() {
+            meta.push_str(&format!(
+                "    <dc:date>{}</dc:date>\n",
+                xml_escape(&self.pubdate)
+            ));
+        }
+        if !self.summary.is_empty() {
+            meta.push_str(&format!(
+                "    <dc:description>{}</dc:description>\n",
+                xml_escape(&self.summary)
+            ));
+        }
+        for tag in self.tags.iter() {
+            meta.push_str(&format!(
+                "    <dc:subject>{}</dc:subject>\n",
+                xml_escape(&tag.name)
+            ));
+        }
+        if !self.serials.is_empty() {
+            meta.push_str(&format!(
+                "    <meta name=\"calibre:series\" content=\"{}\"/>\n",
+                xml_escape(&self.serials)
+            ));
+        }
+        meta.push_str(&format!(
+            "    <meta name=\"calibre:rating\" content=\"{}\"/>\n",
+            self.rating.average
+        ));
+
+        format!(
+            r#"<?xml version="1.0" encoding="utf-8"?>
+<package xmlns="http://www.idpf.org/2007/opf" unique-identifier="uuid_id" version="2.0">
+  <metadata xmlns:dc="http://purl.org/dc/elements/1.1/" xmlns:opf="http://www.idpf.org/2007/opf">
+{}  </metadata>
+  <guide>
+    <reference type="cover" title="Cover" href="{}"/>
+  </guide>
+</package>
+"#,
+            meta,
+            xml_escape(&self.images.large)
+        )
     }
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct TagSnapshot {
+    tag: String,
+    taken_at: u64,
+    items: Vec<TagChartItem>, //按榜单顺序
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct TagChartItem {
+    id: String,
+    title: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TagHistory {
+    tag: String,
+    id: String,
+    title: String, //最近一次上榜时的书名
+    history: Vec<TagRank>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TagRank {
+    taken_at: u64,
+    rank: Option<u32>, //未上榜时为null
+}
+
+/// 热门新书榜条目，kind为fiction(虚构类)/nonfiction(非虚构类)
+#[derive(Debug, Clone, Serialize)]
+pub struct HotBook {
+    kind: &'static str,
+    rank: u32,
+    #[serde(flatten)]
+    book: DoubanBook,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BookPreview {
+    id: String,    //id
+    title: String, //章节标题
+    url: String,   //试读页地址
+    text: String,  //纯文本
+    html: String,  //html
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Image {
     small: String,
     medium: String,
     large: String,
+    #[serde(default)]
+    raw: String, //高清原图，不可用时同large
 }
 
 impl Image {
-    fn new(large: String) -> Image {
+    pub(crate) fn new(large: String) -> Image {
         Image {
             large,
             medium: String::new(),
             small: String::new(),
+            raw: String::new(),
         }
     }
 }
@@ -389,32 +1241,24 @@ pub struct Tag {
     name: String,
 }
 
+impl Tag {
+    pub(crate) fn new(name: String) -> Tag {
+        Tag { name }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Rating {
     average: f32,
+    #[serde(rename = "numRaters")]
+    num_raters: u32,
 }
 
 impl Rating {
-    fn new(rating: f32) -> Rating {
-        Rating { average: rating }
+    pub(crate) fn new(rating: f32, num_raters: u32) -> Rating {
+        Rating {
+            average: rating,
+            num_raters,
+        }
     }
 }
-
-#[derive(Debug, Serialize, Deserialize)]
-pub struct HtmlResult {
-    count: i32,
-    html: String,
-    limit: i32,
-}
-
-#[derive(Debug, Serialize, Deserialize)]
-pub struct BookListItem {
-    title: String,       //书名
-    id: String,          //id
-    author: Vec<String>, //作者
-    pubdate: String,     //出版时间
-    publisher: String,   //出版社
-    images: Image,       //封面
-    rating: Rating,      //评分
-    summary: String,     //简介
-}
@@ -0,0 +1,151 @@
+use crate::util::now_secs;
+use lazy_static::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::RwLock;
+
+lazy_static! {
+    // 条目最近一次抓取与评分变化的时间，key 为 {kind}_{id}
+    static ref CHANGES: RwLock<HashMap<String, Change>> = RwLock::new(HashMap::new());
+}
+
+const CHANGE_INDEX_SIZE: usize = 20000;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Change {
+    pub fetched_at: u64, //最近一次抓取时间
+    pub changed_at: u64, //评分最近一次变化的时间，首次抓取时同fetched_at
+    pub rating: String,
+}
+
+/// 记录一次详情抓取，评分与上次不同时更新changed_at
+pub fn record(kind: &str, id: &str, rating: &str) {
+    let key = format!("{}_{}", kind, id);
+    let now = now_secs();
+    let mut changes = CHANGES.write().unwrap();
+    match changes.get_mut(&key) {
+        Some(change) => {
+            if change.rating != rating {
+                change.rating = rating.to_string();
+                change.changed_at = now;
+            }
+            change.fetched_at = now;
+        }
+        None => {
+            if changes.len() >= CHANGE_INDEX_SIZE {
+                evict(&mut changes);
+            }
+            changes.insert(
+                key,
+                Change {
+                    fetched_at: now,
+                    changed_at: now,
+                    rating: rating.to_string(),
+                },
+            );
+        }
+    }
+}
+
+pub fn get(kind: &str, id: &str) -> Option<Change> {
+    CHANGES
+        .read()
+        .unwrap()
+        .get(&format!("{}_{}", kind, id))
+        .cloned()
+}
+
+/// 导出全部变化记录，key 为 {kind}_{id}
+pub fn all() -> Vec<(String, Change)> {
+    CHANGES
+        .read()
+        .unwrap()
+        .iter()
+        .map(|(key, change)| (key.clone(), change.clone()))
+        .collect()
+}
+
+/// 导入变化记录，已有的以较新的抓取为准
+pub fn insert(key: String, change: Change) {
+    let mut changes = CHANGES.write().unwrap();
+    match changes.get(&key) {
+        Some(x) if x.fetched_at >= change.fetched_at => {}
+        Some(_) => {
+            changes.insert(key, change);
+        }
+        None => {
+            if changes.len() >= CHANGE_INDEX_SIZE {
+                evict(&mut changes);
+            }
+            changes.insert(key, change);
+        }
+    }
+}
+
+/// 索引已满时淘汰最久未抓取的条目
+fn evict(changes: &mut HashMap<String, Change>) {
+    let oldest = changes
+        .iter()
+        .min_by_key(|(_, x)| x.fetched_at)
+        .map(|(key, _)| key.clone());
+    if let Some(key) = oldest {
+        changes.remove(&key);
+    }
+}
+
+/// 写入快照文件
+pub async fn save(file: &str) {
+    if file.is_empty() {
+        return;
+    }
+    let data = serde_json::to_vec(&*CHANGES.read().unwrap()).unwrap();
+    if let Err(err) = tokio::fs::write(file, data).await {
+        println!("写入变化记录快照失败: {:?}", err);
+    }
+}
+
+/// 加载快照文件，文件不存在时忽略，与已有记录合并
+pub async fn load(file: &str) {
+    if file.is_empty() {
+        return;
+    }
+    let data = match tokio::fs::read(file).await {
+        Ok(data) => data,
+        Err(_) => return,
+    };
+    match serde_json::from_slice::<HashMap<String, Change>>(&data) {
+        Ok(changes) => {
+            for (key, change) in changes {
+                insert(key, change);
+            }
+        }
+        Err(err) => println!("解析变化记录快照失败: {:?}", err),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn change(fetched_at: u64) -> Change {
+        Change {
+            fetched_at,
+            changed_at: fetched_at,
+            rating: String::new(),
+        }
+    }
+
+    #[test]
+    fn evict_oldest_fetch() {
+        let mut changes = HashMap::new();
+        changes.insert("movie_1".to_string(), change(300));
+        changes.insert("movie_2".to_string(), change(100));
+        changes.insert("book_3".to_string(), change(200));
+
+        evict(&mut changes);
+        assert!(!changes.contains_key("movie_2"));
+        evict(&mut changes);
+        assert!(!changes.contains_key("book_3"));
+        assert!(changes.contains_key("movie_1"));
+    }
+}
@@ -0,0 +1,92 @@
+use actix_web::body::{to_bytes, BoxBody, MessageBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::Method;
+use actix_web::{web, Error};
+use serde_json::Value;
+use std::collections::HashMap;
+use zhconv::{zhconv, Variant};
+
+/// 繁简转换方式，t2s: 查询词转简体；s2t: 查询词转简体，并把返回结果转为繁体
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Convert {
+    T2s,
+    S2t,
+}
+
+impl Convert {
+    pub fn parse(text: &str) -> Option<Convert> {
+        match text {
+            "t2s" => Some(Convert::T2s),
+            "s2t" => Some(Convert::S2t),
+            _ => None,
+        }
+    }
+}
+
+/// 豆瓣只支持简体搜索，两种转换方式都先把查询词转为简体
+pub fn to_simplified(text: &str) -> String {
+    zhconv(text, Variant::ZhHans)
+}
+
+pub fn to_traditional(text: &str) -> String {
+    zhconv(text, Variant::ZhHant)
+}
+
+/// 获取GET请求中需要把结果转为繁体的convert参数
+pub fn get_convert(req: &ServiceRequest) -> Option<Convert> {
+    if req.method() != Method::GET {
+        return None;
+    }
+    let query = web::Query::<HashMap<String, String>>::from_query(req.query_string()).ok()?;
+    match Convert::parse(query.get("convert")?) {
+        Some(Convert::S2t) => Some(Convert::S2t),
+        _ => None,
+    }
+}
+
+/// 把json响应中的字符串转为繁体，非json响应原样返回
+pub async fn to_traditional_response<B>(res: ServiceResponse<B>) -> Result<ServiceResponse, Error>
+where
+    B: MessageBody + 'static,
+{
+    if !res.status().is_success() {
+        return Ok(res.map_into_boxed_body());
+    }
+
+    let (req, res) = res.into_parts();
+    let (res, body) = res.into_parts();
+    let bytes = to_bytes(body)
+        .await
+        .map_err(|e| actix_web::error::ErrorInternalServerError(e.into()))?;
+    let body = if bytes.starts_with(b"{") || bytes.starts_with(b"[") {
+        match serde_json::from_slice::<Value>(&bytes) {
+            Ok(mut value) => {
+                convert_value(&mut value);
+                BoxBody::new(value.to_string())
+            }
+            Err(_) => BoxBody::new(bytes),
+        }
+    } else {
+        BoxBody::new(bytes)
+    };
+    let res = res.set_body(body);
+    Ok(ServiceResponse::new(req, res))
+}
+
+fn convert_value(value: &mut Value) {
+    match value {
+        // 只有ascii字符的(id、链接等)不需要转换
+        Value::String(s) if !s.is_ascii() => *s = to_traditional(s),
+        Value::Object(map) => {
+            for v in map.values_mut() {
+                convert_value(v);
+            }
+        }
+        Value::Array(list) => {
+            for v in list.iter_mut() {
+                convert_value(v);
+            }
+        }
+        _ => {}
+    }
+}
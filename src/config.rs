@@ -1,4 +1,4 @@
-use clap::Parser;
+use clap::{Parser, Subcommand};
 use serde::Deserialize;
 
 #[derive(Parser, Debug, Clone, Deserialize)]
@@ -14,6 +14,188 @@ pub struct Opt {
     pub limit: usize,
     #[clap(long, default_value = "", env = "DOUBAN_COOKIE")]
     pub cookie: String,
+    /// Douban account, used to login and renew cookie
+    #[clap(long, default_value = "", env = "DOUBAN_USER")]
+    pub user: String,
+    #[clap(long, default_value = "", env = "DOUBAN_PASSWORD")]
+    pub password: String,
+    /// Cookie renew interval in hours, 0 to disable
+    #[clap(long, default_value = "0", env = "DOUBAN_AUTH_REFRESH_INTERVAL")]
+    pub auth_refresh_interval: u64,
+    /// Movie search fallback chain, format: source[:timeout_secs],...
+    /// available sources: suggest, web, mobile, local
+    #[clap(long, default_value = "web:30", env = "DOUBAN_SEARCH_STRATEGY")]
+    pub search_strategy: String,
+    /// Keywords of noise search results (trailers, behind the scenes) separated by comma,
+    /// results without year and rating whose title contains one of them are skipped, empty to disable
+    #[clap(long, default_value = "花絮,预告", env = "DOUBAN_NOISE_KEYWORDS")]
+    pub noise_keywords: String,
+    /// Upstream entries separated by comma, "direct" or a proxy url like http://127.0.0.1:7890 or socks5://127.0.0.1:1080
+    #[clap(long, alias = "proxy", default_value = "direct", env = "DOUBAN_UPSTREAMS")]
+    pub upstreams: String,
+    /// Upstream health probe interval in seconds
+    #[clap(long, default_value = "60", env = "DOUBAN_PROBE_INTERVAL")]
+    pub probe_interval: u64,
+    /// Max requests per second to douban, adjusted automatically when throttled, 0 to disable
+    #[clap(long, default_value = "0", env = "DOUBAN_MAX_QPS")]
+    pub max_qps: f64,
+    /// Min requests per second when throttled
+    #[clap(long, default_value = "0.2", env = "DOUBAN_MIN_QPS")]
+    pub min_qps: f64,
+    /// Max retries of GET/HEAD requests on timeout, connection error or 502/503/504, 0 to disable
+    #[clap(long, default_value = "2", env = "DOUBAN_RETRY_COUNT")]
+    pub retry_count: u32,
+    /// Initial retry backoff in milliseconds, doubled on each retry
+    #[clap(long, default_value = "500", env = "DOUBAN_RETRY_BACKOFF")]
+    pub retry_backoff: u64,
+    /// Disk http cache directory for upstream pages, empty to disable
+    #[clap(long, default_value = "", env = "DOUBAN_HTTP_CACHE_DIR")]
+    pub http_cache_dir: String,
+    /// Max number of pages kept in the disk http cache, oldest evicted first
+    #[clap(long, default_value = "10000", env = "DOUBAN_HTTP_CACHE_MAX_ENTRIES")]
+    pub http_cache_max_entries: usize,
+    /// New releases chart refresh interval in minutes, 0 to disable
+    #[clap(long, default_value = "0", env = "DOUBAN_REFRESH_INTERVAL")]
+    pub refresh_interval: u64,
+    /// New releases chart refresh cron "min hour day month weekday" in DOUBAN_TIMEZONE, overrides the interval
+    #[clap(long, default_value = "", env = "DOUBAN_REFRESH_CRON")]
+    pub refresh_cron: String,
+    /// Subscription check interval in minutes, 0 to disable
+    #[clap(long, default_value = "30", env = "DOUBAN_SUBSCRIPTION_INTERVAL")]
+    pub subscription_interval: u64,
+    /// Book tags to snapshot chart periodically, separated by comma, empty to disable
+    #[clap(long, default_value = "", env = "DOUBAN_BOOK_TAGS")]
+    pub book_tags: String,
+    /// Book tag chart snapshot interval in minutes
+    #[clap(long, default_value = "360", env = "DOUBAN_BOOK_TAG_INTERVAL")]
+    pub book_tag_interval: u64,
+    /// Book tag chart snapshot file, empty to keep in memory only
+    #[clap(long, default_value = "", env = "DOUBAN_BOOK_TAG_SNAPSHOT_FILE")]
+    pub book_tag_snapshot_file: String,
+    /// Interval in minutes to refetch wish/doing counts of tracked movies, 0 to disable
+    #[clap(long, default_value = "0", env = "DOUBAN_TRENDING_INTERVAL")]
+    pub trending_interval: u64,
+    /// Wish/doing count snapshot file, empty to keep in memory only
+    #[clap(long, default_value = "", env = "DOUBAN_TRENDING_FILE")]
+    pub trending_file: String,
+    /// Rating change index snapshot file for /sync/changed, empty to keep in memory only
+    #[clap(long, default_value = "", env = "DOUBAN_CHANGES_FILE")]
+    pub changes_file: String,
+    /// Rating change index snapshot interval in minutes
+    #[clap(long, default_value = "10", env = "DOUBAN_CHANGES_INTERVAL")]
+    pub changes_interval: u64,
+    /// Max concurrent detail fetches of search with type=full
+    #[clap(long, default_value = "4", env = "DOUBAN_CONCURRENCY")]
+    pub concurrency: usize,
+    /// OMDb api key, supplements imdb_rating and imdb_votes of movie detail when set
+    #[clap(long, default_value = "", env = "DOUBAN_OMDB_API_KEY")]
+    pub omdb_api_key: String,
+    /// Max image size of /proxy in MB
+    #[clap(long, default_value = "20", env = "DOUBAN_PROXY_MAX_SIZE")]
+    pub proxy_max_size: u64,
+    /// Max concurrent downloads of /proxy
+    #[clap(long, default_value = "10", env = "DOUBAN_PROXY_CONCURRENCY")]
+    pub proxy_concurrency: usize,
+    /// Download timeout of /proxy in seconds
+    #[clap(long, default_value = "30", env = "DOUBAN_PROXY_TIMEOUT")]
+    pub proxy_timeout: u64,
+    /// Base url of douban movie, for mirror sites or mock servers
+    #[clap(
+        long,
+        default_value = "https://movie.douban.com",
+        env = "DOUBAN_MOVIE_BASE_URL"
+    )]
+    pub movie_base_url: String,
+    /// Base url of douban book
+    #[clap(
+        long,
+        default_value = "https://book.douban.com",
+        env = "DOUBAN_BOOK_BASE_URL"
+    )]
+    pub book_base_url: String,
+    /// Base url of douban music
+    #[clap(
+        long,
+        default_value = "https://music.douban.com",
+        env = "DOUBAN_MUSIC_BASE_URL"
+    )]
+    pub music_base_url: String,
+    /// Base url of douban main site, used by search and doulist
+    #[clap(
+        long,
+        default_value = "https://www.douban.com",
+        env = "DOUBAN_WWW_BASE_URL"
+    )]
+    pub www_base_url: String,
+    /// Base url of douban mobile site
+    #[clap(
+        long,
+        default_value = "https://m.douban.com",
+        env = "DOUBAN_MOBILE_BASE_URL"
+    )]
+    pub mobile_base_url: String,
+    /// Max value of ?timeout_ms= in milliseconds
+    #[clap(long, default_value = "60000", env = "DOUBAN_MAX_TIMEOUT_MS")]
+    pub max_timeout_ms: u64,
+    /// Offline mode, movie search only returns cached data
+    #[clap(long, env = "DOUBAN_OFFLINE")]
+    pub offline: bool,
+    /// Disable jsonp output of ?callback=fn
+    #[clap(long, env = "DOUBAN_DISABLE_JSONP")]
+    pub disable_jsonp: bool,
+    /// Enable /movies/{sid}/parental-guide, fetching parental guide from IMDb
+    #[clap(long, env = "DOUBAN_IMDB_PARENTAL_GUIDE")]
+    pub imdb_parental_guide: bool,
+    /// Token for /admin endpoints, passed by X-Admin-Token header, empty to disable check
+    #[clap(long, default_value = "", env = "DOUBAN_ADMIN_TOKEN")]
+    pub admin_token: String,
+    /// Allowed CORS origins separated by comma, * to allow all, empty to disable
+    #[clap(long, default_value = "", env = "DOUBAN_CORS_ORIGINS")]
+    pub cors_origins: String,
+    /// Fields removed from all json responses, separated by comma, eg: intro,summary
+    #[clap(long, default_value = "", env = "DOUBAN_HIDDEN_FIELDS")]
+    pub hidden_fields: String,
+    /// Archive directory of fetched douban pages (gzip), empty to disable
+    #[clap(long, default_value = "", env = "DOUBAN_ARCHIVE_DIR")]
+    pub archive_dir: String,
+    /// Json config file of hot reloadable items, reloaded by SIGHUP or POST /admin/reload
+    #[clap(long, default_value = "", env = "DOUBAN_CONFIG")]
+    pub config: String,
+    /// Timezone of output time fields, UTC or offset like +08:00
+    #[clap(long, default_value = "UTC", env = "DOUBAN_TIMEZONE")]
+    pub timezone: String,
     #[clap(short, long)]
     pub debug: bool,
+    #[clap(subcommand)]
+    #[serde(skip)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum Command {
+    /// Rebuild movie and celebrity cache from --archive-dir, output ndjson for /admin/cache/import
+    RebuildCache {
+        /// Output file
+        #[clap(short, long, default_value = "cache.ndjson")]
+        output: String,
+    },
+    /// Check DNS, douban connectivity, upstream proxies and cookie, print a diagnosis report
+    Doctor,
+}
+
+/// 配置文件中可热更的配置项，未配置的项保持当前值
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct HotConfig {
+    pub cookie: Option<String>,
+    pub upstreams: Option<String>,
+    pub max_qps: Option<f64>,
+    pub min_qps: Option<f64>,
+}
+
+impl HotConfig {
+    pub async fn load(path: &str) -> anyhow::Result<HotConfig> {
+        let data = tokio::fs::read(path).await?;
+        Ok(serde_json::from_slice(&data)?)
+    }
 }
@@ -0,0 +1,64 @@
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::header::{self, HeaderValue};
+use actix_web::http::Method;
+use actix_web::HttpResponse;
+
+const ALLOW_METHODS: &str = "GET, POST, OPTIONS";
+const MAX_AGE: &str = "86400";
+
+/// 解析允许跨域的来源，多个用逗号分隔，*为允许所有来源
+pub fn parse_origins(text: &str) -> Vec<String> {
+    text.split(',')
+        .map(|x| x.trim().trim_end_matches('/').to_string())
+        .filter(|x| !x.is_empty())
+        .collect()
+}
+
+/// 请求来源允许跨域时，返回Access-Control-Allow-Origin的值
+pub fn allow_origin(origins: &[String], req: &ServiceRequest) -> Option<String> {
+    let origin = req.headers().get(header::ORIGIN)?.to_str().ok()?;
+    if origins.iter().any(|x| x == "*") {
+        Some("*".to_string())
+    } else if origins.iter().any(|x| x == origin) {
+        Some(origin.to_string())
+    } else {
+        None
+    }
+}
+
+pub fn is_preflight(req: &ServiceRequest) -> bool {
+    req.method() == Method::OPTIONS
+        && req
+            .headers()
+            .contains_key(header::ACCESS_CONTROL_REQUEST_METHOD)
+}
+
+/// 直接响应预检请求
+pub fn preflight(req: ServiceRequest, origin: &str) -> ServiceResponse {
+    let allow_headers = req
+        .headers()
+        .get(header::ACCESS_CONTROL_REQUEST_HEADERS)
+        .cloned()
+        .unwrap_or_else(|| HeaderValue::from_static("Content-Type"));
+    let mut res = HttpResponse::NoContent()
+        .append_header((header::ACCESS_CONTROL_ALLOW_METHODS, ALLOW_METHODS))
+        .append_header((header::ACCESS_CONTROL_ALLOW_HEADERS, allow_headers))
+        .append_header((header::ACCESS_CONTROL_MAX_AGE, MAX_AGE))
+        .finish();
+    set_origin(res.headers_mut(), origin);
+    req.into_response(res)
+}
+
+/// 给跨域请求的响应加上允许来源
+pub fn add_headers<B>(res: &mut ServiceResponse<B>, origin: &str) {
+    set_origin(res.headers_mut(), origin);
+}
+
+fn set_origin(headers: &mut header::HeaderMap, origin: &str) {
+    if let Ok(value) = HeaderValue::from_str(origin) {
+        headers.insert(header::ACCESS_CONTROL_ALLOW_ORIGIN, value);
+    }
+    if origin != "*" {
+        headers.insert(header::VARY, HeaderValue::from_static("Origin"));
+    }
+}
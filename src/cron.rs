@@ -0,0 +1,169 @@
+use crate::util::civil_from_days;
+use anyhow::{anyhow, Result};
+
+// 最多向后查找的天数，覆盖闰年的2月29日
+const MAX_DAYS: i64 = 4 * 366;
+
+/// 五段cron表达式: 分 时 日 月 周，支持 *、a-b、a,b、*/n、a-b/n，周日为0或7，按配置的时区计算
+#[derive(Debug, Clone, PartialEq)]
+pub struct Schedule {
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    any_day: bool,     //日为*
+    any_weekday: bool, //周为*
+}
+
+impl Schedule {
+    pub fn parse(expr: &str) -> Result<Schedule> {
+        let fields = expr.split_whitespace().collect::<Vec<&str>>();
+        if fields.len() != 5 {
+            return Err(anyhow!("cron表达式应为5段(分 时 日 月 周): {}", expr));
+        }
+        let mut weekdays = parse_field(fields[4], 0, 7)?;
+        // 7与0都表示周日
+        if weekdays & (1 << 7) != 0 {
+            weekdays |= 1;
+        }
+
+        Ok(Schedule {
+            minutes: parse_field(fields[0], 0, 59)?,
+            hours: parse_field(fields[1], 0, 23)?,
+            days: parse_field(fields[2], 1, 31)?,
+            months: parse_field(fields[3], 1, 12)?,
+            weekdays,
+            any_day: fields[2] == "*",
+            any_weekday: fields[4] == "*",
+        })
+    }
+
+    /// secs之后(不含)的下一次触发时刻，unix时间戳，offset为时区偏移(秒)
+    pub fn next_after(&self, secs: u64, offset: i64) -> Option<u64> {
+        let local = ((secs / 60 + 1) * 60) as i64 + offset;
+        let first_day = local.div_euclid(86400);
+        for day in first_day..first_day + MAX_DAYS {
+            if !self.matches_day(day) {
+                continue;
+            }
+            let from = if day == first_day {
+                local.rem_euclid(86400) / 60
+            } else {
+                0
+            };
+            let minute = (from..24 * 60)
+                .find(|x| self.hours & (1 << (x / 60)) != 0 && self.minutes & (1 << (x % 60)) != 0);
+            if let Some(minute) = minute {
+                return Some((day * 86400 + minute * 60 - offset) as u64);
+            }
+        }
+
+        None
+    }
+
+    /// 日与周同时指定时满足其一即可，与标准cron一致
+    fn matches_day(&self, day: i64) -> bool {
+        let (_, month, dom) = civil_from_days(day);
+        if self.months & (1 << month) == 0 {
+            return false;
+        }
+        // 1970-01-01为周四
+        let weekday = (day + 4).rem_euclid(7);
+        let dom_ok = self.days & (1 << dom) != 0;
+        let dow_ok = self.weekdays & (1 << weekday) != 0;
+        match (self.any_day, self.any_weekday) {
+            (true, true) => true,
+            (true, false) => dow_ok,
+            (false, true) => dom_ok,
+            (false, false) => dom_ok || dow_ok,
+        }
+    }
+}
+
+/// 解析单个字段为位掩码
+fn parse_field(text: &str, min: u64, max: u64) -> Result<u64> {
+    let mut mask = 0;
+    for part in text.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (range, parse_num(step, 1, max)?),
+            None => (part, 1),
+        };
+        let (from, to) = match range {
+            "*" => (min, max),
+            x => match x.split_once('-') {
+                Some((from, to)) => (parse_num(from, min, max)?, parse_num(to, min, max)?),
+                // a/n 表示从a开始到最大值
+                None if part.contains('/') => (parse_num(x, min, max)?, max),
+                None => {
+                    let x = parse_num(x, min, max)?;
+                    (x, x)
+                }
+            },
+        };
+        if from > to {
+            return Err(anyhow!("cron范围无效: {}", part));
+        }
+        for x in (from..=to).step_by(step as usize) {
+            mask |= 1 << x;
+        }
+    }
+
+    Ok(mask)
+}
+
+fn parse_num(text: &str, min: u64, max: u64) -> Result<u64> {
+    match text.parse::<u64>() {
+        Ok(x) if (min..=max).contains(&x) => Ok(x),
+        _ => Err(anyhow!("cron字段取值应在{}-{}之间: {}", min, max, text)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 2024-01-01 00:00 北京时间，周一
+    const MONDAY: u64 = 1704038400;
+    const OFFSET: i64 = 8 * 3600;
+
+    #[test]
+    fn next_after_schedules() {
+        let daily = Schedule::parse("30 6 * * *").unwrap();
+        assert_eq!(
+            daily.next_after(MONDAY, OFFSET),
+            Some(MONDAY + 6 * 3600 + 30 * 60)
+        );
+        let at = MONDAY + 6 * 3600 + 30 * 60;
+        assert_eq!(daily.next_after(at, OFFSET), Some(at + 86400));
+
+        let every = Schedule::parse("*/15 * * * *").unwrap();
+        assert_eq!(
+            every.next_after(MONDAY + 60, OFFSET),
+            Some(MONDAY + 15 * 60)
+        );
+
+        let friday = Schedule::parse("0 8 * * 5").unwrap();
+        assert_eq!(
+            friday.next_after(MONDAY, OFFSET),
+            Some(MONDAY + 4 * 86400 + 8 * 3600)
+        );
+
+        let sunday = Schedule::parse("0 0 * * 7").unwrap();
+        assert_eq!(sunday.next_after(MONDAY, OFFSET), Some(MONDAY + 6 * 86400));
+
+        let monthly = Schedule::parse("0 9 1 * *").unwrap();
+        assert_eq!(
+            monthly.next_after(MONDAY + 10 * 3600, OFFSET),
+            Some(MONDAY + 31 * 86400 + 9 * 3600)
+        );
+    }
+
+    #[test]
+    fn parse_invalid() {
+        assert!(Schedule::parse("* * * *").is_err());
+        assert!(Schedule::parse("60 * * * *").is_err());
+        assert!(Schedule::parse("0 5-1 * * *").is_err());
+        assert!(Schedule::parse("0 0 0 * *").is_err());
+    }
+}
@@ -0,0 +1,89 @@
+use crate::config::Opt;
+use crate::http::HttpClient;
+use reqwest::Url;
+
+// 图片域名不受基础地址配置影响，单独检查解析
+const IMG_HOST: &str = "img1.doubanio.com";
+
+/// 输出部署自检报告，全部检查通过时返回true
+pub async fn run(opt: &Opt) -> bool {
+    let client = HttpClient::new(opt.clone());
+    let mut ok = true;
+
+    println!("== 运行环境 ==");
+    println!(
+        "douban-api-rs {} ({}-{})",
+        env!("CARGO_PKG_VERSION"),
+        std::env::consts::ARCH,
+        std::env::consts::OS
+    );
+
+    println!("== DNS解析 ==");
+    let mut hosts = vec![
+        client.movie_base(),
+        client.book_base(),
+        client.music_base(),
+        client.www_base(),
+        client.mobile_base(),
+    ]
+    .into_iter()
+    .filter_map(|x| Url::parse(x).ok())
+    .filter_map(|x| x.host_str().map(|x| x.to_string()))
+    .collect::<Vec<String>>();
+    hosts.push(IMG_HOST.to_string());
+    hosts.dedup();
+    for host in hosts.iter() {
+        match tokio::net::lookup_host((host.as_str(), 443)).await {
+            Ok(addrs) => {
+                let addrs = addrs.map(|x| x.ip().to_string()).collect::<Vec<String>>();
+                println!("[OK]   {} -> {}", host, addrs.join(", "));
+            }
+            Err(err) => {
+                ok = false;
+                println!("[FAIL] {} 解析失败: {}", host, err);
+            }
+        }
+    }
+
+    println!("== 上游连通性 ==");
+    for (name, res, elapsed) in client.check_upstreams().await {
+        match res {
+            Ok(status) if status < 400 => println!("[OK]   {} HTTP {} {}ms", name, status, elapsed),
+            Ok(status) => {
+                ok = false;
+                // 403/429通常为IP被豆瓣限流
+                println!("[FAIL] {} HTTP {} {}ms，可能被豆瓣限流", name, status, elapsed);
+            }
+            Err(err) => {
+                ok = false;
+                println!("[FAIL] {} 连接失败: {}", name, err);
+            }
+        }
+    }
+
+    println!("== 登录状态 ==");
+    if opt.cookie.is_empty() {
+        println!("[SKIP] 未配置DOUBAN_COOKIE，部分需登录访问的条目会搜索不到");
+    } else {
+        match client.check_cookie().await {
+            Ok(true) => println!("[OK]   cookie有效"),
+            Ok(false) => {
+                ok = false;
+                println!("[FAIL] cookie已失效，请重新获取DOUBAN_COOKIE");
+            }
+            Err(err) => {
+                ok = false;
+                println!("[FAIL] 检查cookie失败: {}", err);
+            }
+        }
+    }
+
+    println!("== 结果 ==");
+    if ok {
+        println!("全部检查通过");
+    } else {
+        println!("存在未通过的检查项");
+    }
+
+    ok
+}
@@ -0,0 +1,209 @@
+use actix_web::body::{BoxBody, MessageBody};
+use actix_web::dev::ServiceResponse;
+use actix_web::http::header::{HeaderValue, CONTENT_TYPE};
+use actix_web::http::StatusCode;
+use actix_web::{HttpResponse, ResponseError};
+use std::fmt;
+
+/// 稳定的错误码，调用方应按错误码而不是错误消息处理
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ErrorCode {
+    InvalidParam,
+    SubjectNotFound,
+    NotFound,
+    DoubanRateLimited,
+    NeedLogin,
+    UpstreamError,
+    UpstreamTimeout,
+    Unauthorized,
+    PayloadTooLarge,
+    TooManyRequests,
+    FeatureDisabled,
+    InternalError,
+}
+
+impl ErrorCode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ErrorCode::InvalidParam => "INVALID_PARAM",
+            ErrorCode::SubjectNotFound => "SUBJECT_NOT_FOUND",
+            ErrorCode::NotFound => "NOT_FOUND",
+            ErrorCode::DoubanRateLimited => "DOUBAN_RATE_LIMITED",
+            ErrorCode::NeedLogin => "NEED_LOGIN",
+            ErrorCode::UpstreamError => "UPSTREAM_ERROR",
+            ErrorCode::UpstreamTimeout => "UPSTREAM_TIMEOUT",
+            ErrorCode::Unauthorized => "UNAUTHORIZED",
+            ErrorCode::PayloadTooLarge => "PAYLOAD_TOO_LARGE",
+            ErrorCode::TooManyRequests => "TOO_MANY_REQUESTS",
+            ErrorCode::FeatureDisabled => "FEATURE_DISABLED",
+            ErrorCode::InternalError => "INTERNAL_ERROR",
+        }
+    }
+
+    pub fn status(&self) -> StatusCode {
+        match self {
+            ErrorCode::InvalidParam => StatusCode::BAD_REQUEST,
+            ErrorCode::SubjectNotFound | ErrorCode::NotFound | ErrorCode::FeatureDisabled => {
+                StatusCode::NOT_FOUND
+            }
+            ErrorCode::DoubanRateLimited | ErrorCode::NeedLogin => StatusCode::SERVICE_UNAVAILABLE,
+            ErrorCode::UpstreamError => StatusCode::BAD_GATEWAY,
+            ErrorCode::UpstreamTimeout => StatusCode::GATEWAY_TIMEOUT,
+            ErrorCode::Unauthorized => StatusCode::UNAUTHORIZED,
+            ErrorCode::PayloadTooLarge => StatusCode::PAYLOAD_TOO_LARGE,
+            ErrorCode::TooManyRequests => StatusCode::TOO_MANY_REQUESTS,
+            ErrorCode::InternalError => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    pub fn message(&self, lang: Lang) -> &'static str {
+        match (self, lang) {
+            (ErrorCode::InvalidParam, Lang::Zh) => "参数错误",
+            (ErrorCode::InvalidParam, Lang::En) => "Invalid parameter",
+            (ErrorCode::SubjectNotFound, Lang::Zh) => "条目不存在",
+            (ErrorCode::SubjectNotFound, Lang::En) => "Subject not found",
+            (ErrorCode::NotFound, Lang::Zh) => "资源不存在",
+            (ErrorCode::NotFound, Lang::En) => "Resource not found",
+            (ErrorCode::DoubanRateLimited, Lang::Zh) => "豆瓣请求被限流，请稍后重试",
+            (ErrorCode::DoubanRateLimited, Lang::En) => "Rate limited by douban, retry later",
+            (ErrorCode::NeedLogin, Lang::Zh) => "豆瓣要求登录，请配置cookie或账号密码",
+            (ErrorCode::NeedLogin, Lang::En) => {
+                "Douban requires login, configure cookie or account"
+            }
+            (ErrorCode::UpstreamError, Lang::Zh) => "请求豆瓣失败",
+            (ErrorCode::UpstreamError, Lang::En) => "Failed to request douban",
+            (ErrorCode::UpstreamTimeout, Lang::Zh) => "请求上游超时",
+            (ErrorCode::UpstreamTimeout, Lang::En) => "Upstream request timeout",
+            (ErrorCode::Unauthorized, Lang::Zh) => "管理口令错误",
+            (ErrorCode::Unauthorized, Lang::En) => "Invalid admin token",
+            (ErrorCode::PayloadTooLarge, Lang::Zh) => "内容过大",
+            (ErrorCode::PayloadTooLarge, Lang::En) => "Payload too large",
+            (ErrorCode::TooManyRequests, Lang::Zh) => "并发请求过多",
+            (ErrorCode::TooManyRequests, Lang::En) => "Too many concurrent requests",
+            (ErrorCode::FeatureDisabled, Lang::Zh) => "功能未开启",
+            (ErrorCode::FeatureDisabled, Lang::En) => "Feature disabled",
+            (ErrorCode::InternalError, Lang::Zh) => "服务内部错误",
+            (ErrorCode::InternalError, Lang::En) => "Internal server error",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Lang {
+    Zh,
+    En,
+}
+
+impl Lang {
+    /// 按 Accept-Language 优先级最高的语言选择，默认中文
+    pub fn from_accept_language(text: &str) -> Lang {
+        let first = text.split(',').next().unwrap_or("").trim().to_lowercase();
+        if first.starts_with("en") {
+            Lang::En
+        } else {
+            Lang::Zh
+        }
+    }
+}
+
+/// 接口错误，detail 为与语言无关的补充信息(参数名、上游错误等)
+#[derive(Debug)]
+pub struct ApiError {
+    pub code: ErrorCode,
+    pub detail: String,
+}
+
+impl ApiError {
+    pub fn new(code: ErrorCode) -> ApiError {
+        ApiError {
+            code,
+            detail: String::new(),
+        }
+    }
+
+    pub fn with_detail<T: fmt::Display>(code: ErrorCode, detail: T) -> ApiError {
+        ApiError {
+            code,
+            detail: detail.to_string(),
+        }
+    }
+
+    pub fn to_json(&self, lang: Lang) -> String {
+        serde_json::json!({
+            "code": self.code.as_str(),
+            "message": self.code.message(lang),
+            "detail": self.detail,
+        })
+        .to_string()
+    }
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.code.as_str(), self.detail)
+    }
+}
+
+impl std::error::Error for ApiError {}
+
+impl ResponseError for ApiError {
+    fn status_code(&self) -> StatusCode {
+        self.code.status()
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        HttpResponse::build(self.status_code())
+            .content_type("application/json; charset=utf-8")
+            .body(self.to_json(Lang::Zh))
+    }
+}
+
+/// 按上游错误类型归类错误码
+impl From<anyhow::Error> for ApiError {
+    fn from(err: anyhow::Error) -> ApiError {
+        let err = match err.downcast::<ApiError>() {
+            Ok(err) => return err,
+            Err(err) => err,
+        };
+        let code = match err.downcast_ref::<reqwest::Error>() {
+            Some(e) if e.is_timeout() => ErrorCode::UpstreamTimeout,
+            Some(e) => match e.status().map(|x| x.as_u16()) {
+                Some(404) => ErrorCode::SubjectNotFound,
+                Some(403) | Some(429) => ErrorCode::DoubanRateLimited,
+                _ => ErrorCode::UpstreamError,
+            },
+            None => ErrorCode::InternalError,
+        };
+        ApiError::with_detail(code, err)
+    }
+}
+
+impl From<reqwest::Error> for ApiError {
+    fn from(err: reqwest::Error) -> ApiError {
+        ApiError::from(anyhow::Error::from(err))
+    }
+}
+
+/// 把接口错误的响应替换为请求语言的错误消息
+pub fn localize<B>(res: ServiceResponse<B>, lang: Lang) -> ServiceResponse<BoxBody>
+where
+    B: MessageBody + 'static,
+{
+    let body = res
+        .response()
+        .error()
+        .and_then(|e| e.as_error::<ApiError>())
+        .map(|e| e.to_json(lang));
+    match body {
+        Some(body) => {
+            let (req, res) = res.into_parts();
+            let mut res = res.set_body(BoxBody::new(body));
+            res.headers_mut().insert(
+                CONTENT_TYPE,
+                HeaderValue::from_static("application/json; charset=utf-8"),
+            );
+            ServiceResponse::new(req, res)
+        }
+        None => res.map_into_boxed_body(),
+    }
+}
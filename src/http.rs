@@ -1,52 +1,877 @@
-use crate::config::Opt;
-use reqwest::header::{HeaderMap, HeaderValue};
-use reqwest::{cookie::Jar, Error, IntoUrl, Request, RequestBuilder, Response, Url};
+use crate::archive;
+use crate::config::{HotConfig, Opt};
+use crate::error::{ApiError, ErrorCode};
+use crate::metrics;
+use crate::util;
+use anyhow::anyhow;
+use reqwest::header::{
+    HeaderMap, HeaderValue, CACHE_CONTROL, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED,
+};
+use reqwest::redirect::Policy;
+use reqwest::{
+    cookie::Jar, Error, IntoUrl, Method, Proxy, Request, RequestBuilder, Response, StatusCode,
+    Url,
+};
+use serde::{Deserialize, Serialize};
 use std::future::Future;
-use std::sync::Arc;
-use std::time::Duration;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
-const ORIGIN: &str = "https://movie.douban.com";
-const REFERER: &str = "https://movie.douban.com/";
+const IMG_HOSTS: [&str; 4] = [
+    "img1.doubanio.com",
+    "img2.doubanio.com",
+    "img3.doubanio.com",
+    "img9.doubanio.com",
+];
+const LOGIN_URL: &str = "https://accounts.douban.com/j/mobile/login/basic";
+const LOGIN_REFERER: &str = "https://accounts.douban.com/passport/login";
+// 上游被限流(封IP)后的冷却时间，期间不再切换回该上游
+const UPSTREAM_BAN_SECS: u64 = 10 * 60;
+// 重试退避间隔的上限
+const RETRY_BACKOFF_MAX: Duration = Duration::from_secs(30);
+// http缓存每写入多少次检查一次文件数上限
+const CACHE_PRUNE_EVERY: usize = 64;
 const UA: &str = "Mozilla/5.0 (Macintosh; Intel Mac OS X 10_15_7) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/92.0.4515.131 Safari/537.36";
 
+tokio::task_local! {
+    // 当前请求自定义的上游超时
+    static REQUEST_TIMEOUT: Duration;
+}
+
+/// 在指定超时内执行，期间发出的上游请求也使用该超时，超时返回None
+pub async fn with_timeout<F: Future>(timeout: Option<Duration>, fut: F) -> Option<F::Output> {
+    match timeout {
+        Some(timeout) => REQUEST_TIMEOUT
+            .scope(timeout, tokio::time::timeout(timeout, fut))
+            .await
+            .ok(),
+        None => Some(fut.await),
+    }
+}
+
 #[derive(Clone)]
 pub struct HttpClient {
-    client: reqwest::Client, //请求客户端
+    upstreams: Arc<RwLock<Arc<Vec<Upstream>>>>,     //上游入口，cookie或代理变化时重建
+    current: Arc<AtomicUsize>,                      //当前使用的上游
+    user: String,                                   //豆瓣账号
+    password: String,                               //豆瓣密码
+    cache_dir: String,                              //http缓存目录
+    cache_max_entries: usize,                       //http缓存最多保留的页面数
+    cache_writes: Arc<AtomicUsize>,                 //http缓存写入次数，用于定期清理
+    archive_dir: String,                            //页面归档目录
+    limiter: Arc<RwLock<Option<Arc<RateLimiter>>>>, //自适应限速
+    retry_count: u32,                               //失败重试次数
+    retry_backoff: Duration,                        //首次重试的退避间隔
+    net: Arc<Mutex<NetConfig>>,                     //当前生效的可热更配置
+    headers: HeaderMap,                             //默认请求头
+    img_host: Arc<RwLock<String>>,                  //当前最快的图片域名
+    movie_base: String,                             //电影站基础地址
+    book_base: String,                              //读书站基础地址
+    music_base: String,                             //音乐站基础地址
+    www_base: String,                               //主站基础地址
+    mobile_base: String,                            //移动站基础地址
+}
+
+/// 可热更的网络配置
+#[derive(Debug, Clone, PartialEq)]
+struct NetConfig {
+    cookie: String,
+    upstreams: String,
+    min_qps: f64,
+    max_qps: f64,
+}
+
+struct Upstream {
+    name: String,
+    client: reqwest::Client,
+    proxy: Option<Proxy>, //代理，webhook回调也经由该代理发送
+    healthy: AtomicBool,
+    latency: AtomicU64,      //探测延迟，毫秒
+    banned_until: AtomicU64, //被限流后的冷却截止时间
 }
 
 impl HttpClient {
     pub fn new(config: Opt) -> HttpClient {
         let mut headers = HeaderMap::new();
-        headers.insert("Origin", HeaderValue::from_static(ORIGIN));
-        headers.insert("Referer", HeaderValue::from_static(REFERER));
+        let movie_base = config.movie_base_url.trim_end_matches('/').to_string();
+        if let Ok(origin) = HeaderValue::from_str(&movie_base) {
+            headers.insert("Origin", origin);
+        }
+        if let Ok(referer) = HeaderValue::from_str(&format!("{}/", movie_base)) {
+            headers.insert("Referer", referer);
+        }
+
+        let upstreams = build_upstreams(&config.upstreams, &config.cookie, &headers);
+        let net = NetConfig {
+            cookie: config.cookie,
+            upstreams: config.upstreams,
+            min_qps: config.min_qps,
+            max_qps: config.max_qps,
+        };
+        Self {
+            upstreams: Arc::new(RwLock::new(Arc::new(upstreams))),
+            current: Arc::new(AtomicUsize::new(0)),
+            user: config.user,
+            password: config.password,
+            cache_dir: config.http_cache_dir,
+            cache_max_entries: config.http_cache_max_entries,
+            cache_writes: Arc::new(AtomicUsize::new(0)),
+            archive_dir: config.archive_dir,
+            limiter: Arc::new(RwLock::new(
+                RateLimiter::new(net.min_qps, net.max_qps).map(Arc::new),
+            )),
+            retry_count: config.retry_count,
+            retry_backoff: Duration::from_millis(config.retry_backoff),
+            net: Arc::new(Mutex::new(net)),
+            headers,
+            img_host: Arc::new(RwLock::new(IMG_HOSTS[1].to_string())),
+            movie_base,
+            book_base: config.book_base_url.trim_end_matches('/').to_string(),
+            music_base: config.music_base_url.trim_end_matches('/').to_string(),
+            www_base: config.www_base_url.trim_end_matches('/').to_string(),
+            mobile_base: config.mobile_base_url.trim_end_matches('/').to_string(),
+        }
+    }
+
+    /// 电影站基础地址，不带结尾的/
+    pub fn movie_base(&self) -> &str {
+        &self.movie_base
+    }
+
+    /// 读书站基础地址
+    pub fn book_base(&self) -> &str {
+        &self.book_base
+    }
+
+    /// 音乐站基础地址
+    pub fn music_base(&self) -> &str {
+        &self.music_base
+    }
+
+    /// 主站(搜索、豆列)基础地址
+    pub fn www_base(&self) -> &str {
+        &self.www_base
+    }
+
+    /// 移动站基础地址
+    pub fn mobile_base(&self) -> &str {
+        &self.mobile_base
+    }
 
-        let url = "https://douban.com/".parse::<Url>().unwrap();
-        let jar = Jar::default();
-        if !config.cookie.is_empty() {
-            for s in config.cookie.split(";") {
-                let cookie_str = format!("{}; Domain=douban.com", s);
-                jar.add_cookie_str(cookie_str.as_str(), &url);
+    /// 应用热重载的配置，返回有变化的配置项，cookie或上游变化时重建连接池
+    pub fn reload(&self, config: &HotConfig) -> Vec<&'static str> {
+        let mut net = self.net.lock().unwrap();
+        let next = NetConfig {
+            cookie: config.cookie.clone().unwrap_or_else(|| net.cookie.clone()),
+            upstreams: config
+                .upstreams
+                .clone()
+                .unwrap_or_else(|| net.upstreams.clone()),
+            min_qps: config.min_qps.unwrap_or(net.min_qps),
+            max_qps: config.max_qps.unwrap_or(net.max_qps),
+        };
+        let mut changed = Vec::new();
+        if next.cookie != net.cookie {
+            changed.push("cookie");
+        }
+        if next.upstreams != net.upstreams {
+            changed.push("upstreams");
+        }
+        if next.min_qps != net.min_qps {
+            changed.push("min_qps");
+        }
+        if next.max_qps != net.max_qps {
+            changed.push("max_qps");
+        }
+
+        if next.cookie != net.cookie || next.upstreams != net.upstreams {
+            let upstreams = build_upstreams(&next.upstreams, &next.cookie, &self.headers);
+            *self.upstreams.write().unwrap() = Arc::new(upstreams);
+            self.current.store(0, Ordering::Relaxed);
+        }
+        if next.min_qps != net.min_qps || next.max_qps != net.max_qps {
+            let limiter = RateLimiter::new(next.min_qps, next.max_qps).map(Arc::new);
+            if limiter.is_none() {
+                metrics::set_upstream_qps(0.0);
             }
-            println!("{:?}", jar);
+            *self.limiter.write().unwrap() = limiter;
         }
-        let client = reqwest::Client::builder()
+        *net = next;
+
+        changed
+    }
+
+    /// 限速后发送请求，并根据响应调节限速；配置了多个上游时，被限流后切换到下一个上游重试；
+    /// GET/HEAD请求超时、连接失败或返回502/503/504时按指数退避重试
+    pub async fn send(&self, req: RequestBuilder) -> Result<Response, Error> {
+        let limiter = self.limiter.read().unwrap().clone();
+        let req = match REQUEST_TIMEOUT.try_with(|x| *x) {
+            Ok(timeout) => req.timeout(timeout),
+            Err(_) => req,
+        };
+        let mut request = req.build()?;
+        let mut retries = self.upstreams.read().unwrap().len() - 1;
+        // 只重试幂等请求
+        let idempotent = request.method() == Method::GET || request.method() == Method::HEAD;
+        let mut attempts = 0;
+        loop {
+            // body为流时无法复制，不重试
+            let retry = if retries > 0 || (idempotent && attempts < self.retry_count) {
+                request.try_clone()
+            } else {
+                None
+            };
+            if let Some(limiter) = &limiter {
+                limiter.acquire().await;
+            }
+            let res = self.client().execute(request).await;
+            let mut throttled = false;
+            if let Ok(res) = &res {
+                throttled = is_throttled(res);
+                metrics::record_upstream(throttled);
+                if let Some(limiter) = &limiter {
+                    limiter.feedback(throttled);
+                }
+                if is_login_redirect(res) {
+                    metrics::record_login_required();
+                    println!("豆瓣要求登录，请求被跳转到登录页: {}", res.url());
+                }
+            }
+            let failed = match &res {
+                Ok(res) => matches!(
+                    res.status(),
+                    StatusCode::BAD_GATEWAY
+                        | StatusCode::SERVICE_UNAVAILABLE
+                        | StatusCode::GATEWAY_TIMEOUT
+                ),
+                Err(err) => err.is_timeout() || err.is_connect(),
+            };
+            match retry {
+                Some(retry) if throttled && retries > 0 && self.switch_upstream() => {
+                    request = retry;
+                    retries -= 1;
+                }
+                Some(retry) if failed && idempotent && attempts < self.retry_count => {
+                    let backoff = self
+                        .retry_backoff
+                        .saturating_mul(1 << attempts.min(16))
+                        .min(RETRY_BACKOFF_MAX);
+                    attempts += 1;
+                    metrics::record_upstream_retry();
+                    let reason = match &res {
+                        Ok(res) => format!("HTTP {}", res.status().as_u16()),
+                        Err(err) => err.to_string(),
+                    };
+                    println!(
+                        "上游请求失败({})，{}ms后第{}/{}次重试: {}",
+                        reason,
+                        backoff.as_millis(),
+                        attempts,
+                        self.retry_count,
+                        retry.url()
+                    );
+                    tokio::time::sleep(backoff).await;
+                    request = retry;
+                }
+                _ => return res,
+            }
+        }
+    }
+
+    /// 当前上游被限流时标记冷却，并切换到下一个可用的上游，没有可切换的上游时返回false
+    fn switch_upstream(&self) -> bool {
+        let upstreams = self.upstreams.read().unwrap().clone();
+        if upstreams.len() < 2 {
+            return false;
+        }
+        let now = now_secs();
+        let current = self.current.load(Ordering::Relaxed).min(upstreams.len() - 1);
+        upstreams[current]
+            .banned_until
+            .store(now + UPSTREAM_BAN_SECS, Ordering::Relaxed);
+        let next = (1..upstreams.len())
+            .map(|i| (current + i) % upstreams.len())
+            .find(|i| upstreams[*i].is_available(now));
+        match next {
+            Some(next) => {
+                self.current.store(next, Ordering::Relaxed);
+                println!(
+                    "上游被限流，切换上游: {} -> {}",
+                    upstreams[current].name, upstreams[next].name
+                );
+                true
+            }
+            None => {
+                println!("所有上游均被限流");
+                false
+            }
+        }
+    }
+
+    /// 获取页面内容，配置了缓存目录时按 Cache-Control/ETag/Last-Modified 做磁盘缓存
+    pub async fn get_html(&self, url: &str) -> anyhow::Result<Page> {
+        if self.cache_dir.is_empty() {
+            let res = self
+                .send(self.client().get(url))
+                .await?
+                .error_for_status()?;
+            check_login(&res, url)?;
+            let final_url = res.url().to_string();
+            let body = res.text().await?;
+            self.archive(url, &final_url, &body).await;
+            return Ok(Page {
+                url: final_url,
+                body,
+            });
+        }
+
+        let path = self.cache_path(url);
+        let cached = match tokio::fs::read(&path).await {
+            Ok(data) => serde_json::from_slice::<CacheEntry>(&data).ok(),
+            Err(_) => None,
+        };
+        let now = now_secs();
+        if let Some(entry) = &cached {
+            if entry.expires > now {
+                return Ok(Page {
+                    url: entry.final_url.clone(),
+                    body: entry.body.clone(),
+                });
+            }
+        }
+
+        let mut req = self.client().get(url);
+        if let Some(entry) = &cached {
+            if !entry.etag.is_empty() {
+                req = req.header(IF_NONE_MATCH, entry.etag.as_str());
+            }
+            if !entry.last_modified.is_empty() {
+                req = req.header(IF_MODIFIED_SINCE, entry.last_modified.as_str());
+            }
+        }
+        let res = self.send(req).await?;
+        if res.status() == StatusCode::NOT_MODIFIED {
+            // 没有带校验标识却收到304，说明缓存已不可用，不能把空响应当作页面内容
+            let mut entry = cached.ok_or_else(|| anyhow!("上游返回304但本地没有缓存: {}", url))?;
+            let (store, expires) = parse_freshness(res.headers(), now);
+            entry.expires = expires;
+            if store {
+                self.save_cache(&path, &entry).await;
+            }
+            return Ok(Page {
+                url: entry.final_url,
+                body: entry.body,
+            });
+        }
+
+        let res = res.error_for_status()?;
+        check_login(&res, url)?;
+        let headers = res.headers().clone();
+        let final_url = res.url().to_string();
+        let body = res.text().await?;
+        self.archive(url, &final_url, &body).await;
+        let (store, expires) = parse_freshness(&headers, now);
+        let etag = header_str(&headers, ETAG);
+        let last_modified = header_str(&headers, LAST_MODIFIED);
+        // 既没有有效期也没有校验标识的响应缓存没有意义
+        if store && (expires > now || !etag.is_empty() || !last_modified.is_empty()) {
+            let entry = CacheEntry {
+                url: url.to_string(),
+                final_url: final_url.clone(),
+                etag,
+                last_modified,
+                expires,
+                body: body.clone(),
+            };
+            self.save_cache(&path, &entry).await;
+        }
+
+        Ok(Page {
+            url: final_url,
+            body,
+        })
+    }
+
+    /// 配置了归档目录时，按电影/影人/图书/音乐分类归档抓取到的页面原文
+    async fn archive(&self, url: &str, final_url: &str, body: &str) {
+        if self.archive_dir.is_empty() {
+            return;
+        }
+        let kind = if url.contains("/celebrity/") {
+            "celebrity"
+        } else if url.starts_with(&self.movie_base) {
+            "movie"
+        } else if url.starts_with(&self.book_base) {
+            "book"
+        } else if url.starts_with(&self.music_base) {
+            "music"
+        } else {
+            "other"
+        };
+        archive::save(&self.archive_dir, kind, url, final_url, body).await;
+    }
+
+    /// 缓存文件名取地址、cookie与当前上游的FNV-1a哈希，不同登录状态或出口的页面互不复用，
+    /// 且重启或升级编译器后仍能命中
+    fn cache_path(&self, url: &str) -> PathBuf {
+        let cookie = self.net.lock().unwrap().cookie.clone();
+        let upstream = {
+            let upstreams = self.upstreams.read().unwrap();
+            let current = self.current.load(Ordering::Relaxed).min(upstreams.len() - 1);
+            upstreams[current].name.clone()
+        };
+        let key = format!("{}\n{}\n{}", url, cookie, upstream);
+        PathBuf::from(&self.cache_dir).join(format!("{:016x}.json", fnv1a(key.as_bytes())))
+    }
+
+    async fn save_cache(&self, path: &PathBuf, entry: &CacheEntry) {
+        if let Err(err) = tokio::fs::create_dir_all(&self.cache_dir).await {
+            println!("创建http缓存目录失败: {:?}", err);
+            return;
+        }
+        let data = serde_json::to_vec(entry).unwrap();
+        if let Err(err) = tokio::fs::write(path, data).await {
+            println!("写入http缓存失败: {:?}", err);
+        }
+        // 每写入一批检查一次，避免每次写缓存都遍历目录
+        if self
+            .cache_writes
+            .fetch_add(1, Ordering::Relaxed)
+            .is_multiple_of(CACHE_PRUNE_EVERY)
+        {
+            self.prune_cache().await;
+        }
+    }
+
+    /// 缓存文件数超过上限时按修改时间删除最旧的文件
+    async fn prune_cache(&self) {
+        let mut dir = match tokio::fs::read_dir(&self.cache_dir).await {
+            Ok(dir) => dir,
+            Err(_) => return,
+        };
+        let mut files = Vec::new();
+        while let Ok(Some(entry)) = dir.next_entry().await {
+            if let Ok(meta) = entry.metadata().await {
+                if meta.is_file() {
+                    files.push((meta.modified().unwrap_or(UNIX_EPOCH), entry.path()));
+                }
+            }
+        }
+        if files.len() <= self.cache_max_entries {
+            return;
+        }
+        files.sort();
+        let excess = files.len() - self.cache_max_entries;
+        for (_, path) in files.into_iter().take(excess) {
+            let _ = tokio::fs::remove_file(path).await;
+        }
+    }
+
+    /// 当前选用的上游请求客户端
+    fn client(&self) -> reqwest::Client {
+        let upstreams = self.upstreams.read().unwrap();
+        let current = self.current.load(Ordering::Relaxed).min(upstreams.len() - 1);
+        upstreams[current].client.clone()
+    }
+
+    /// 检查webhook地址为http(s)且解析到的地址均为公网地址，返回解析到的地址
+    pub async fn check_webhook(&self, webhook: &str) -> anyhow::Result<(Url, Vec<SocketAddr>)> {
+        let url = Url::parse(webhook)?;
+        if url.scheme() != "http" && url.scheme() != "https" {
+            return Err(anyhow!("webhook只支持http(s)地址"));
+        }
+        let host = url.host_str().ok_or_else(|| anyhow!("webhook缺少域名"))?;
+        let port = url.port_or_known_default().unwrap_or(80);
+        let addrs = tokio::net::lookup_host((host.trim_matches(['[', ']']), port))
+            .await?
+            .collect::<Vec<SocketAddr>>();
+        if addrs.is_empty() || addrs.iter().any(|x| !util::is_public_ip(x.ip())) {
+            return Err(anyhow!("webhook不能指向内网或本机地址: {}", host));
+        }
+
+        Ok((url, addrs))
+    }
+
+    /// POST回调webhook，经由当前上游的代理发送，不跟随跳转；
+    /// 直连时固定使用检查过的地址，避免DNS重绑定到内网
+    pub async fn post_webhook<T: Serialize>(&self, webhook: &str, body: &T) -> anyhow::Result<()> {
+        let (url, addrs) = self.check_webhook(webhook).await?;
+        let proxy = {
+            let upstreams = self.upstreams.read().unwrap();
+            let current = self.current.load(Ordering::Relaxed).min(upstreams.len() - 1);
+            upstreams[current].proxy.clone()
+        };
+        let mut builder = reqwest::Client::builder()
             .user_agent(UA)
-            .default_headers(headers)
-            .cookie_provider(Arc::new(jar))
+            .redirect(Policy::none())
             .connect_timeout(Duration::from_secs(10))
-            .timeout(Duration::from_secs(30))
-            // .connection_verbose(true)
-            .build()
-            .unwrap();
-        Self { client }
+            .timeout(Duration::from_secs(10));
+        builder = match proxy {
+            Some(proxy) => builder.proxy(proxy),
+            None => builder
+                .no_proxy()
+                .resolve_to_addrs(url.host_str().unwrap_or_default(), &addrs),
+        };
+        builder
+            .build()?
+            .post(url)
+            .json(body)
+            .send()
+            .await?
+            .error_for_status()?;
+
+        Ok(())
+    }
+
+    pub fn has_multi_upstream(&self) -> bool {
+        self.upstreams.read().unwrap().len() > 1
+    }
+
+    /// 探测所有上游的可用性与延迟，选择可用且延迟最低的上游
+    pub async fn probe_upstreams(&self) {
+        let upstreams = self.upstreams.read().unwrap().clone();
+        let probes = upstreams.iter().map(|upstream| async move {
+            let start = Instant::now();
+            let res = upstream
+                .client
+                .head(format!("{}/", self.movie_base))
+                .timeout(Duration::from_secs(10))
+                .send()
+                .await;
+            let healthy = match res {
+                Ok(res) => !res.status().is_server_error() && !is_throttled(&res),
+                Err(_) => false,
+            };
+            upstream.healthy.store(healthy, Ordering::Relaxed);
+            upstream
+                .latency
+                .store(start.elapsed().as_millis() as u64, Ordering::Relaxed);
+        });
+        futures::future::join_all(probes).await;
+
+        let now = now_secs();
+        let best = upstreams
+            .iter()
+            .enumerate()
+            .filter(|(_, x)| x.is_available(now))
+            .min_by_key(|(_, x)| x.latency.load(Ordering::Relaxed))
+            .map(|(i, _)| i);
+        if let Some(best) = best {
+            let prev = self.current.swap(best, Ordering::Relaxed);
+            if prev != best {
+                let prev = upstreams.get(prev).map(|x| x.name.as_str()).unwrap_or("");
+                println!("切换上游: {} -> {}", prev, upstreams[best].name);
+            }
+        } else {
+            println!("所有上游均不可用");
+        }
+    }
+
+    /// 逐个上游请求电影站首页，返回(上游, 状态码或错误, 耗时毫秒)，用于自检
+    pub async fn check_upstreams(&self) -> Vec<(String, Result<u16, String>, u64)> {
+        let upstreams = self.upstreams.read().unwrap().clone();
+        let checks = upstreams.iter().map(|upstream| async move {
+            let start = Instant::now();
+            let res = upstream
+                .client
+                .get(format!("{}/", self.movie_base))
+                .timeout(Duration::from_secs(10))
+                .send()
+                .await
+                .map(|x| x.status().as_u16())
+                .map_err(|err| err.to_string());
+            (upstream.name.clone(), res, start.elapsed().as_millis() as u64)
+        });
+
+        futures::future::join_all(checks).await
+    }
+
+    /// 配置了cookie时检查是否仍为登录状态，未登录会被跳转到登录页
+    pub async fn check_cookie(&self) -> anyhow::Result<bool> {
+        let res = self.get(format!("{}/mine/", self.www_base)).send().await?;
+        Ok(!is_login_redirect(&res))
+    }
+
+    pub fn img_host(&self) -> String {
+        self.img_host.read().unwrap().clone()
+    }
+
+    /// 探测各图片域名的连通性与速度，选择最快的域名
+    pub async fn probe_img_hosts(&self) {
+        let probes = IMG_HOSTS.iter().map(|host| async move {
+            let start = Instant::now();
+            let res = self
+                .client()
+                .head(format!("https://{}/", host))
+                .timeout(Duration::from_secs(10))
+                .send()
+                .await;
+            match res {
+                Ok(_) => Some((start.elapsed(), *host)),
+                Err(_) => None,
+            }
+        });
+        let best = futures::future::join_all(probes)
+            .await
+            .into_iter()
+            .flatten()
+            .min_by_key(|(elapsed, _)| *elapsed);
+        match best {
+            Some((_, host)) => {
+                let mut img_host = self.img_host.write().unwrap();
+                if *img_host != host {
+                    println!("切换图片域名: {} -> {}", img_host, host);
+                    *img_host = host.to_string();
+                }
+            }
+            None => println!("所有图片域名均不可用"),
+        }
+    }
+
+    pub fn can_login(&self) -> bool {
+        !self.user.is_empty() && !self.password.is_empty()
+    }
+
+    /// 使用账号密码登录，登录成功后cookie会自动保存到cookie jar中
+    pub async fn login(&self) -> anyhow::Result<()> {
+        if !self.can_login() {
+            return Err(anyhow!("未配置豆瓣账号密码"));
+        }
+
+        let res = self
+            .client()
+            .post(LOGIN_URL)
+            .header("Origin", "https://accounts.douban.com")
+            .header("Referer", LOGIN_REFERER)
+            .form(&[
+                ("ck", ""),
+                ("remember", "true"),
+                ("name", self.user.as_str()),
+                ("password", self.password.as_str()),
+            ])
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<serde_json::Value>()
+            .await?;
+
+        if res["status"] == "success" {
+            Ok(())
+        } else {
+            Err(anyhow!(
+                "豆瓣登录失败: {} {}",
+                res["message"].as_str().unwrap_or(""),
+                res["description"].as_str().unwrap_or("")
+            ))
+        }
     }
 
     pub fn get<U: IntoUrl>(&self, url: U) -> RequestBuilder {
-        self.client.get(url)
+        self.client().get(url)
+    }
+
+    pub fn head<U: IntoUrl>(&self, url: U) -> RequestBuilder {
+        self.client().head(url)
     }
 
     #[allow(dead_code)]
     pub fn execute(&self, request: Request) -> impl Future<Output = Result<Response, Error>> {
-        self.client.execute(request)
+        self.client().execute(request)
+    }
+}
+
+/// 按上游配置创建请求客户端，多个上游共享同一个cookie jar
+fn build_upstreams(names: &str, cookie: &str, headers: &HeaderMap) -> Vec<Upstream> {
+    let url = "https://douban.com/".parse::<Url>().unwrap();
+    let jar = Jar::default();
+    if !cookie.is_empty() {
+        for s in cookie.split(";") {
+            let cookie_str = format!("{}; Domain=douban.com", s);
+            jar.add_cookie_str(cookie_str.as_str(), &url);
+        }
+        println!("{:?}", jar);
+    }
+    let jar = Arc::new(jar);
+    let mut upstreams = Vec::new();
+    for name in names.split(',') {
+        let name = name.trim();
+        if name.is_empty() {
+            continue;
+        }
+        let proxy = if name == "direct" {
+            None
+        } else {
+            match Proxy::all(name) {
+                Ok(proxy) => Some(proxy),
+                Err(err) => {
+                    println!("忽略无效的上游代理: {} {:?}", name, err);
+                    continue;
+                }
+            }
+        };
+        upstreams.push(Upstream::new(name, headers, &jar, proxy));
+    }
+    if upstreams.is_empty() {
+        upstreams.push(Upstream::new("direct", headers, &jar, None));
+    }
+
+    upstreams
+}
+
+/// 403/429或跳转到安全验证页面视为被限流
+fn is_throttled(res: &Response) -> bool {
+    res.status() == StatusCode::FORBIDDEN
+        || res.status() == StatusCode::TOO_MANY_REQUESTS
+        || res.url().host_str() == Some("sec.douban.com")
+}
+
+/// 请求是否被302跳转到豆瓣登录页
+pub fn is_login_redirect(res: &Response) -> bool {
+    res.url().host_str() == Some("accounts.douban.com")
+}
+
+/// 被跳转到登录页时返回NEED_LOGIN错误，避免把登录页当作内容解析
+pub fn check_login(res: &Response, url: &str) -> Result<(), ApiError> {
+    if is_login_redirect(res) {
+        return Err(ApiError::with_detail(ErrorCode::NeedLogin, url));
+    }
+    Ok(())
+}
+
+/// AIMD 自适应限速，被限流时减半，正常响应时逐步恢复
+struct RateLimiter {
+    min_qps: f64,
+    max_qps: f64,
+    state: Mutex<(f64, Instant)>, //(当前qps, 下次可请求时间)
+}
+
+impl RateLimiter {
+    fn new(min_qps: f64, max_qps: f64) -> Option<RateLimiter> {
+        if max_qps <= 0.0 {
+            return None;
+        }
+        let min_qps = min_qps.max(0.01).min(max_qps);
+        metrics::set_upstream_qps(max_qps);
+        Some(RateLimiter {
+            min_qps,
+            max_qps,
+            state: Mutex::new((max_qps, Instant::now())),
+        })
     }
+
+    async fn acquire(&self) {
+        let wait = {
+            let mut state = self.state.lock().unwrap();
+            let now = Instant::now();
+            let at = state.1.max(now);
+            state.1 = at + Duration::from_secs_f64(1.0 / state.0);
+            at - now
+        };
+        if !wait.is_zero() {
+            tokio::time::sleep(wait).await;
+        }
+    }
+
+    fn feedback(&self, throttled: bool) {
+        let mut state = self.state.lock().unwrap();
+        let qps = if throttled {
+            (state.0 / 2.0).max(self.min_qps)
+        } else {
+            (state.0 + self.max_qps / 20.0).min(self.max_qps)
+        };
+        if throttled && qps != state.0 {
+            println!("豆瓣限流，请求速率调整为 {:.2}/s", qps);
+        }
+        state.0 = qps;
+        metrics::set_upstream_qps(qps);
+    }
+}
+
+impl Upstream {
+    fn new(name: &str, headers: &HeaderMap, jar: &Arc<Jar>, proxy: Option<Proxy>) -> Upstream {
+        let mut builder = reqwest::Client::builder()
+            .user_agent(UA)
+            .default_headers(headers.clone())
+            .cookie_provider(Arc::clone(jar))
+            .connect_timeout(Duration::from_secs(10))
+            .timeout(Duration::from_secs(30));
+        // .connection_verbose(true)
+        if let Some(proxy) = proxy.clone() {
+            builder = builder.proxy(proxy);
+        }
+        Upstream {
+            name: name.to_string(),
+            client: builder.build().unwrap(),
+            proxy,
+            healthy: AtomicBool::new(true),
+            latency: AtomicU64::new(0),
+            banned_until: AtomicU64::new(0),
+        }
+    }
+
+    /// 探测可用且不在限流冷却期内
+    fn is_available(&self, now: u64) -> bool {
+        self.healthy.load(Ordering::Relaxed) && self.banned_until.load(Ordering::Relaxed) <= now
+    }
+}
+
+/// 页面内容，url 为跳转后的最终地址
+pub struct Page {
+    pub url: String,
+    pub body: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CacheEntry {
+    url: String,
+    final_url: String,
+    etag: String,
+    last_modified: String,
+    expires: u64,
+    body: String,
+}
+
+/// 64位FNV-1a哈希，结果与平台和编译器版本无关
+fn fnv1a(data: &[u8]) -> u64 {
+    data.iter().fold(0xcbf29ce484222325, |hash, &b| {
+        (hash ^ b as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|x| x.as_secs())
+        .unwrap_or(0)
+}
+
+fn header_str(headers: &HeaderMap, name: reqwest::header::HeaderName) -> String {
+    headers
+        .get(name)
+        .and_then(|x| x.to_str().ok())
+        .unwrap_or("")
+        .to_string()
+}
+
+/// 解析 Cache-Control，返回 (是否可存储, 过期时间)
+fn parse_freshness(headers: &HeaderMap, now: u64) -> (bool, u64) {
+    let cache_control = header_str(headers, CACHE_CONTROL).to_lowercase();
+    let mut store = true;
+    let mut expires = now;
+    for directive in cache_control.split(',') {
+        let directive = directive.trim();
+        if directive == "no-store" {
+            store = false;
+        } else if directive == "no-cache" {
+            expires = now;
+            break;
+        } else if let Some(age) = directive.strip_prefix("max-age=") {
+            expires = now + age.trim_matches('"').parse::<u64>().unwrap_or(0);
+        }
+    }
+
+    (store, expires)
 }
@@ -0,0 +1,59 @@
+use actix_web::body::{to_bytes, BoxBody, MessageBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::header::{HeaderValue, CONTENT_TYPE};
+use actix_web::http::Method;
+use actix_web::{web, Error};
+use std::collections::HashMap;
+
+const MAX_CALLBACK_LEN: usize = 64;
+
+/// 获取GET请求的callback参数，只允许字母、数字、_、$、.，避免注入脚本
+pub fn get_callback(req: &ServiceRequest) -> Option<String> {
+    if req.method() != Method::GET {
+        return None;
+    }
+    let query = web::Query::<HashMap<String, String>>::from_query(req.query_string()).ok()?;
+    let callback = query.get("callback")?;
+    let valid = !callback.is_empty()
+        && callback.len() <= MAX_CALLBACK_LEN
+        && callback
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '$' || c == '.');
+    if valid {
+        Some(callback.to_string())
+    } else {
+        None
+    }
+}
+
+/// 把json响应包装为jsonp，非json响应原样返回
+pub async fn wrap<B>(res: ServiceResponse<B>, callback: &str) -> Result<ServiceResponse, Error>
+where
+    B: MessageBody + 'static,
+{
+    if !res.status().is_success() {
+        return Ok(res.map_into_boxed_body());
+    }
+
+    let (req, res) = res.into_parts();
+    let (mut res, body) = res.into_parts();
+    let bytes = to_bytes(body)
+        .await
+        .map_err(|e| actix_web::error::ErrorInternalServerError(e.into()))?;
+    if !(bytes.starts_with(b"{") || bytes.starts_with(b"[")) {
+        let res = res.set_body(BoxBody::new(bytes));
+        return Ok(ServiceResponse::new(req, res));
+    }
+
+    let mut body = Vec::with_capacity(bytes.len() + callback.len() + 3);
+    body.extend_from_slice(callback.as_bytes());
+    body.push(b'(');
+    body.extend_from_slice(&bytes);
+    body.extend_from_slice(b");");
+    res.headers_mut().insert(
+        CONTENT_TYPE,
+        HeaderValue::from_static("application/javascript; charset=utf-8"),
+    );
+    let res = res.set_body(BoxBody::new(body));
+    Ok(ServiceResponse::new(req, res))
+}
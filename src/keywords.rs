@@ -0,0 +1,101 @@
+use serde::Serialize;
+use std::collections::HashMap;
+
+/// 中文候选词的长度范围
+const MIN_WORD_LEN: usize = 2;
+const MAX_WORD_LEN: usize = 4;
+/// 至少出现在多少条短评中才作为关键词
+const MIN_COUNT: usize = 2;
+
+// 不作为关键词首尾的虚词
+const STOP_CHARS: &str = "的了是我也在和就都不很有这那看说个一啊吧呢吗着过让被把给又还得地你他她它们与及或而但";
+const STOP_WORDS: [&str; 32] = [
+    "电影", "这个", "一个", "没有", "就是", "觉得", "还是", "不是", "什么", "真的", "我们", "自己",
+    "可以", "但是", "因为", "所以", "这部", "有点", "非常", "还有", "已经", "最后", "如果", "一部",
+    "这样", "那么", "感觉", "时候", "其实", "这种", "一样", "为什么",
+];
+const STOP_WORDS_EN: [&str; 10] = [
+    "the", "and", "for", "this", "that", "with", "movie", "film", "was", "but",
+];
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Keyword {
+    pub word: String,
+    pub count: usize, //出现该词的短评条数
+}
+
+/// 按n-gram统计短评中的高频词，不依赖分词词典；被等频更长词包含的短词会被去掉
+pub fn top_keywords(texts: &[String], top: usize) -> Vec<Keyword> {
+    let mut counts: HashMap<String, usize> = HashMap::new();
+    for text in texts.iter() {
+        let mut words = candidates(text);
+        words.sort();
+        words.dedup();
+        for word in words.into_iter() {
+            *counts.entry(word).or_insert(0) += 1;
+        }
+    }
+    counts.retain(|word, count| *count >= MIN_COUNT && !is_stop_word(word));
+
+    let mut list = counts
+        .iter()
+        .filter(|(word, count)| {
+            !counts
+                .iter()
+                .any(|(x, c)| c >= *count && x.len() > word.len() && x.contains(word.as_str()))
+        })
+        .map(|(word, count)| Keyword {
+            word: word.clone(),
+            count: *count,
+        })
+        .collect::<Vec<Keyword>>();
+    list.sort_by(|a, b| {
+        b.count
+            .cmp(&a.count)
+            .then(b.word.chars().count().cmp(&a.word.chars().count()))
+            .then(a.word.cmp(&b.word))
+    });
+    list.truncate(top);
+
+    list
+}
+
+/// 连续的中文取2-4字的n-gram，英文按单词
+fn candidates(text: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut cjk = Vec::new();
+    let mut latin = String::new();
+    for c in text.chars().chain(std::iter::once(' ')) {
+        if ('\u{4e00}'..='\u{9fff}').contains(&c) {
+            cjk.push(c);
+        } else if !cjk.is_empty() {
+            ngrams(&cjk, &mut words);
+            cjk.clear();
+        }
+        if c.is_ascii_alphanumeric() {
+            latin.push(c.to_ascii_lowercase());
+        } else if !latin.is_empty() {
+            if latin.len() >= 3 && !latin.chars().all(|x| x.is_ascii_digit()) {
+                words.push(latin.clone());
+            }
+            latin.clear();
+        }
+    }
+
+    words
+}
+
+fn ngrams(chars: &[char], words: &mut Vec<String>) {
+    for len in MIN_WORD_LEN..=MAX_WORD_LEN {
+        for window in chars.windows(len) {
+            if STOP_CHARS.contains(window[0]) || STOP_CHARS.contains(window[len - 1]) {
+                continue;
+            }
+            words.push(window.iter().collect());
+        }
+    }
+}
+
+fn is_stop_word(word: &str) -> bool {
+    STOP_WORDS.contains(&word) || STOP_WORDS_EN.contains(&word)
+}
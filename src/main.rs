@@ -1,18 +1,49 @@
+use actix_web::dev::Service;
+use actix_web::http::header::HeaderValue;
 use actix_web::{
-    get, middleware, web, App, HttpRequest, HttpResponse, HttpServer, Responder, Result,
+    delete, get, middleware, post, web, App, HttpRequest, HttpResponse, HttpResponseBuilder,
+    HttpServer, Responder, Result,
 };
 mod api;
+mod archive;
+mod changes;
+mod chinese;
 mod bookapi;
 mod config;
+mod cors;
+mod cron;
+mod doctor;
+mod error;
 mod http;
-use api::Douban;
+mod jsonp;
+mod keywords;
+mod metrics;
+mod musicapi;
+mod palette;
+mod redact;
+mod scrape;
+mod trending;
+mod util;
+use api::{CacheRecord, Douban, ExploreFilter, SearchFilter, SearchStatus};
 use bookapi::DoubanBookApi;
 use clap::Parser;
-use config::Opt;
+use config::{Command, HotConfig, Opt};
+use error::{ApiError, ErrorCode, Lang};
+use futures::future::Either;
+use futures::StreamExt;
 use http::HttpClient;
-use serde::Deserialize;
+use musicapi::DoubanMusicApi;
+use serde::{Deserialize, Serialize};
 use std::env;
 use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Semaphore;
+use util::CacheMeta;
+
+const CACHE_IMPORT_MAX_SIZE: usize = 256 * 1024 * 1024;
+const MAX_QUERY_LEN: usize = 100;
+// /proxy下载到不完整图片时的最多下载次数
+const PROXY_ATTEMPTS: u32 = 3;
 
 #[get("/")]
 async fn index() -> impl Responder {
@@ -23,13 +54,59 @@ async fn index() -> impl Responder {
        接口列表：<br/>
        /movies?q={movie_name}<br/>
        /movies?q={movie_name}&type=full<br/>
+       /tv?q={tv_name}<br/>
+       /suggest?q={name}<br/>
+       /film?q={movie_name}<br/>
        /movies/{sid}<br/>
        /movies/{sid}/celebrities<br/>
+       /movies/imdb/{imdb_id}<br/>
+       /aliases/{sid}<br/>
+       /aliases?name={alias}<br/>
        /celebrities/{cid}<br/>
+       /celebrities/{cid}/nfo<br/>
+       /celebrities/{cid}/timeline<br/>
+       /widget/movie/{sid}<br/>
+       POST /celebrities/batch<br/>
+       /movies/{sid}/images<br/>
+       /tv/{sid}/seasons/{n}/posters<br/>
+       /movies/{sid}/subtitle-info<br/>
+       /movies/{sid}/parental-guide<br/>
+       /movies/{sid}/quotes<br/>
+       /movies/{sid}/comment-keywords?pages=3&top=20<br/>
+       /movies/{sid}/episodes<br/>
        /photo/{sid}<br/>
+       /feeds/new-releases<br/>
+       /trending?hours=24&count=20<br/>
+       /daily<br/>
+       /boxoffice/weekly<br/>
+       /collections/{id}?start=0<br/>
+       /celebrities/birthdays?date=MM-DD<br/>
+       /onthisday?date=MM-DD<br/>
+       /explore?decade=1990s&region=中国香港&genre=犯罪<br/>
        /v2/book/search?q={book_name}<br/>
        /v2/book/id/{sid}<br/>
+       /v2/book/id/{sid}/opf<br/>
+       /v2/book/id/{sid}/preview<br/>
        /v2/book/isbn/{isbn}<br/>
+       /v2/book/author/{id}/works?start=0<br/>
+       /v2/book/tag/{tag}/history?id={sid}<br/>
+       /v2/book/recent_hot?kind=fiction<br/>
+       /v2/music/search?q={album}<br/>
+       /v2/music/id/{sid}<br/>
+       /whatis/{id}<br/>
+       /metrics<br/>
+       /stats/library?top=10<br/>
+       POST /auth/refresh<br/>
+       /admin/cache/export?format=ndjson<br/>
+       POST /admin/cache/import<br/>
+       POST /admin/reload<br/>
+       POST /sync/changed<br/>
+       POST /parse/movie/{sid}<br/>
+       POST /parse/celebrity/{cid}<br/>
+       POST /scrape/series<br/>
+       POST /subscriptions<br/>
+       /subscriptions<br/>
+       DELETE /subscriptions/{id}<br/>
     "#,
         )
 }
@@ -40,10 +117,63 @@ async fn movies(
     req: HttpRequest,
     query: web::Query<SearchQuery>,
     opt: web::Data<Opt>,
-) -> Result<String> {
-    if query.q.is_empty() {
+) -> Result<HttpResponse> {
+    search_movies(douban_api, req, query, opt, "").await
+}
+
+/// 自动补全用的轻量搜索，只使用豆瓣搜索建议
+#[get("/suggest")]
+async fn suggest(douban_api: web::Data<Douban>, query: web::Query<SuggestQuery>) -> Result<String> {
+    let q = normalize_query(&query.q)?;
+    if q.is_empty() {
         return Ok("[]".to_string());
     }
+    let result = douban_api.suggest(&q).await.map_err(ApiError::from)?;
+    Ok(serde_json::to_string(&result).unwrap())
+}
+
+/// 只搜索电视剧，返回结构与/movies一致
+#[get("/tv")]
+async fn tv(
+    douban_api: web::Data<Douban>,
+    req: HttpRequest,
+    query: web::Query<SearchQuery>,
+    opt: web::Data<Opt>,
+) -> Result<HttpResponse> {
+    search_movies(douban_api, req, query, opt, "电视剧").await
+}
+
+/// 只搜索电影，返回结构与/movies一致
+#[get("/film")]
+async fn film(
+    douban_api: web::Data<Douban>,
+    req: HttpRequest,
+    query: web::Query<SearchQuery>,
+    opt: web::Data<Opt>,
+) -> Result<HttpResponse> {
+    search_movies(douban_api, req, query, opt, "电影").await
+}
+
+/// cat: 只保留指定分类，空为不限，?cat=movie|tv参数优先
+async fn search_movies(
+    douban_api: web::Data<Douban>,
+    req: HttpRequest,
+    query: web::Query<SearchQuery>,
+    opt: web::Data<Opt>,
+    cat: &'static str,
+) -> Result<HttpResponse> {
+    let q = search_query(&query)?;
+    let cat = match query.cat.as_str() {
+        "" => cat,
+        "movie" => "电影",
+        "tv" => "电视剧",
+        _ => return Err(ApiError::with_detail(ErrorCode::InvalidParam, "cat: movie|tv").into()),
+    };
+    if q.is_empty() {
+        return Ok(HttpResponse::Ok()
+            .content_type("text/plain; charset=utf-8")
+            .body("[]"));
+    }
 
     // 没有useragent或为空，是来自jellyfin-plugin-opendouban插件的请求
     let from_jellyfin = !req.headers().contains_key("User-Agent")
@@ -60,161 +190,2429 @@ async fn movies(
         count = opt.limit as i32
     }
 
+    let filter = SearchFilter {
+        min_rating: query.min_rating.unwrap_or(0.0),
+        min_votes: query.min_votes.unwrap_or(0),
+        keep_noise: !query.noise_filter.unwrap_or(true),
+        cat,
+    };
+
+    // 离线模式只返回已缓存的数据，并用X-Stale标记
+    if opt.offline || query.cache_only {
+        let (body, status) = if query.search_type == "full" {
+            let result = douban_api.search_full_cached(&q, count, &query.image_size, &filter);
+            let status = cached_status(result.is_empty());
+            (search_body(&result, status, query.with_status), status)
+        } else {
+            let result = douban_api.search_cached(&q, count, &filter);
+            let status = cached_status(result.is_empty());
+            (search_body(&result, status, query.with_status), status)
+        };
+        return Ok(HttpResponse::Ok()
+            .append_header(("X-Search-Source", "local"))
+            .append_header(("X-Result-Status", status.to_string()))
+            .append_header(("X-Stale", "true"))
+            .content_type("text/plain; charset=utf-8")
+            .body(body));
+    }
+
+    // 响应头X-Search-Source标注实际使用的数据源
+    let timeout = request_timeout(query.timeout_ms, opt.max_timeout_ms)?;
     if query.search_type == "full" {
-        let result = douban_api
-            .search_full(&query.q, count, &query.image_size)
-            .await
-            .unwrap();
-        Ok(serde_json::to_string(&result).unwrap())
+        let deadline = budget_deadline(query.budget_ms);
+        let (result, source, status) = http::with_timeout(
+            timeout,
+            douban_api.search_full(&q, count, &query.image_size, &filter, deadline),
+        )
+        .await
+        .ok_or_else(|| timeout_error(query.timeout_ms))?
+        .map_err(ApiError::from)?;
+        // 指定了超时预算时返回incomplete与missing标记
+        let body = if deadline.is_some() {
+            search_body(&result, status, query.with_status)
+        } else {
+            search_body(&result.data, status, query.with_status)
+        };
+        Ok(HttpResponse::Ok()
+            .append_header(("X-Search-Source", source.to_string()))
+            .append_header(("X-Result-Status", status.to_string()))
+            .content_type("text/plain; charset=utf-8")
+            .body(body))
     } else {
-        let result = douban_api
-            .search(&query.q, count, &query.image_size)
-            .await
-            .unwrap();
-        Ok(serde_json::to_string(&result).unwrap())
+        let (result, source, status) = http::with_timeout(
+            timeout,
+            douban_api.search_with_status(&q, count, &query.image_size, &filter),
+        )
+        .await
+        .ok_or_else(|| timeout_error(query.timeout_ms))?
+        .map_err(ApiError::from)?;
+        Ok(HttpResponse::Ok()
+            .append_header(("X-Search-Source", source.to_string()))
+            .append_header(("X-Result-Status", status.to_string()))
+            .content_type("text/plain; charset=utf-8")
+            .body(search_body(&result, status, query.with_status)))
+    }
+}
+
+fn cached_status(is_empty: bool) -> SearchStatus {
+    if is_empty {
+        SearchStatus::Empty
+    } else {
+        SearchStatus::Ok
+    }
+}
+
+/// with_status=true时在响应中带上result_status，数组结果包装为{"result_status","data"}
+fn search_body<T: Serialize>(result: &T, status: SearchStatus, with_status: bool) -> String {
+    if !with_status {
+        return serde_json::to_string(result).unwrap();
+    }
+    match serde_json::to_value(result).unwrap() {
+        serde_json::Value::Object(mut map) => {
+            map.insert("result_status".to_string(), status.to_string().into());
+            serde_json::Value::Object(map).to_string()
+        }
+        data => serde_json::json!({ "result_status": status.to_string(), "data": data }).to_string(),
     }
 }
 
 /// {sid} - deserializes to a String
 #[get("/movies/{sid}")]
 async fn movie(
+    douban_api: web::Data<Douban>,
+    req: HttpRequest,
+    path: web::Path<String>,
+    query: web::Query<MovieQuery>,
+    opt: web::Data<Opt>,
+) -> Result<HttpResponse> {
+    let sid = path.into_inner();
+    // ?format=nfo或Accept: application/xml时输出kodi的nfo
+    let nfo = match query.format.as_str() {
+        "nfo" => true,
+        "json" => false,
+        "" => req
+            .headers()
+            .get("Accept")
+            .and_then(|x| x.to_str().ok())
+            .map(|x| x.contains("application/xml"))
+            .unwrap_or(false),
+        _ => return Err(ApiError::with_detail(ErrorCode::InvalidParam, "format: json|nfo").into()),
+    };
+    let timeout = request_timeout(query.timeout_ms, opt.max_timeout_ms)?;
+    let (result, meta) = http::with_timeout(timeout, async {
+        let (mut result, meta) = douban_api
+            .get_movie_info_meta(&sid, &query.image_size)
+            .await?;
+        if query.expand.split(',').any(|x| x.trim() == "celebrities") {
+            douban_api.expand_celebrities(&mut result).await;
+        }
+        if query.palette {
+            douban_api.add_palette(&mut result).await;
+        }
+        douban_api.add_imdb_rating(&mut result).await;
+        Ok::<_, anyhow::Error>((result, meta))
+    })
+    .await
+    .ok_or_else(|| timeout_error(query.timeout_ms))?
+    .map_err(ApiError::from)?;
+    if nfo {
+        let mut result = result;
+        redact::apply(&mut result, &redact::parse_fields(&opt.hidden_fields));
+        let tvshow = douban_api.is_tv(&result);
+        return Ok(cache_response(&meta)
+            .content_type("application/xml; charset=utf-8")
+            .body(result.to_nfo(tvshow)));
+    }
+    Ok(cache_response(&meta).body(serde_json::to_string(&result).unwrap()))
+}
+
+/// 按IMDb编号(如tt0111161)查找对应的豆瓣条目
+#[get("/movies/imdb/{imdb}")]
+async fn movie_by_imdb(
     douban_api: web::Data<Douban>,
     path: web::Path<String>,
     query: web::Query<MovieQuery>,
+) -> Result<String> {
+    let imdb = path.into_inner().to_lowercase();
+    let valid = imdb.len() >= 9
+        && imdb.len() <= 12
+        && imdb.starts_with("tt")
+        && imdb[2..].chars().all(|c| c.is_ascii_digit());
+    if !valid {
+        return Err(ApiError::with_detail(ErrorCode::InvalidParam, "imdb: tt0111161").into());
+    }
+    let mut result = douban_api
+        .get_movie_by_imdb(&imdb, &query.image_size)
+        .await
+        .map_err(ApiError::from)?;
+    douban_api.add_imdb_rating(&mut result).await;
+    Ok(serde_json::to_string(&result).unwrap())
+}
+
+/// 解析提交的电影详情页html，用于离线调试解析规则
+#[post("/parse/movie/{sid}")]
+async fn parse_movie(
+    douban_api: web::Data<Douban>,
+    path: web::Path<String>,
+    query: web::Query<MovieQuery>,
+    html: String,
+) -> Result<String> {
+    let sid = path.into_inner();
+    match douban_api.parse_movie_html(&sid, &html, &query.image_size) {
+        Ok(result) => Ok(serde_json::to_string(&result).unwrap()),
+        Err(e) => Err(ApiError::with_detail(ErrorCode::InvalidParam, e).into()),
+    }
+}
+
+/// 解析提交的影人详情页html
+#[post("/parse/celebrity/{id}")]
+async fn parse_celebrity(
+    douban_api: web::Data<Douban>,
+    path: web::Path<String>,
+    html: String,
+) -> Result<String> {
+    let id = path.into_inner();
+    match douban_api.parse_celebrity_html(&id, &html) {
+        Ok(result) => Ok(serde_json::to_string(&result).unwrap()),
+        Err(e) => Err(ApiError::with_detail(ErrorCode::InvalidParam, e).into()),
+    }
+}
+
+#[get("/movies/{sid}/celebrities")]
+async fn celebrities(
+    douban_api: web::Data<Douban>,
+    path: web::Path<String>,
+    query: web::Query<CelebritiesQuery>,
+) -> Result<String> {
+    let sid = path.into_inner();
+    // 传了分页参数时返回分组的全部演职员
+    if query.start.is_some() || query.limit.is_some() || query.paged {
+        let result = douban_api
+            .get_celebrity_page(
+                &sid,
+                &query.image_size,
+                query.start.unwrap_or(0),
+                query.limit.unwrap_or(20),
+            )
+            .await
+            .map_err(ApiError::from)?;
+        if query.paged {
+            return Ok(serde_json::to_string(&result.into_page()).unwrap());
+        }
+        return Ok(serde_json::to_string(&result).unwrap());
+    }
+    let count = query.count.unwrap_or(api::DEFAULT_CELEBRITY_COUNT);
+    if !(1..=200).contains(&count) {
+        return Err(ApiError::with_detail(ErrorCode::InvalidParam, "count: 1-200").into());
+    }
+    let roles = if query.roles.trim().is_empty() {
+        api::DEFAULT_CELEBRITY_ROLES
+    } else {
+        query.roles.as_str()
+    };
+    let roles = match api::parse_celebrity_roles(roles) {
+        Some(roles) if !roles.is_empty() => roles,
+        _ => {
+            return Err(ApiError::with_detail(
+                ErrorCode::InvalidParam,
+                "roles: director,actor,voice,writer,producer,composer,cinematographer,editor",
+            )
+            .into())
+        }
+    };
+    let mut result = douban_api
+        .get_celebrities(&sid, &query.image_size, count, &roles)
+        .await
+        .map_err(ApiError::from)?;
+    if query.inline_info {
+        let inline_count = query.inline_count.unwrap_or(5);
+        if !(1..=20).contains(&inline_count) {
+            return Err(
+                ApiError::with_detail(ErrorCode::InvalidParam, "inline_count: 1-20").into(),
+            );
+        }
+        douban_api.inline_celebrity_info(&mut result, inline_count).await;
+    }
+    Ok(serde_json::to_string(&result).unwrap())
+}
+
+#[get("/movies/{sid}/parental-guide")]
+async fn parental_guide(
+    douban_api: web::Data<Douban>,
+    path: web::Path<String>,
+    opt: web::Data<Opt>,
+) -> Result<String> {
+    if !opt.imdb_parental_guide {
+        return Err(ApiError::with_detail(
+            ErrorCode::FeatureDisabled,
+            "DOUBAN_IMDB_PARENTAL_GUIDE",
+        )
+        .into());
+    }
+    let sid = path.into_inner();
+    match douban_api.get_parental_guide(&sid).await {
+        Ok(result) => Ok(serde_json::to_string(&result).unwrap()),
+        Err(e) => Err(ApiError::from(e).into()),
+    }
+}
+
+#[get("/movies/{sid}/quotes")]
+async fn quotes(douban_api: web::Data<Douban>, path: web::Path<String>) -> Result<String> {
+    let sid = path.into_inner();
+    let result = douban_api.get_quotes(&sid).await.map_err(ApiError::from)?;
+    Ok(serde_json::to_string(&result).unwrap())
+}
+
+/// 短评关键词云，pages为抓取的短评页数(每页20条)，top为返回的关键词数
+#[get("/movies/{sid}/comment-keywords")]
+async fn comment_keywords(
+    douban_api: web::Data<Douban>,
+    path: web::Path<String>,
+    query: web::Query<CommentKeywordsQuery>,
 ) -> Result<String> {
     let sid = path.into_inner();
+    let pages = query.pages.unwrap_or(3);
+    let top = query.top.unwrap_or(20);
+    if !(1..=10).contains(&pages) {
+        return Err(ApiError::with_detail(ErrorCode::InvalidParam, "pages: 1-10").into());
+    }
+    if !(1..=100).contains(&top) {
+        return Err(ApiError::with_detail(ErrorCode::InvalidParam, "top: 1-100").into());
+    }
     let result = douban_api
-        .get_movie_info(&sid, &query.image_size)
+        .get_comment_keywords(&sid, pages, top)
         .await
-        .unwrap();
+        .map_err(ApiError::from)?;
     Ok(serde_json::to_string(&result).unwrap())
 }
 
-#[get("/movies/{sid}/celebrities")]
-async fn celebrities(douban_api: web::Data<Douban>, path: web::Path<String>) -> Result<String> {
+/// 剧集分集信息，detail=false时只返回集数与单集片长，不抓取分集页
+#[get("/movies/{sid}/episodes")]
+async fn episodes(
+    douban_api: web::Data<Douban>,
+    path: web::Path<String>,
+    query: web::Query<EpisodesQuery>,
+) -> Result<String> {
+    let sid = path.into_inner();
+    let result = douban_api
+        .get_episodes(&sid, query.detail.unwrap_or(true))
+        .await
+        .map_err(ApiError::from)?;
+    Ok(serde_json::to_string(&result).unwrap())
+}
+
+#[get("/movies/{sid}/subtitle-info")]
+async fn subtitle_info(douban_api: web::Data<Douban>, path: web::Path<String>) -> Result<String> {
     let sid = path.into_inner();
-    let result = douban_api.get_celebrities(&sid).await.unwrap();
+    let result = douban_api
+        .get_subtitle_info(&sid)
+        .await
+        .map_err(ApiError::from)?;
     Ok(serde_json::to_string(&result).unwrap())
 }
 
 #[get("/celebrities/{id}")]
 async fn celebrity(douban_api: web::Data<Douban>, path: web::Path<String>) -> Result<String> {
     let id = path.into_inner();
-    let result = douban_api.get_celebrity(&id).await.unwrap();
+    let result = douban_api
+        .get_celebrity(&id)
+        .await
+        .map_err(ApiError::from)?;
     Ok(serde_json::to_string(&result).unwrap())
 }
 
-#[get("/photo/{sid}")]
-async fn photo(douban_api: web::Data<Douban>, path: web::Path<String>) -> Result<String> {
+/// 影人作品按年份聚合的时间线
+#[get("/celebrities/{id}/timeline")]
+async fn celebrity_timeline(
+    douban_api: web::Data<Douban>,
+    path: web::Path<String>,
+) -> Result<String> {
+    let id = path.into_inner();
+    let result = douban_api
+        .get_celebrity_timeline(&id)
+        .await
+        .map_err(ApiError::from)?;
+    Ok(serde_json::to_string(&result).unwrap())
+}
+
+#[get("/aliases/{sid}")]
+async fn aliases(douban_api: web::Data<Douban>, path: web::Path<String>) -> Result<String> {
     let sid = path.into_inner();
-    let result = douban_api.get_wallpaper(&sid).await.unwrap();
+    let result = douban_api.get_aliases(&sid).await.map_err(ApiError::from)?;
     Ok(serde_json::to_string(&result).unwrap())
 }
 
-#[get("/v2/book/search")]
-async fn books(
-    query: web::Query<SearchQuery>,
-    book_api: web::Data<DoubanBookApi>,
+#[get("/aliases")]
+async fn alias_lookup(
+    douban_api: web::Data<Douban>,
+    query: web::Query<AliasQuery>,
 ) -> Result<String> {
-    if query.q.is_empty() {
-        return Ok("[]".to_string());
+    match douban_api.find_by_alias(&query.name).await {
+        Ok(Some(result)) => Ok(serde_json::to_string(&result).unwrap()),
+        Ok(None) => Err(ApiError::with_detail(ErrorCode::SubjectNotFound, &query.name).into()),
+        Err(e) => Err(ApiError::from(e).into()),
     }
-    let count = query.count.unwrap_or(2);
-    if count > 20 {
-        return Err(actix_web::error::ErrorBadRequest(
-            "{\"message\":\"count不能大于20\"}",
-        ));
+}
+
+#[post("/celebrities/batch")]
+async fn celebrity_batch(
+    douban_api: web::Data<Douban>,
+    body: web::Json<CelebrityBatchBody>,
+    query: web::Query<BudgetQuery>,
+) -> Result<String> {
+    if body.ids.len() > 50 {
+        return Err(ApiError::with_detail(ErrorCode::InvalidParam, "ids: max 50").into());
+    }
+    let deadline = budget_deadline(query.budget_ms);
+    let result = douban_api.get_celebrity_batch(&body.ids, deadline).await;
+    if deadline.is_some() {
+        Ok(serde_json::to_string(&result).unwrap())
+    } else {
+        Ok(serde_json::to_string(&result.data).unwrap())
     }
-    let result = book_api.search(&query.q, count).await.unwrap();
-    Ok(serde_json::to_string(&result).unwrap())
 }
 
-#[get("/v2/book/id/{sid}")]
-async fn book(path: web::Path<String>, book_api: web::Data<DoubanBookApi>) -> Result<String> {
-    let sid = path.into_inner();
-    match book_api.get_book_info(&sid).await {
-        Ok(info) => Ok(serde_json::to_string(&info).unwrap()),
-        Err(e) => Err(actix_web::error::ErrorInternalServerError(e)),
+/// 增量同步，返回since之后评分变化过的条目；从未抓取过的条目在unknown中，需调用方自行获取
+#[post("/sync/changed")]
+async fn sync_changed(body: web::Json<SyncChangedBody>) -> Result<String> {
+    if !["movie", "book"].contains(&body.kind.as_str()) {
+        return Err(ApiError::with_detail(ErrorCode::InvalidParam, "kind: movie|book").into());
+    }
+    if body.items.len() > 1000 {
+        return Err(ApiError::with_detail(ErrorCode::InvalidParam, "items: max 1000").into());
+    }
+    let mut changed = Vec::new();
+    let mut unknown = Vec::new();
+    for item in body.items.iter() {
+        match changes::get(&body.kind, &item.sid) {
+            Some(change) if change.changed_at > item.since => changed.push(serde_json::json!({
+                "sid": item.sid,
+                "changed_at": change.changed_at,
+                "fetched_at": change.fetched_at,
+                "rating": change.rating,
+            })),
+            Some(_) => {}
+            None => unknown.push(item.sid.clone()),
+        }
     }
+    Ok(serde_json::json!({ "changed": changed, "unknown": unknown }).to_string())
 }
 
-#[get("/v2/book/isbn/{isbn}")]
-async fn book_by_isbn(
+/// 可嵌入博客的电影卡片HTML片段，theme=dark为深色主题
+#[get("/widget/movie/{sid}")]
+async fn movie_widget(
+    douban_api: web::Data<Douban>,
     path: web::Path<String>,
-    book_api: web::Data<DoubanBookApi>,
-) -> Result<String> {
-    let isbn = path.into_inner();
-    match book_api.get_book_info_by_isbn(&isbn).await {
-        Ok(info) => Ok(serde_json::to_string(&info).unwrap()),
-        Err(e) => Err(actix_web::error::ErrorInternalServerError(e)),
+    query: web::Query<WidgetQuery>,
+    opt: web::Data<Opt>,
+) -> Result<HttpResponse> {
+    let sid = path.into_inner();
+    let dark = match query.theme.as_str() {
+        "" | "light" => false,
+        "dark" => true,
+        _ => {
+            return Err(ApiError::with_detail(ErrorCode::InvalidParam, "theme: light|dark").into())
+        }
+    };
+    let mut info = douban_api
+        .get_movie_info(&sid, &query.image_size)
+        .await
+        .map_err(ApiError::from)?;
+    redact::apply(&mut info, &redact::parse_fields(&opt.hidden_fields));
+    Ok(HttpResponse::Ok()
+        .content_type("text/html; charset=utf-8")
+        .body(info.to_widget(dark)))
+}
+
+#[get("/celebrities/{id}/nfo")]
+async fn celebrity_nfo(
+    douban_api: web::Data<Douban>,
+    path: web::Path<String>,
+    opt: web::Data<Opt>,
+) -> Result<HttpResponse> {
+    let id = path.into_inner();
+    let mut info = douban_api
+        .get_celebrity(&id)
+        .await
+        .map_err(ApiError::from)?;
+    redact::apply(&mut info, &redact::parse_fields(&opt.hidden_fields));
+    Ok(HttpResponse::Ok()
+        .content_type("application/xml; charset=utf-8")
+        .body(info.to_nfo()))
+}
+
+/// 为空时响应头X-Empty-Reason标明原因，保持数组结构兼容插件
+/// type: W-壁纸(默认) R-海报 S-剧照，指定count时自动翻页，paged=true时返回统一分页结构
+#[get("/photo/{sid}")]
+async fn photo(
+    douban_api: web::Data<Douban>,
+    path: web::Path<String>,
+    query: web::Query<PhotoQuery>,
+) -> Result<HttpResponse> {
+    let sid = path.into_inner();
+    let photo_type = match query.photo_type.as_str() {
+        "" => "W",
+        "W" | "R" | "S" => query.photo_type.as_str(),
+        _ => return Err(ApiError::with_detail(ErrorCode::InvalidParam, "type: W|R|S").into()),
+    };
+    let count = query.count.unwrap_or(0);
+    if count > 100 {
+        return Err(ApiError::with_detail(ErrorCode::InvalidParam, "count: max 100").into());
+    }
+    let start = query.start.unwrap_or(0);
+    // 只指定start时取一页
+    let count = if count == 0 && start > 0 { 30 } else { count };
+    let result = douban_api
+        .get_wallpaper(&sid, photo_type, start, count)
+        .await
+        .map_err(ApiError::from)?;
+    let mut builder = HttpResponse::Ok();
+    builder.content_type("text/plain; charset=utf-8");
+    if let Some(reason) = result.reason {
+        builder.append_header(("X-Empty-Reason", reason));
+    }
+    if query.paged {
+        return Ok(builder.body(serde_json::to_string(&result.into_page()).unwrap()));
     }
+    Ok(builder.body(serde_json::to_string(&result.photos).unwrap()))
 }
 
-#[get("/proxy")]
-async fn proxy(query: web::Query<ProxyQuery>, douban_api: web::Data<Douban>) -> impl Responder {
-    let resp = douban_api.proxy_img(&query.url).await.unwrap();
-    let content_type = resp.headers().get("content-type").unwrap();
-    HttpResponse::build(resp.status())
-        .append_header(("content-type", content_type))
-        .body(resp.bytes().await.unwrap())
+#[get("/movies/{sid}/images")]
+async fn images(
+    douban_api: web::Data<Douban>,
+    path: web::Path<String>,
+    query: web::Query<ImagesQuery>,
+) -> Result<String> {
+    let sid = path.into_inner();
+    let count = query.count.unwrap_or(0);
+    let result = douban_api
+        .get_images(&sid, count)
+        .await
+        .map_err(ApiError::from)?;
+    Ok(serde_json::to_string(&result).unwrap())
 }
 
-#[actix_web::main]
-async fn main() -> std::io::Result<()> {
-    let opt = Opt::parse();
-    if env::var("RUST_LOG").is_err() {
-        if opt.debug {
-            env::set_var(
-                "RUST_LOG",
-                "actix_web=debug,actix_server=debug,reqwest=debug",
-            );
-        } else {
-            env::set_var("RUST_LOG", "actix_web=info,actix_server=info,reqwest=warn");
-        }
+/// 获取剧集第n季的海报，返回的sid为该季条目id，可配合/movies/{sid}获取季详情
+#[get("/tv/{sid}/seasons/{n}/posters")]
+async fn season_posters(
+    douban_api: web::Data<Douban>,
+    path: web::Path<(String, u32)>,
+    query: web::Query<ImagesQuery>,
+) -> Result<String> {
+    let (sid, season) = path.into_inner();
+    if season == 0 {
+        return Err(ApiError::with_detail(ErrorCode::InvalidParam, "season").into());
     }
-    env_logger::init();
+    let result = douban_api
+        .get_season_posters(&sid, season, query.count.unwrap_or(0))
+        .await
+        .map_err(ApiError::from)?;
+    Ok(serde_json::to_string(&result).unwrap())
+}
 
-    let client = Arc::new(HttpClient::new(Opt::parse()));
+/// 近期想看/在看人数增速最快的条目
+#[get("/trending")]
+async fn trending_list(query: web::Query<TrendingQuery>) -> Result<String> {
+    let hours = query.hours.unwrap_or(24);
+    if !(1..=720).contains(&hours) {
+        return Err(ApiError::with_detail(ErrorCode::InvalidParam, "hours: 1-720").into());
+    }
+    let count = query.count.unwrap_or(20);
+    if !(1..=100).contains(&count) {
+        return Err(ApiError::with_detail(ErrorCode::InvalidParam, "count: 1-100").into());
+    }
+    let result = trending::top(hours * 3600, count);
+    Ok(serde_json::to_string(&result).unwrap())
+}
 
-    HttpServer::new(move || {
-        App::new()
-            .wrap(middleware::Logger::default())
-            .app_data(web::Data::new(Douban::new(Arc::clone(&client))))
-            .app_data(web::Data::new(DoubanBookApi::new(Arc::clone(&client))))
-            .app_data(web::Data::new(Opt::parse()))
-            .service(index)
-            .service(movies)
-            .service(movie)
-            .service(celebrities)
-            .service(celebrity)
-            .service(photo)
-            .service(book)
-            .service(books)
-            .service(book_by_isbn)
-            .service(proxy)
-    })
-    .bind((opt.host, opt.port))?
-    .run()
-    .await
+#[get("/feeds/new-releases")]
+async fn new_releases(douban_api: web::Data<Douban>) -> Result<String> {
+    let result = douban_api.get_new_releases();
+    Ok(serde_json::to_string(&result).unwrap())
 }
 
-#[derive(Deserialize)]
-struct SearchQuery {
-    pub q: String,
-    #[serde(alias = "type", default)]
-    pub search_type: String,
-    #[serde(alias = "s", default)]
-    pub image_size: String,
-    pub count: Option<i32>,
+#[get("/boxoffice/weekly")]
+async fn weekly_box_office(douban_api: web::Data<Douban>) -> Result<String> {
+    match douban_api.get_weekly_box_office().await {
+        Ok(result) => Ok(serde_json::to_string(&result).unwrap()),
+        Err(err) => {
+            println!("获取周票房榜失败: {:?}", err);
+            Err(ApiError::from(err).into())
+        }
+    }
 }
 
-#[derive(Deserialize)]
-struct MovieQuery {
-    #[serde(alias = "s", default)]
-    pub image_size: String,
+#[get("/daily")]
+async fn daily(douban_api: web::Data<Douban>) -> Result<String> {
+    match douban_api.get_daily().await {
+        Ok(result) => Ok(serde_json::to_string(&result).unwrap()),
+        Err(err) => {
+            println!("获取电影日历失败: {:?}", err);
+            Err(ApiError::from(err).into())
+        }
+    }
 }
 
-#[derive(Deserialize)]
-struct ProxyQuery {
-    pub url: String,
+#[get("/collections/{id}")]
+async fn collection(
+    douban_api: web::Data<Douban>,
+    path: web::Path<String>,
+    query: web::Query<PageQuery>,
+) -> Result<String> {
+    let id = path.into_inner();
+    let result = douban_api
+        .get_collection(&id, query.start.unwrap_or(0))
+        .await
+        .map_err(ApiError::from)?;
+    if query.paged {
+        return Ok(serde_json::to_string(&result.into_page()).unwrap());
+    }
+    Ok(serde_json::to_string(&result).unwrap())
+}
+
+#[get("/explore")]
+async fn explore(douban_api: web::Data<Douban>, query: web::Query<ExploreQuery>) -> Result<String> {
+    let filter = ExploreFilter {
+        decade: query.decade.clone().unwrap_or_default(),
+        region: query.region.clone().unwrap_or_default(),
+        genre: query.genre.clone().unwrap_or_default(),
+        tag: query.tag.clone().unwrap_or_default(),
+        sort: query.sort.clone().unwrap_or_default(),
+    };
+    match douban_api.explore(&filter, query.start.unwrap_or(0)).await {
+        Ok(result) if query.paged => Ok(serde_json::to_string(&result.into_page()).unwrap()),
+        Ok(result) => Ok(serde_json::to_string(&result).unwrap()),
+        Err(e) => Err(ApiError::from(e).into()),
+    }
+}
+
+#[get("/onthisday")]
+async fn on_this_day(
+    douban_api: web::Data<Douban>,
+    query: web::Query<OnThisDayQuery>,
+) -> Result<String> {
+    let (month, day) = parse_month_day(&query.date)?;
+    let result = douban_api.get_on_this_day(month, day);
+    Ok(serde_json::to_string(&result).unwrap())
+}
+
+/// 指定日期生日的影人，date格式为MM-DD，默认为今天
+#[get("/celebrities/birthdays")]
+async fn birthdays(
+    douban_api: web::Data<Douban>,
+    query: web::Query<OnThisDayQuery>,
+) -> Result<String> {
+    let (month, day) = parse_month_day(&query.date)?;
+    let result = douban_api.get_birthdays(month, day);
+    Ok(serde_json::to_string(&result).unwrap())
+}
+
+/// 搜索词去除控制字符与首尾空白，超长时返回参数错误
+fn normalize_query(q: &str) -> Result<String, ApiError> {
+    let q = q
+        .chars()
+        .filter(|c| !c.is_control())
+        .collect::<String>()
+        .trim()
+        .to_string();
+    if q.chars().count() > MAX_QUERY_LEN {
+        return Err(ApiError::with_detail(
+            ErrorCode::InvalidParam,
+            format!("q: max {} chars", MAX_QUERY_LEN),
+        ));
+    }
+    Ok(q)
+}
+
+/// 规范化搜索词，convert=t2s|s2t时转为简体再搜索
+fn search_query(query: &SearchQuery) -> Result<String, ApiError> {
+    let q = normalize_query(&query.q)?;
+    if query.convert.is_empty() {
+        return Ok(q);
+    }
+    match chinese::Convert::parse(&query.convert) {
+        Some(_) => Ok(chinese::to_simplified(&q)),
+        None => Err(ApiError::with_detail(ErrorCode::InvalidParam, "convert: t2s|s2t")),
+    }
+}
+
+/// 解析MM-DD格式的日期，不传时为东八区的今天
+fn parse_month_day(date: &Option<String>) -> Result<(u32, u32), ApiError> {
+    let (month, day) = match date {
+        Some(date) => {
+            let mut parts = date.split('-').map(|x| x.parse::<u32>().unwrap_or(0));
+            (parts.next().unwrap_or(0), parts.next().unwrap_or(0))
+        }
+        None => {
+            let (_year, month, day) = util::today();
+            (month, day)
+        }
+    };
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return Err(ApiError::with_detail(
+            ErrorCode::InvalidParam,
+            "date: MM-DD",
+        ));
+    }
+    Ok((month, day))
+}
+
+#[get("/v2/book/search")]
+async fn books(
+    query: web::Query<SearchQuery>,
+    book_api: web::Data<DoubanBookApi>,
+) -> Result<String> {
+    let q = search_query(&query)?;
+    if q.is_empty() {
+        return Ok("[]".to_string());
+    }
+    let count = query.count.unwrap_or(2);
+    if count > 20 {
+        return Err(ApiError::with_detail(ErrorCode::InvalidParam, "count: max 20").into());
+    }
+    if !["", "relevance", "rating", "pubdate"].contains(&query.sort.as_str()) {
+        return Err(ApiError::with_detail(
+            ErrorCode::InvalidParam,
+            "sort: relevance|rating|pubdate",
+        )
+        .into());
+    }
+    let result = book_api
+        .search(
+            &q,
+            count,
+            query.dedupe,
+            query.search_type == "full",
+            &query.sort,
+        )
+        .await
+        .map_err(ApiError::from)?;
+    Ok(book_body(&result, &query.fields))
+}
+
+/// format=marc 输出简化的MARC编目字段
+#[get("/v2/book/id/{sid}")]
+async fn book(
+    path: web::Path<String>,
+    query: web::Query<BookQuery>,
+    book_api: web::Data<DoubanBookApi>,
+    opt: web::Data<Opt>,
+) -> Result<HttpResponse> {
+    let sid = path.into_inner();
+    let (mut info, meta) = book_api
+        .get_book_info_meta(&sid, false)
+        .await
+        .map_err(ApiError::from)?;
+    let body = match query.format.as_str() {
+        "" | "json" => book_body(&info, &query.fields),
+        "marc" => {
+            // marc字段按图书字段映射，需在映射前剔除黑名单字段
+            redact::apply(&mut info, &redact::parse_fields(&opt.hidden_fields));
+            serde_json::to_string(&info.to_marc()).unwrap()
+        }
+        _ => {
+            return Err(ApiError::with_detail(ErrorCode::InvalidParam, "format: json|marc").into())
+        }
+    };
+    Ok(cache_response(&meta).body(body))
+}
+
+#[get("/v2/book/id/{sid}/opf")]
+async fn book_opf(
+    path: web::Path<String>,
+    book_api: web::Data<DoubanBookApi>,
+    opt: web::Data<Opt>,
+) -> Result<HttpResponse> {
+    let sid = path.into_inner();
+    let mut info = book_api.get_book_info(&sid).await.map_err(ApiError::from)?;
+    redact::apply(&mut info, &redact::parse_fields(&opt.hidden_fields));
+    Ok(HttpResponse::Ok()
+        .content_type("application/oebps-package+xml; charset=utf-8")
+        .body(info.to_opf()))
+}
+
+#[get("/v2/book/id/{sid}/preview")]
+async fn book_preview(
+    path: web::Path<String>,
+    book_api: web::Data<DoubanBookApi>,
+) -> Result<String> {
+    let sid = path.into_inner();
+    match book_api.get_book_preview(&sid).await {
+        Ok(Some(preview)) => Ok(serde_json::to_string(&preview).unwrap()),
+        Ok(None) => Err(ApiError::with_detail(ErrorCode::NotFound, "preview").into()),
+        Err(e) => Err(ApiError::from(e).into()),
+    }
+}
+
+#[get("/v2/book/author/{id}/works")]
+async fn book_author_works(
+    path: web::Path<String>,
+    query: web::Query<PageQuery>,
+    book_api: web::Data<DoubanBookApi>,
+) -> Result<String> {
+    let id = path.into_inner();
+    let start = query.start.unwrap_or(0);
+    match book_api.get_author_works(&id, start).await {
+        Ok(result) if query.paged => {
+            let page = result.into_author_works_page(start as usize);
+            Ok(serde_json::to_string(&page).unwrap())
+        }
+        Ok(result) => Ok(serde_json::to_string(&result).unwrap()),
+        Err(e) => Err(ApiError::from(e).into()),
+    }
+}
+
+/// 书籍在关注标签榜单快照中的排名变化
+#[get("/v2/book/tag/{tag}/history")]
+async fn book_tag_history(
+    path: web::Path<String>,
+    query: web::Query<TagHistoryQuery>,
+    book_api: web::Data<DoubanBookApi>,
+) -> Result<String> {
+    let tag = path.into_inner();
+    if query.id.is_empty() {
+        return Err(ApiError::with_detail(ErrorCode::InvalidParam, "id").into());
+    }
+    let result = book_api.get_tag_history(&tag, &query.id);
+    Ok(serde_json::to_string(&result).unwrap())
+}
+
+/// 热门新书榜，kind: fiction(虚构类)/nonfiction(非虚构类)，空为全部
+#[get("/v2/book/recent_hot")]
+async fn book_recent_hot(
+    query: web::Query<RecentHotQuery>,
+    book_api: web::Data<DoubanBookApi>,
+) -> Result<String> {
+    if !["", "fiction", "nonfiction"].contains(&query.kind.as_str()) {
+        return Err(
+            ApiError::with_detail(ErrorCode::InvalidParam, "kind: fiction|nonfiction").into(),
+        );
+    }
+    let result = book_api
+        .get_recent_hot(&query.kind)
+        .await
+        .map_err(ApiError::from)?;
+    Ok(serde_json::to_string(&result).unwrap())
+}
+
+#[get("/v2/book/isbn/{isbn}")]
+async fn book_by_isbn(
+    path: web::Path<String>,
+    query: web::Query<BookQuery>,
+    book_api: web::Data<DoubanBookApi>,
+) -> Result<HttpResponse> {
+    let isbn = path.into_inner();
+    let (info, meta) = book_api
+        .get_book_info_meta(&isbn, true)
+        .await
+        .map_err(ApiError::from)?;
+    Ok(cache_response(&meta).body(book_body(&info, &query.fields)))
+}
+
+/// ?fields=id,title,rating时只输出书籍的指定字段，搜索结果裁剪books中的每一项
+fn book_body<T: Serialize>(result: &T, fields: &str) -> String {
+    let fields = redact::parse_fields(fields);
+    if fields.is_empty() {
+        return serde_json::to_string(result).unwrap();
+    }
+    let mut value = serde_json::to_value(result).unwrap();
+    match value.get_mut("books") {
+        Some(serde_json::Value::Array(list)) => {
+            for item in list.iter_mut() {
+                redact::select_fields(item, &fields);
+            }
+        }
+        _ => redact::select_fields(&mut value, &fields),
+    }
+    value.to_string()
+}
+
+#[get("/v2/music/search")]
+async fn music_search(
+    query: web::Query<SearchQuery>,
+    music_api: web::Data<DoubanMusicApi>,
+) -> Result<String> {
+    let q = search_query(&query)?;
+    if q.is_empty() {
+        return Ok("[]".to_string());
+    }
+    let count = query.count.unwrap_or(2);
+    if !(1..=20).contains(&count) {
+        return Err(ApiError::with_detail(ErrorCode::InvalidParam, "count: 1-20").into());
+    }
+    let result = music_api
+        .search(&q, count as usize)
+        .await
+        .map_err(ApiError::from)?;
+    Ok(serde_json::to_string(&result).unwrap())
+}
+
+#[get("/v2/music/id/{sid}")]
+async fn music(
+    path: web::Path<String>,
+    music_api: web::Data<DoubanMusicApi>,
+) -> Result<String> {
+    let sid = path.into_inner();
+    let info = music_api
+        .get_music_info(&sid)
+        .await
+        .map_err(ApiError::from)?;
+    Ok(serde_json::to_string(&info).unwrap())
+}
+
+/// 按 movie/book/celebrity 顺序探测 id 的条目类型
+#[get("/whatis/{id}")]
+async fn whatis(
+    douban_api: web::Data<Douban>,
+    book_api: web::Data<DoubanBookApi>,
+    path: web::Path<String>,
+) -> Result<HttpResponse> {
+    let id = path.into_inner();
+    let result = if let Ok(info) = douban_api.get_movie_info(&id, "").await {
+        serde_json::json!({ "id": id, "type": "movie", "info": info })
+    } else if let Ok(info) = book_api.get_book_info(&id).await {
+        serde_json::json!({ "id": id, "type": "book", "info": info })
+    } else if let Ok(info) = douban_api.get_celebrity(&id).await {
+        serde_json::json!({ "id": id, "type": "celebrity", "info": info })
+    } else {
+        return Err(ApiError::with_detail(ErrorCode::SubjectNotFound, &id).into());
+    };
+    Ok(HttpResponse::Ok()
+        .content_type("text/plain; charset=utf-8")
+        .body(result.to_string()))
+}
+
+#[get("/proxy")]
+async fn proxy(
+    query: web::Query<ProxyQuery>,
+    douban_api: web::Data<Douban>,
+    opt: web::Data<Opt>,
+    limiter: web::Data<Semaphore>,
+) -> Result<HttpResponse> {
+    let _permit = limiter
+        .try_acquire()
+        .map_err(|_| ApiError::new(ErrorCode::TooManyRequests))?;
+
+    let max_size = opt.proxy_max_size * 1024 * 1024;
+    let timeout = Duration::from_secs(opt.proxy_timeout);
+    let download = async {
+        let mut attempt = 0;
+        loop {
+            attempt += 1;
+            let mut resp = douban_api.proxy_img(&query.url).await?;
+            let content_length = resp.content_length();
+            if content_length.unwrap_or(0) > max_size {
+                return Err(ApiError::new(ErrorCode::PayloadTooLarge));
+            }
+            let status = resp.status();
+            let content_type = resp
+                .headers()
+                .get("content-type")
+                .cloned()
+                .unwrap_or_else(|| HeaderValue::from_static("application/octet-stream"));
+            // 没有content-length时边下载边检查大小
+            let mut body = Vec::new();
+            while let Some(chunk) = resp.chunk().await? {
+                if (body.len() + chunk.len()) as u64 > max_size {
+                    return Err(ApiError::new(ErrorCode::PayloadTooLarge));
+                }
+                body.extend_from_slice(&chunk);
+            }
+            // 长度与content-length不符或图片被截断时重试
+            let complete = !status.is_success()
+                || (content_length.is_none_or(|x| x == body.len() as u64)
+                    && util::is_complete_image(&body));
+            if !complete {
+                let retry = attempt < PROXY_ATTEMPTS;
+                metrics::record_incomplete_image(retry);
+                println!("图片不完整: {} 第{}次下载", query.url, attempt);
+                if retry {
+                    continue;
+                }
+                return Err(ApiError::with_detail(
+                    ErrorCode::UpstreamError,
+                    "incomplete image",
+                ));
+            }
+            return Ok(HttpResponse::build(status)
+                .append_header(("content-type", content_type))
+                .body(body));
+        }
+    };
+    match actix_web::rt::time::timeout(timeout, download).await {
+        Ok(resp) => Ok(resp?),
+        Err(_) => Err(ApiError::new(ErrorCode::UpstreamTimeout).into()),
+    }
+}
+
+/// 一次提交整季剧集文件名，返回剧集级与单集级匹配结果
+#[post("/scrape/series")]
+async fn scrape_series(
+    douban_api: web::Data<Douban>,
+    body: web::Json<ScrapeSeriesBody>,
+) -> Result<String> {
+    if body.files.is_empty() || body.files.len() > 500 {
+        return Err(ApiError::with_detail(ErrorCode::InvalidParam, "files: 1-500").into());
+    }
+    let result = douban_api.scrape_series(&body.files).await;
+    Ok(serde_json::to_string(&result).unwrap())
+}
+
+#[post("/subscriptions")]
+async fn add_subscription(
+    req: HttpRequest,
+    opt: web::Data<Opt>,
+    douban_api: web::Data<Douban>,
+    body: web::Json<SubscriptionBody>,
+) -> Result<String> {
+    check_admin(&req, &opt)?;
+    if body.sid.is_empty()
+        || !(body.webhook.starts_with("http://") || body.webhook.starts_with("https://"))
+    {
+        return Err(
+            ApiError::with_detail(ErrorCode::InvalidParam, "sid, webhook: http(s) url").into(),
+        );
+    }
+    match douban_api.add_subscription(&body.sid, &body.webhook).await {
+        Ok(result) => Ok(serde_json::to_string(&result).unwrap()),
+        Err(e) => Err(ApiError::with_detail(ErrorCode::InvalidParam, e).into()),
+    }
+}
+
+#[get("/subscriptions")]
+async fn subscriptions(
+    req: HttpRequest,
+    opt: web::Data<Opt>,
+    douban_api: web::Data<Douban>,
+) -> Result<String> {
+    check_admin(&req, &opt)?;
+    let result = douban_api.get_subscriptions();
+    Ok(serde_json::to_string(&result).unwrap())
+}
+
+#[delete("/subscriptions/{id}")]
+async fn remove_subscription(
+    req: HttpRequest,
+    opt: web::Data<Opt>,
+    douban_api: web::Data<Douban>,
+    path: web::Path<String>,
+) -> Result<String> {
+    check_admin(&req, &opt)?;
+    let id = path.into_inner();
+    match douban_api.remove_subscription(&id) {
+        Some(result) => Ok(serde_json::to_string(&result).unwrap()),
+        None => Err(ApiError::with_detail(ErrorCode::NotFound, &id).into()),
+    }
+}
+
+/// 导出缓存，format=json 输出json数组，默认每行一条记录的ndjson
+#[get("/admin/cache/export")]
+async fn cache_export(
+    req: HttpRequest,
+    douban_api: web::Data<Douban>,
+    query: web::Query<ExportQuery>,
+    opt: web::Data<Opt>,
+) -> Result<HttpResponse> {
+    check_admin(&req, &opt)?;
+    let records = douban_api.export_cache();
+    let (body, file_name) = if query.format == "json" {
+        (serde_json::to_string(&records).unwrap(), "cache.json")
+    } else {
+        let lines = records
+            .iter()
+            .map(|x| serde_json::to_string(x).unwrap())
+            .collect::<Vec<String>>();
+        (lines.join("\n"), "cache.ndjson")
+    };
+    Ok(HttpResponse::Ok()
+        .content_type("application/json; charset=utf-8")
+        .append_header((
+            "Content-Disposition",
+            format!("attachment; filename=\"{}\"", file_name),
+        ))
+        .body(body))
+}
+
+/// 导入export导出的json或ndjson
+#[post("/admin/cache/import")]
+async fn cache_import(
+    req: HttpRequest,
+    douban_api: web::Data<Douban>,
+    mut payload: web::Payload,
+    opt: web::Data<Opt>,
+) -> Result<String> {
+    check_admin(&req, &opt)?;
+    let mut body = web::BytesMut::new();
+    while let Some(chunk) = payload.next().await {
+        let chunk = chunk?;
+        if body.len() + chunk.len() > CACHE_IMPORT_MAX_SIZE {
+            return Err(ApiError::new(ErrorCode::PayloadTooLarge).into());
+        }
+        body.extend_from_slice(&chunk);
+    }
+    let text = String::from_utf8_lossy(&body);
+    let records: serde_json::Result<Vec<CacheRecord>> = if text.trim_start().starts_with('[') {
+        serde_json::from_str(&text)
+    } else {
+        text.lines()
+            .filter(|x| !x.trim().is_empty())
+            .map(serde_json::from_str)
+            .collect()
+    };
+    match records {
+        Ok(records) => {
+            let count = douban_api.import_cache(records).await;
+            Ok(format!("{{\"imported\":{}}}", count))
+        }
+        Err(e) => Err(ApiError::with_detail(ErrorCode::InvalidParam, e).into()),
+    }
+}
+
+/// 重新加载配置文件中的可热更配置项
+#[post("/admin/reload")]
+async fn admin_reload(
+    req: HttpRequest,
+    client: web::Data<Arc<HttpClient>>,
+    opt: web::Data<Opt>,
+) -> Result<String> {
+    check_admin(&req, &opt)?;
+    if opt.config.is_empty() {
+        return Err(ApiError::with_detail(ErrorCode::FeatureDisabled, "DOUBAN_CONFIG").into());
+    }
+    let changed = reload_config(&client, &opt.config)
+        .await
+        .map_err(ApiError::from)?;
+    Ok(serde_json::json!({ "reloaded": changed }).to_string())
+}
+
+/// 读取配置文件并应用到请求客户端，返回有变化的配置项
+async fn reload_config(client: &HttpClient, path: &str) -> anyhow::Result<Vec<&'static str>> {
+    let config = HotConfig::load(path).await?;
+    let changed = client.reload(&config);
+    if !changed.is_empty() {
+        println!("配置已重新加载: {}", changed.join(","));
+    }
+    Ok(changed)
+}
+
+/// 配置了管理口令时，校验请求头X-Admin-Token
+fn check_admin(req: &HttpRequest, opt: &Opt) -> Result<()> {
+    // 未配置口令时管理接口不开放
+    if opt.admin_token.is_empty() {
+        return Err(ApiError::with_detail(ErrorCode::FeatureDisabled, "DOUBAN_ADMIN_TOKEN").into());
+    }
+    let token = req
+        .headers()
+        .get("X-Admin-Token")
+        .map(|x| x.as_bytes())
+        .unwrap_or_default();
+    if constant_time_eq(token, opt.admin_token.as_bytes()) {
+        Ok(())
+    } else {
+        Err(ApiError::new(ErrorCode::Unauthorized).into())
+    }
+}
+
+/// 比较耗时与内容无关，避免按响应时间逐字节猜测口令
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter().zip(b.iter()).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+#[post("/auth/refresh")]
+async fn auth_refresh(
+    req: HttpRequest,
+    opt: web::Data<Opt>,
+    client: web::Data<Arc<HttpClient>>,
+) -> Result<String> {
+    check_admin(&req, &opt)?;
+    match client.login().await {
+        Ok(()) => Ok("{\"code\":0,\"msg\":\"登录成功\"}".to_string()),
+        Err(e) => Err(ApiError::from(e).into()),
+    }
+}
+
+#[get("/metrics")]
+async fn metrics_handler() -> impl Responder {
+    HttpResponse::Ok()
+        .content_type("text/plain; version=0.0.4; charset=utf-8")
+        .body(metrics::render())
+}
+
+/// 刮削过的条目构成统计，top为返回的导演/演员数
+#[get("/stats/library")]
+async fn library_stats(
+    douban_api: web::Data<Douban>,
+    query: web::Query<LibraryStatsQuery>,
+) -> Result<String> {
+    let top = query.top.unwrap_or(10);
+    if !(1..=100).contains(&top) {
+        return Err(ApiError::with_detail(ErrorCode::InvalidParam, "top: 1-100").into());
+    }
+    Ok(serde_json::to_string(&douban_api.library_stats(top)).unwrap())
+}
+
+/// 把请求的超时预算(毫秒)转换为截止时间
+fn budget_deadline(budget_ms: Option<u64>) -> Option<tokio::time::Instant> {
+    budget_ms.map(|x| tokio::time::Instant::now() + Duration::from_millis(x))
+}
+
+/// 详情响应附带缓存状态头: X-Cache、X-Fetched-At、X-Cache-TTL-Remaining(秒)
+fn cache_response(meta: &CacheMeta) -> HttpResponseBuilder {
+    let mut builder = HttpResponse::Ok();
+    builder
+        .content_type("text/plain; charset=utf-8")
+        .append_header(("X-Cache", if meta.hit { "HIT" } else { "MISS" }))
+        .append_header(("X-Fetched-At", util::iso_time(meta.fetched_at)))
+        .append_header(("X-Cache-TTL-Remaining", meta.ttl_remaining.to_string()));
+    builder
+}
+
+/// 校验每请求的上游超时，超过上限时按上限处理
+fn request_timeout(timeout_ms: Option<u64>, max_ms: u64) -> Result<Option<Duration>, ApiError> {
+    match timeout_ms {
+        Some(0) => Err(ApiError::with_detail(ErrorCode::InvalidParam, "timeout_ms")),
+        Some(x) => Ok(Some(Duration::from_millis(x.min(max_ms)))),
+        None => Ok(None),
+    }
+}
+
+fn timeout_error(timeout_ms: Option<u64>) -> ApiError {
+    ApiError::with_detail(
+        ErrorCode::UpstreamTimeout,
+        format!("timeout_ms: {}", timeout_ms.unwrap_or(0)),
+    )
+}
+
+/// 用归档页面生成缓存记录文件，可通过/admin/cache/import导入
+fn rebuild_cache(opt: &Opt, output: &str) -> std::io::Result<()> {
+    if opt.archive_dir.is_empty() {
+        println!("未配置归档目录 --archive-dir");
+        return Ok(());
+    }
+    let entries = archive::load_all(&opt.archive_dir);
+    let client = Arc::new(HttpClient::new(opt.clone()));
+    let records = Douban::new(client, opt).rebuild_from_archive(&entries);
+    let lines = records
+        .iter()
+        .map(|x| serde_json::to_string(x).unwrap())
+        .collect::<Vec<String>>();
+    std::fs::write(output, lines.join("\n"))?;
+    println!(
+        "已从 {} 个归档页面重建 {} 条缓存记录: {}",
+        entries.len(),
+        records.len(),
+        output
+    );
+    Ok(())
+}
+
+#[actix_web::main]
+async fn main() -> std::io::Result<()> {
+    let opt = Opt::parse();
+    if env::var("RUST_LOG").is_err() {
+        if opt.debug {
+            env::set_var(
+                "RUST_LOG",
+                "actix_web=debug,actix_server=debug,reqwest=debug",
+            );
+        } else {
+            env::set_var("RUST_LOG", "actix_web=info,actix_server=info,reqwest=warn");
+        }
+    }
+    env_logger::init();
+
+    match util::parse_time_offset(&opt.timezone) {
+        Some(offset) => util::set_time_offset(offset),
+        None => println!("时区配置无效，使用UTC: {}", opt.timezone),
+    }
+
+    if let Some(Command::RebuildCache { output }) = &opt.command {
+        return rebuild_cache(&opt, output);
+    }
+    if let Some(Command::Doctor) = &opt.command {
+        if !doctor::run(&opt).await {
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
+    let client = Arc::new(HttpClient::new(Opt::parse()));
+    let bind = (opt.host.clone(), opt.port);
+    let proxy_limiter = web::Data::new(Semaphore::new(opt.proxy_concurrency));
+    let jsonp_enabled = !opt.disable_jsonp;
+    let cors_origins = cors::parse_origins(&opt.cors_origins);
+    let hidden_fields = Arc::new(redact::parse_fields(&opt.hidden_fields));
+
+    // 定时探测图片域名，生成图片地址时使用最快的域名
+    let img_probe_client = Arc::clone(&client);
+    let img_probe_interval = Duration::from_secs(opt.probe_interval.max(1));
+    actix_web::rt::spawn(async move {
+        loop {
+            img_probe_client.probe_img_hosts().await;
+            actix_web::rt::time::sleep(img_probe_interval).await;
+        }
+    });
+
+    // 配置文件中的配置项覆盖命令行与环境变量，收到SIGHUP时重新加载
+    if !opt.config.is_empty() {
+        if let Err(err) = reload_config(&client, &opt.config).await {
+            println!("加载配置文件失败: {:?}", err);
+        }
+        #[cfg(unix)]
+        {
+            let reload_client = Arc::clone(&client);
+            let path = opt.config.clone();
+            actix_web::rt::spawn(async move {
+                use tokio::signal::unix::{signal, SignalKind};
+                let mut hangup = match signal(SignalKind::hangup()) {
+                    Ok(hangup) => hangup,
+                    Err(err) => {
+                        println!("监听SIGHUP失败: {:?}", err);
+                        return;
+                    }
+                };
+                while hangup.recv().await.is_some() {
+                    if let Err(err) = reload_config(&reload_client, &path).await {
+                        println!("重新加载配置失败: {:?}", err);
+                    }
+                }
+            });
+        }
+    }
+
+    // 多个上游时定时健康探测，自动切换到最优上游，上游可热更所以始终启动
+    let probe_client = Arc::clone(&client);
+    let probe_interval = Duration::from_secs(opt.probe_interval.max(1));
+    actix_web::rt::spawn(async move {
+        loop {
+            if probe_client.has_multi_upstream() {
+                probe_client.probe_upstreams().await;
+            }
+            actix_web::rt::time::sleep(probe_interval).await;
+        }
+    });
+
+    // 配置了账号密码时登录获取cookie，并定时续期
+    if client.can_login() {
+        let login_client = Arc::clone(&client);
+        let interval = Duration::from_secs(opt.auth_refresh_interval * 60 * 60);
+        let need_login = opt.cookie.is_empty();
+        actix_web::rt::spawn(async move {
+            if need_login {
+                match login_client.login().await {
+                    Ok(()) => println!("豆瓣登录成功"),
+                    Err(err) => println!("{:?}", err),
+                }
+            }
+            if interval.is_zero() {
+                return;
+            }
+            loop {
+                actix_web::rt::time::sleep(interval).await;
+                match login_client.login().await {
+                    Ok(()) => println!("豆瓣cookie续期成功"),
+                    Err(err) => println!("{:?}", err),
+                }
+            }
+        });
+    }
+
+    // 定时检查订阅条目的状态变化
+    if opt.subscription_interval > 0 {
+        let douban_api = Douban::new(Arc::clone(&client), &opt);
+        let interval = Duration::from_secs(opt.subscription_interval * 60);
+        actix_web::rt::spawn(async move {
+            loop {
+                actix_web::rt::time::sleep(interval).await;
+                douban_api.check_subscriptions().await;
+            }
+        });
+    }
+
+    // 定时写入评分变化记录快照，退出时再写入一次
+    changes::load(&opt.changes_file).await;
+    if !opt.changes_file.is_empty() {
+        let file = opt.changes_file.clone();
+        let interval = Duration::from_secs(opt.changes_interval.max(1) * 60);
+        actix_web::rt::spawn(async move {
+            loop {
+                actix_web::rt::time::sleep(interval).await;
+                changes::save(&file).await;
+            }
+        });
+    }
+
+    // 定时重新抓取跟踪条目的想看/在看人数
+    trending::load(&opt.trending_file).await;
+    if opt.trending_interval > 0 {
+        let douban_api = Douban::new(Arc::clone(&client), &opt);
+        let file = opt.trending_file.clone();
+        let interval = Duration::from_secs(opt.trending_interval * 60);
+        actix_web::rt::spawn(async move {
+            loop {
+                actix_web::rt::time::sleep(interval).await;
+                douban_api.refresh_trending(&file).await;
+            }
+        });
+    }
+
+    // 定时刷新新片榜并预热详情缓存，配置了cron时按cron触发，否则按间隔
+    let refresh_cron = if opt.refresh_cron.is_empty() {
+        None
+    } else {
+        match cron::Schedule::parse(&opt.refresh_cron) {
+            Ok(schedule) => Some(schedule),
+            Err(err) => {
+                println!("DOUBAN_REFRESH_CRON配置无效: {:?}", err);
+                std::process::exit(1);
+            }
+        }
+    };
+    if refresh_cron.is_some() || opt.refresh_interval > 0 {
+        let douban_api = Douban::new(Arc::clone(&client), &opt);
+        let interval = Duration::from_secs(opt.refresh_interval * 60);
+        actix_web::rt::spawn(async move {
+            loop {
+                if let Some(schedule) = &refresh_cron {
+                    let now = util::now_secs();
+                    let next = match schedule.next_after(now, util::time_offset()) {
+                        Some(next) => next,
+                        None => break,
+                    };
+                    actix_web::rt::time::sleep(Duration::from_secs(next - now)).await;
+                }
+                let api = douban_api.clone();
+                let res =
+                    actix_web::rt::spawn(async move { api.refresh_new_releases().await }).await;
+                match res {
+                    Ok(Ok(())) => println!("新片榜刷新完成"),
+                    Ok(Err(err)) => println!("新片榜刷新失败: {:?}", err),
+                    Err(err) => println!("新片榜刷新失败: {:?}", err),
+                }
+                if refresh_cron.is_none() {
+                    actix_web::rt::time::sleep(interval).await;
+                }
+            }
+        });
+    }
+
+    // 定时抓取关注标签的图书榜单快照
+    let book_tags = opt
+        .book_tags
+        .split(',')
+        .map(|x| x.trim().to_string())
+        .filter(|x| !x.is_empty())
+        .collect::<Vec<String>>();
+    let book_api = DoubanBookApi::new(Arc::clone(&client));
+    book_api
+        .load_tag_snapshots(&opt.book_tag_snapshot_file)
+        .await;
+    if !book_tags.is_empty() && opt.book_tag_interval > 0 {
+        let file = opt.book_tag_snapshot_file.clone();
+        let interval = Duration::from_secs(opt.book_tag_interval * 60);
+        actix_web::rt::spawn(async move {
+            loop {
+                book_api.snapshot_tags(&book_tags, &file).await;
+                actix_web::rt::time::sleep(interval).await;
+            }
+        });
+    }
+
+    let changes_file = opt.changes_file.clone();
+    let server = HttpServer::new(move || {
+        let cors_origins = cors_origins.clone();
+        let hidden_fields = Arc::clone(&hidden_fields);
+        App::new()
+            // 剔除配置的黑名单字段
+            .wrap_fn(move |req, srv| {
+                let fields = if hidden_fields.is_empty() || redact::is_exempt(&req) {
+                    None
+                } else {
+                    Some(Arc::clone(&hidden_fields))
+                };
+                let fut = srv.call(req);
+                async move {
+                    let res = fut.await?;
+                    match fields {
+                        Some(fields) => redact::strip(res, &fields).await,
+                        None => Ok(res.map_into_boxed_body()),
+                    }
+                }
+            })
+            // ?convert=s2t时把结果转为繁体
+            .wrap_fn(|req, srv| {
+                let convert = chinese::get_convert(&req);
+                let fut = srv.call(req);
+                async move {
+                    let res = fut.await?;
+                    match convert {
+                        Some(_) => chinese::to_traditional_response(res).await,
+                        None => Ok(res.map_into_boxed_body()),
+                    }
+                }
+            })
+            // 支持?callback=fn输出jsonp
+            .wrap_fn(move |req, srv| {
+                let callback = if jsonp_enabled {
+                    jsonp::get_callback(&req)
+                } else {
+                    None
+                };
+                let fut = srv.call(req);
+                async move {
+                    let res = fut.await?;
+                    match callback {
+                        Some(callback) => jsonp::wrap(res, &callback).await,
+                        None => Ok(res.map_into_boxed_body()),
+                    }
+                }
+            })
+            // 允许配置的来源跨域访问，并响应预检请求
+            .wrap_fn(move |req, srv| {
+                let origin = cors::allow_origin(&cors_origins, &req);
+                if let Some(origin) = &origin {
+                    if cors::is_preflight(&req) {
+                        return Either::Left(futures::future::ok(cors::preflight(req, origin)));
+                    }
+                }
+                let fut = srv.call(req);
+                Either::Right(async move {
+                    let mut res = fut.await?;
+                    if let Some(origin) = origin {
+                        cors::add_headers(&mut res, &origin);
+                    }
+                    Ok(res)
+                })
+            })
+            // 按Accept-Language输出错误消息
+            .wrap_fn(|req, srv| {
+                let lang = req
+                    .headers()
+                    .get("Accept-Language")
+                    .and_then(|x| x.to_str().ok())
+                    .map(Lang::from_accept_language)
+                    .unwrap_or(Lang::Zh);
+                let fut = srv.call(req);
+                async move {
+                    let res = fut.await?;
+                    if lang == Lang::Zh {
+                        Ok(res)
+                    } else {
+                        Ok(error::localize(res, lang))
+                    }
+                }
+            })
+            .wrap(middleware::Logger::default())
+            .app_data(web::Data::new(Douban::new(Arc::clone(&client), &opt)))
+            .app_data(web::Data::new(DoubanBookApi::new(Arc::clone(&client))))
+            .app_data(web::Data::new(DoubanMusicApi::new(Arc::clone(&client))))
+            .app_data(web::Data::new(Arc::clone(&client)))
+            .app_data(web::Data::new(Opt::parse()))
+            .app_data(proxy_limiter.clone())
+            // 允许提交完整的详情页html
+            .app_data(web::PayloadConfig::new(4 * 1024 * 1024))
+            .configure(routes)
+    });
+    let server = match systemd_listener() {
+        Some(listener) => {
+            println!("使用systemd传入的socket: {:?}", listener.local_addr());
+            server.listen(listener)?
+        }
+        None => server.bind(bind)?,
+    };
+    server.run().await?;
+    changes::save(&changes_file).await;
+
+    Ok(())
+}
+
+/// 注册全部路由
+fn routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(index)
+        .service(movies)
+        .service(suggest)
+        .service(tv)
+        .service(film)
+        .service(movie_by_imdb)
+        .service(movie)
+        .service(celebrities)
+        .service(subtitle_info)
+        .service(parental_guide)
+        .service(quotes)
+        .service(comment_keywords)
+        .service(episodes)
+        .service(aliases)
+        .service(alias_lookup)
+        .service(celebrity_batch)
+        .service(birthdays)
+        .service(celebrity)
+        .service(movie_widget)
+        .service(celebrity_nfo)
+        .service(celebrity_timeline)
+        .service(photo)
+        .service(images)
+        .service(season_posters)
+        .service(new_releases)
+        .service(trending_list)
+        .service(daily)
+        .service(weekly_box_office)
+        .service(collection)
+        .service(on_this_day)
+        .service(explore)
+        .service(book)
+        .service(books)
+        .service(book_opf)
+        .service(book_preview)
+        .service(book_by_isbn)
+        .service(book_author_works)
+        .service(book_tag_history)
+        .service(book_recent_hot)
+        .service(music_search)
+        .service(music)
+        .service(proxy)
+        .service(whatis)
+        .service(metrics_handler)
+        .service(library_stats)
+        .service(auth_refresh)
+        .service(cache_export)
+        .service(cache_import)
+        .service(admin_reload)
+        .service(sync_changed)
+        .service(parse_movie)
+        .service(parse_celebrity)
+        .service(scrape_series)
+        .service(add_subscription)
+        .service(subscriptions)
+        .service(remove_subscription);
+}
+
+/// systemd socket activation：LISTEN_PID为当前进程时使用从fd 3开始传入的第一个socket
+#[cfg(unix)]
+fn systemd_listener() -> Option<std::net::TcpListener> {
+    use std::os::unix::io::FromRawFd;
+
+    let pid = env::var("LISTEN_PID").ok()?.parse::<u32>().ok()?;
+    let fds = env::var("LISTEN_FDS").ok()?.parse::<i32>().ok()?;
+    if pid != std::process::id() || fds < 1 {
+        return None;
+    }
+    // 避免传给子进程
+    env::remove_var("LISTEN_PID");
+    env::remove_var("LISTEN_FDS");
+    env::remove_var("LISTEN_FDNAMES");
+    let listener = unsafe { std::net::TcpListener::from_raw_fd(3) };
+    if let Err(err) = listener.set_nonblocking(true) {
+        println!("设置systemd socket失败: {:?}", err);
+        return None;
+    }
+
+    Some(listener)
+}
+
+#[cfg(not(unix))]
+fn systemd_listener() -> Option<std::net::TcpListener> {
+    None
+}
+
+#[derive(Deserialize)]
+struct SearchQuery {
+    pub q: String,
+    #[serde(alias = "type", default)]
+    pub search_type: String,
+    #[serde(alias = "s", default)]
+    pub image_size: String,
+    pub count: Option<i32>,
+    #[serde(default)]
+    pub dedupe: bool,
+    pub min_rating: Option<f32>,
+    pub min_votes: Option<u32>,
+    #[serde(default)]
+    pub cache_only: bool,
+    pub noise_filter: Option<bool>,
+    pub budget_ms: Option<u64>,
+    pub timeout_ms: Option<u64>,
+    #[serde(default)]
+    pub sort: String,
+    #[serde(default)]
+    pub with_status: bool,
+    #[serde(default)]
+    pub convert: String,
+    #[serde(default)]
+    pub cat: String,
+    #[serde(default)]
+    pub fields: String,
+}
+
+#[derive(Deserialize)]
+struct BudgetQuery {
+    pub budget_ms: Option<u64>,
+}
+
+#[derive(Deserialize)]
+struct MovieQuery {
+    #[serde(alias = "s", default)]
+    pub image_size: String,
+    #[serde(default)]
+    pub expand: String,
+    pub timeout_ms: Option<u64>,
+    #[serde(default)]
+    pub palette: bool,
+    #[serde(default)]
+    pub format: String,
+}
+
+#[derive(Deserialize)]
+struct CommentKeywordsQuery {
+    pub pages: Option<usize>,
+    pub top: Option<usize>,
+}
+
+#[derive(Deserialize)]
+struct WidgetQuery {
+    #[serde(alias = "s", default)]
+    pub image_size: String,
+    #[serde(default)]
+    pub theme: String,
+}
+
+#[derive(Deserialize)]
+struct CelebritiesQuery {
+    #[serde(alias = "s", default)]
+    pub image_size: String,
+    pub start: Option<usize>,
+    pub limit: Option<usize>,
+    #[serde(default)]
+    pub paged: bool,
+    pub count: Option<usize>,
+    #[serde(default)]
+    pub roles: String,
+    #[serde(default)]
+    pub inline_info: bool,
+    pub inline_count: Option<usize>,
+}
+
+#[derive(Deserialize)]
+struct ImagesQuery {
+    pub count: Option<usize>,
+}
+
+#[derive(Deserialize)]
+struct BookQuery {
+    #[serde(default)]
+    pub format: String,
+    #[serde(default)]
+    pub fields: String,
+}
+
+#[derive(Deserialize)]
+struct PageQuery {
+    pub start: Option<u32>,
+    #[serde(default)]
+    pub paged: bool,
+}
+
+#[derive(Deserialize)]
+struct TagHistoryQuery {
+    #[serde(default)]
+    pub id: String,
+}
+
+#[derive(Deserialize)]
+struct TrendingQuery {
+    pub hours: Option<u64>,
+    pub count: Option<usize>,
+}
+
+#[derive(Deserialize)]
+struct SuggestQuery {
+    #[serde(default)]
+    pub q: String,
+}
+
+#[derive(Deserialize)]
+struct PhotoQuery {
+    #[serde(alias = "type", default)]
+    pub photo_type: String,
+    pub start: Option<usize>,
+    pub count: Option<usize>,
+    #[serde(default)]
+    pub paged: bool,
+}
+
+#[derive(Deserialize)]
+struct LibraryStatsQuery {
+    pub top: Option<usize>,
+}
+
+#[derive(Deserialize)]
+struct EpisodesQuery {
+    pub detail: Option<bool>,
+}
+
+#[derive(Deserialize)]
+struct RecentHotQuery {
+    #[serde(default)]
+    pub kind: String,
+}
+
+#[derive(Deserialize)]
+struct OnThisDayQuery {
+    pub date: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct AliasQuery {
+    pub name: String,
+}
+
+#[derive(Deserialize)]
+struct ExploreQuery {
+    pub decade: Option<String>,
+    pub region: Option<String>,
+    pub genre: Option<String>,
+    pub tag: Option<String>,
+    pub sort: Option<String>,
+    pub start: Option<u32>,
+    #[serde(default)]
+    pub paged: bool,
+}
+
+#[derive(Deserialize)]
+struct ExportQuery {
+    #[serde(default)]
+    pub format: String,
+}
+
+#[derive(Deserialize)]
+struct ScrapeSeriesBody {
+    pub files: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct SubscriptionBody {
+    pub sid: String,
+    pub webhook: String,
+}
+
+#[derive(Deserialize)]
+struct CelebrityBatchBody {
+    pub ids: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct SyncChangedBody {
+    #[serde(default = "default_sync_kind")]
+    pub kind: String,
+    pub items: Vec<SyncItem>,
+}
+
+fn default_sync_kind() -> String {
+    "movie".to_string()
+}
+
+#[derive(Deserialize)]
+struct SyncItem {
+    pub sid: String,
+    #[serde(default)]
+    pub since: u64,
+}
+
+#[derive(Deserialize)]
+struct ProxyQuery {
+    pub url: String,
+}
+
+/// 路由的输入输出契约测试：本地起一个回放 tests/fixtures 的假上游，
+/// 通过 --movie-base-url 等参数指向它，断言每个路由的状态码与json结构
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::http::{Method, StatusCode};
+    use serde_json::{json, Value};
+
+    const FIXTURE_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures");
+    const ADMIN_TOKEN: &str = "test-token";
+
+    /// 按路径回放上游响应，固件中的{{upstream}}替换为假上游的地址
+    async fn upstream(req: HttpRequest) -> HttpResponse {
+        let query = req.query_string();
+        let fixture = match req.path() {
+            "/subject/1292052/" => "movie_1292052.html",
+            "/celebrity/1054521/" => "celebrity_1054521.html",
+            "/subject/2567698/" => "book_2567698.html",
+            "/search" if query.contains("cat=1002") => "search_movie.html",
+            "/search" if query.contains("cat=1001") => "search_book.html",
+            "/j/subject_suggest" => "suggest_movie.json",
+            // 豆瓣按isbn查询时跳转到条目页
+            "/isbn/9787536692930/" => {
+                return HttpResponse::Found()
+                    .insert_header(("Location", "/subject/2567698/"))
+                    .finish()
+            }
+            // 图片(封面原图检查)
+            x if x.starts_with("/view/") => return HttpResponse::Ok().finish(),
+            _ => return HttpResponse::NotFound().finish(),
+        };
+        let base = format!("http://{}", req.connection_info().host());
+        let body = std::fs::read_to_string(format!("{}/{}", FIXTURE_DIR, fixture))
+            .unwrap()
+            .replace("{{upstream}}", &base);
+        let content_type = if fixture.ends_with(".json") {
+            "application/json; charset=utf-8"
+        } else {
+            "text/html; charset=utf-8"
+        };
+        HttpResponse::Ok().content_type(content_type).body(body)
+    }
+
+    async fn start_upstream() -> String {
+        let server = HttpServer::new(|| App::new().default_service(web::to(upstream)))
+            .workers(1)
+            .disable_signals()
+            .bind(("127.0.0.1", 0))
+            .unwrap();
+        let addr = server.addrs()[0];
+        actix_web::rt::spawn(server.run());
+        format!("http://{}", addr)
+    }
+
+    fn test_opt(base: &str) -> Opt {
+        Opt::parse_from([
+            "douban-api-rs",
+            "--movie-base-url",
+            base,
+            "--book-base-url",
+            base,
+            "--music-base-url",
+            base,
+            "--www-base-url",
+            base,
+            "--mobile-base-url",
+            base,
+            "--retry-count",
+            "0",
+            "--admin-token",
+            ADMIN_TOKEN,
+        ])
+    }
+
+    fn fixture(name: &str) -> String {
+        std::fs::read_to_string(format!("{}/{}", FIXTURE_DIR, name)).unwrap()
+    }
+
+    /// 按schema断言json结构，schema中的字符串为类型名(string/number/bool/object/any)，
+    /// 以?结尾表示可为null；对象只检查列出的字段且字段必须存在；
+    /// 数组schema为空时不检查元素，否则要求数组非空且每一项符合第一个元素
+    fn assert_schema(path: &str, value: &Value, schema: &Value) {
+        match schema {
+            Value::String(kind) => {
+                let (kind, nullable) = match kind.strip_suffix('?') {
+                    Some(kind) => (kind, true),
+                    None => (kind.as_str(), false),
+                };
+                if value.is_null() {
+                    assert!(nullable, "{}: 不能为null", path);
+                    return;
+                }
+                let ok = match kind {
+                    "string" => value.is_string(),
+                    "number" => value.is_number(),
+                    "bool" => value.is_boolean(),
+                    "object" => value.is_object(),
+                    "any" => true,
+                    _ => panic!("{}: 未知类型{}", path, kind),
+                };
+                assert!(ok, "{}: 应为{}，实际为{}", path, kind, value);
+            }
+            Value::Object(fields) => {
+                let object = value
+                    .as_object()
+                    .unwrap_or_else(|| panic!("{}: 应为object，实际为{}", path, value));
+                for (name, schema) in fields.iter() {
+                    let path = format!("{}.{}", path, name);
+                    let value = object
+                        .get(name)
+                        .unwrap_or_else(|| panic!("{}: 缺少字段", path));
+                    assert_schema(&path, value, schema);
+                }
+            }
+            Value::Array(items) => {
+                let list = value
+                    .as_array()
+                    .unwrap_or_else(|| panic!("{}: 应为array，实际为{}", path, value));
+                if let Some(item) = items.first() {
+                    assert!(!list.is_empty(), "{}: 不能为空", path);
+                    for (i, value) in list.iter().enumerate() {
+                        assert_schema(&format!("{}[{}]", path, i), value, item);
+                    }
+                }
+            }
+            _ => panic!("{}: 无效的schema {}", path, schema),
+        }
+    }
+
+    fn movie_schema() -> Value {
+        json!({
+            "cat": "string",
+            "cat_kind": "string?",
+            "sid": "string",
+            "name": "string",
+            "rating": "string",
+            "votes": "number",
+            "img": "string",
+            "year": "string",
+        })
+    }
+
+    /// json请求体按json发送，其余为页面源码
+    fn content_type(body: &str) -> &'static str {
+        if body.starts_with('{') {
+            "application/json"
+        } else {
+            "text/html; charset=utf-8"
+        }
+    }
+
+    fn movie_info_schema() -> Value {
+        json!({
+            "sid": "string",
+            "name": "string",
+            "originalName": "string",
+            "baseName": "string",
+            "seasonNumber": "number",
+            "rating": "string",
+            "votes": "number",
+            "img": "string",
+            "year": "string",
+            "intro": "string",
+            "director": "string",
+            "writer": "string",
+            "actor": "string",
+            "genre": "string",
+            "site": "string",
+            "country": "string",
+            "language": "string",
+            "screen": "string",
+            "screen_normalized": "string",
+            "release_dates": [{"date": "string", "region": "string"}],
+            "production_companies": [],
+            "duration": "string",
+            "subname": "string",
+            "aka_cn": [],
+            "aka_en": [],
+            "aka_other": [],
+            "imdb": "string",
+            "content_rating": "string?",
+            "playable": "bool",
+            "play_sources": [],
+            "wish_count": "number",
+            "doing_count": "number",
+            "celebrities": [{
+                "id": "string",
+                "img": "string",
+                "name": "string",
+                "role_kind": "string?",
+                "role": "string",
+            }],
+        })
+    }
+
+    fn celebrity_schema() -> Value {
+        json!({
+            "id": "string",
+            "img": "string",
+            "name": "string",
+            "role": "string",
+            "intro": "string",
+            "gender": "string",
+            "constellation": "string",
+            "birthdate": "string",
+            "birthdate_normalized": "string",
+            "birthplace": "string",
+            "nickname": "string",
+            "imdb": "string",
+            "family": "string",
+            "works": [{"sid": "string", "name": "string"}],
+        })
+    }
+
+    fn book_schema() -> Value {
+        json!({
+            "id": "string",
+            "author": [],
+            "images": {
+                "small": "string",
+                "medium": "string",
+                "large": "string",
+                "raw": "string",
+            },
+            "rating": {"average": "number", "numRaters": "number"},
+            "pubdate": "string",
+            "publisher": "string",
+            "summary": "string",
+            "title": "string",
+        })
+    }
+
+    fn book_detail_schema() -> Value {
+        let mut schema = book_schema();
+        let detail = json!({
+            "author_ids": [],
+            "author_intro": "string",
+            "author_intro_text": "string",
+            "translators": [],
+            "binding": "string",
+            "category": "string",
+            "isbn13": "string",
+            "pages": "string",
+            "price": "string",
+            "ebook_price": "string",
+            "pubdate_normalized": "string",
+            "producer": "string",
+            "serials": "string",
+            "subtitle": "string",
+            "summary_text": "string",
+            "tags": [{"name": "string"}],
+            "origin": "string",
+            "content_rating": "string?",
+        });
+        schema
+            .as_object_mut()
+            .unwrap()
+            .extend(detail.as_object().unwrap().clone());
+        schema
+    }
+
+    fn error_schema() -> Value {
+        json!({"code": "string", "message": "string", "detail": "string"})
+    }
+
+    #[actix_web::test]
+    async fn route_contracts() {
+        let base = start_upstream().await;
+        let opt = test_opt(&base);
+        let client = Arc::new(HttpClient::new(opt.clone()));
+        let app = actix_web::test::init_service(
+            App::new()
+                .app_data(web::Data::new(Douban::new(Arc::clone(&client), &opt)))
+                .app_data(web::Data::new(DoubanBookApi::new(Arc::clone(&client))))
+                .app_data(web::Data::new(DoubanMusicApi::new(Arc::clone(&client))))
+                .app_data(web::Data::new(Arc::clone(&client)))
+                .app_data(web::Data::new(opt.clone()))
+                .app_data(web::Data::new(Semaphore::new(opt.proxy_concurrency)))
+                .app_data(web::PayloadConfig::new(4 * 1024 * 1024))
+                .configure(routes),
+        )
+        .await;
+
+        let q_movie = urlencoding::encode("肖申克").to_string();
+        let q_book = urlencoding::encode("三体").to_string();
+        // (方法, 路径, 请求体, 状态码, 响应schema)，schema为null时不检查响应体
+        // 依赖固件之外豆瓣页面(影人列表、剧照、榜单、豆列、短评、图书试读、音乐等)或外部服务
+        // (OMDb、webhook、登录)的路由不在此覆盖，清单见README的测试一节
+        let cases: Vec<(Method, String, String, StatusCode, Value)> = vec![
+            (
+                Method::GET,
+                format!("/movies?q={}", q_movie),
+                String::new(),
+                StatusCode::OK,
+                json!([movie_schema()]),
+            ),
+            (
+                Method::GET,
+                format!("/movies?q={}&with_status=true", q_movie),
+                String::new(),
+                StatusCode::OK,
+                json!({"result_status": "string", "data": [movie_schema()]}),
+            ),
+            (
+                Method::GET,
+                format!("/suggest?q={}", q_movie),
+                String::new(),
+                StatusCode::OK,
+                json!([{
+                    "sid": "string",
+                    "title": "string",
+                    "year": "string",
+                    "type": "string",
+                    "img": "string",
+                }]),
+            ),
+            (
+                Method::GET,
+                "/movies/1292052".to_string(),
+                String::new(),
+                StatusCode::OK,
+                movie_info_schema(),
+            ),
+            (
+                Method::GET,
+                "/celebrities/1054521".to_string(),
+                String::new(),
+                StatusCode::OK,
+                celebrity_schema(),
+            ),
+            (
+                Method::POST,
+                "/parse/movie/1292052".to_string(),
+                fixture("movie_1292052.html"),
+                StatusCode::OK,
+                movie_info_schema(),
+            ),
+            (
+                Method::POST,
+                "/parse/celebrity/1054521".to_string(),
+                fixture("celebrity_1054521.html"),
+                StatusCode::OK,
+                celebrity_schema(),
+            ),
+            (
+                Method::GET,
+                format!("/v2/book/search?q={}", q_book),
+                String::new(),
+                StatusCode::OK,
+                json!({"code": "number", "msg": "string", "books": [book_schema()]}),
+            ),
+            (
+                Method::GET,
+                "/v2/book/id/2567698".to_string(),
+                String::new(),
+                StatusCode::OK,
+                book_detail_schema(),
+            ),
+            (
+                Method::GET,
+                "/v2/book/isbn/9787536692930".to_string(),
+                String::new(),
+                StatusCode::OK,
+                book_detail_schema(),
+            ),
+            (
+                Method::GET,
+                format!("/film?q={}", q_movie),
+                String::new(),
+                StatusCode::OK,
+                json!([movie_schema()]),
+            ),
+            (
+                Method::GET,
+                format!("/tv?q={}", q_movie),
+                String::new(),
+                StatusCode::OK,
+                json!([]),
+            ),
+            (
+                Method::GET,
+                "/movies/1292052?format=nfo".to_string(),
+                String::new(),
+                StatusCode::OK,
+                Value::Null,
+            ),
+            (
+                Method::GET,
+                "/widget/movie/1292052".to_string(),
+                String::new(),
+                StatusCode::OK,
+                Value::Null,
+            ),
+            (
+                Method::GET,
+                "/celebrities/1054521/nfo".to_string(),
+                String::new(),
+                StatusCode::OK,
+                Value::Null,
+            ),
+            (
+                Method::POST,
+                "/celebrities/batch".to_string(),
+                r#"{"ids":["1054521"]}"#.to_string(),
+                StatusCode::OK,
+                json!([{"id": "string", "name": "string"}]),
+            ),
+            (
+                Method::GET,
+                "/aliases/1292052".to_string(),
+                String::new(),
+                StatusCode::OK,
+                Value::Null,
+            ),
+            (
+                Method::GET,
+                "/movies/1292052/subtitle-info".to_string(),
+                String::new(),
+                StatusCode::OK,
+                Value::Null,
+            ),
+            (
+                Method::GET,
+                "/whatis/1292052".to_string(),
+                String::new(),
+                StatusCode::OK,
+                json!({"id": "string", "type": "string", "info": movie_info_schema()}),
+            ),
+            (
+                Method::POST,
+                "/sync/changed".to_string(),
+                r#"{"kind":"movie","items":[{"sid":"1292052","since":0}]}"#.to_string(),
+                StatusCode::OK,
+                json!({"changed": [], "unknown": []}),
+            ),
+            (
+                Method::GET,
+                "/v2/book/id/2567698?format=marc".to_string(),
+                String::new(),
+                StatusCode::OK,
+                json!({"id": "string", "fields": [{"tag": "string", "subfields": []}]}),
+            ),
+            (
+                Method::GET,
+                "/v2/book/id/2567698/opf".to_string(),
+                String::new(),
+                StatusCode::OK,
+                Value::Null,
+            ),
+            (
+                Method::GET,
+                "/stats/library".to_string(),
+                String::new(),
+                StatusCode::OK,
+                Value::Null,
+            ),
+            (
+                Method::GET,
+                "/admin/cache/export?format=json".to_string(),
+                String::new(),
+                StatusCode::OK,
+                json!([]),
+            ),
+            (
+                Method::GET,
+                "/trending".to_string(),
+                String::new(),
+                StatusCode::OK,
+                json!([]),
+            ),
+            (
+                Method::GET,
+                "/subscriptions".to_string(),
+                String::new(),
+                StatusCode::OK,
+                json!([]),
+            ),
+            (
+                Method::GET,
+                "/".to_string(),
+                String::new(),
+                StatusCode::OK,
+                Value::Null,
+            ),
+            (
+                Method::GET,
+                "/metrics".to_string(),
+                String::new(),
+                StatusCode::OK,
+                Value::Null,
+            ),
+            (
+                Method::GET,
+                "/movies/404404".to_string(),
+                String::new(),
+                StatusCode::NOT_FOUND,
+                error_schema(),
+            ),
+            (
+                Method::GET,
+                "/trending?hours=0".to_string(),
+                String::new(),
+                StatusCode::BAD_REQUEST,
+                error_schema(),
+            ),
+            (
+                Method::POST,
+                "/parse/movie/1".to_string(),
+                "<html></html>".to_string(),
+                StatusCode::BAD_REQUEST,
+                error_schema(),
+            ),
+            (
+                Method::GET,
+                "/movies/1292052?format=yaml".to_string(),
+                String::new(),
+                StatusCode::BAD_REQUEST,
+                error_schema(),
+            ),
+            (
+                Method::GET,
+                "/widget/movie/1292052?theme=blue".to_string(),
+                String::new(),
+                StatusCode::BAD_REQUEST,
+                error_schema(),
+            ),
+            (
+                Method::POST,
+                "/sync/changed".to_string(),
+                r#"{"kind":"music","items":[]}"#.to_string(),
+                StatusCode::BAD_REQUEST,
+                error_schema(),
+            ),
+            (
+                Method::GET,
+                "/stats/library?top=0".to_string(),
+                String::new(),
+                StatusCode::BAD_REQUEST,
+                error_schema(),
+            ),
+            (
+                Method::POST,
+                "/subscriptions".to_string(),
+                r#"{"sid":"1292052","webhook":"http://127.0.0.1/hook"}"#.to_string(),
+                StatusCode::BAD_REQUEST,
+                error_schema(),
+            ),
+            (
+                Method::POST,
+                "/admin/reload".to_string(),
+                String::new(),
+                StatusCode::NOT_FOUND,
+                error_schema(),
+            ),
+        ];
+
+        for (method, uri, body, status, schema) in cases.into_iter() {
+            let req = actix_web::test::TestRequest::default()
+                .method(method.clone())
+                .uri(&uri)
+                .insert_header(("X-Admin-Token", ADMIN_TOKEN))
+                .insert_header(("Content-Type", content_type(&body)))
+                .set_payload(body)
+                .to_request();
+            let res = actix_web::test::call_service(&app, req).await;
+            let res_status = res.status();
+            let bytes = actix_web::test::read_body(res).await;
+            assert_eq!(
+                res_status,
+                status,
+                "{} {}: {}",
+                method,
+                uri,
+                String::from_utf8_lossy(&bytes)
+            );
+            if schema.is_null() {
+                continue;
+            }
+            let value = serde_json::from_slice::<Value>(&bytes)
+                .unwrap_or_else(|e| panic!("{} {}: 响应不是json {:?}", method, uri, e));
+            assert_schema(&format!("{} {}", method, uri), &value, &schema);
+        }
+
+        // 管理与订阅接口不带口令时拒绝
+        let req = actix_web::test::TestRequest::get()
+            .uri("/subscriptions")
+            .to_request();
+        let res = actix_web::test::call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+    }
 }
@@ -0,0 +1,232 @@
+use lazy_static::*;
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+lazy_static! {
+    static ref FIELD_STATS: Mutex<BTreeMap<(String, String), FieldStat>> =
+        Mutex::new(BTreeMap::new());
+}
+
+static UPSTREAM_REQUESTS: AtomicU64 = AtomicU64::new(0);
+static UPSTREAM_THROTTLED: AtomicU64 = AtomicU64::new(0);
+static UPSTREAM_LOGIN_REQUIRED: AtomicU64 = AtomicU64::new(0);
+static IMAGE_INCOMPLETE: AtomicU64 = AtomicU64::new(0);
+static IMAGE_RETRIES: AtomicU64 = AtomicU64::new(0);
+static UPSTREAM_RETRIES: AtomicU64 = AtomicU64::new(0);
+// 当前限速，f64 按位存储
+static UPSTREAM_QPS: AtomicU64 = AtomicU64::new(0);
+
+/// 最近多少次解析结果作为对比窗口
+const WINDOW_SIZE: usize = 20;
+/// 窗口空置率比历史空置率高出多少时报警
+const ALARM_THRESHOLD: f64 = 0.5;
+
+#[derive(Default)]
+struct FieldStat {
+    total: u64,
+    empty: u64,
+    alarms: u64,
+    alarming: bool,
+    recent: VecDeque<bool>, //最近的解析结果，true 为空
+}
+
+impl FieldStat {
+    fn record(&mut self, is_empty: bool) -> bool {
+        self.total += 1;
+        if is_empty {
+            self.empty += 1;
+        }
+        self.recent.push_back(is_empty);
+        if self.recent.len() > WINDOW_SIZE {
+            self.recent.pop_front();
+        }
+
+        // 历史样本不足时不做判断
+        if self.total < (WINDOW_SIZE * 2) as u64 {
+            return false;
+        }
+        let recent_empty = self.recent.iter().filter(|&&x| x).count() as u64;
+        let history_total = self.total - self.recent.len() as u64;
+        let history_rate = (self.empty - recent_empty) as f64 / history_total as f64;
+        let recent_rate = recent_empty as f64 / self.recent.len() as f64;
+        if recent_rate - history_rate >= ALARM_THRESHOLD {
+            if !self.alarming {
+                self.alarming = true;
+                self.alarms += 1;
+                return true;
+            }
+        } else {
+            self.alarming = false;
+        }
+
+        false
+    }
+}
+
+/// 统计解析结果各字段的空置情况，字段空置率突增时输出报警
+pub fn validate<T: Serialize>(kind: &str, data: &T) {
+    let fields = match serde_json::to_value(data) {
+        Ok(Value::Object(map)) => map,
+        _ => return,
+    };
+
+    let mut stats = FIELD_STATS.lock().unwrap();
+    for (field, value) in fields.iter() {
+        let is_empty = match value {
+            Value::Null => true,
+            Value::String(s) => s.trim().is_empty(),
+            Value::Array(a) => a.is_empty(),
+            Value::Object(o) => o.is_empty(),
+            _ => false,
+        };
+        let stat = stats
+            .entry((kind.to_string(), field.to_string()))
+            .or_default();
+        if stat.record(is_empty) {
+            println!(
+                "解析报警: {} 的 {} 字段空置率突增，豆瓣页面结构可能已变化",
+                kind, field
+            );
+        }
+    }
+}
+
+/// 记录一次上游请求，throttled 表示被豆瓣限流
+pub fn record_upstream(throttled: bool) {
+    UPSTREAM_REQUESTS.fetch_add(1, Ordering::Relaxed);
+    if throttled {
+        UPSTREAM_THROTTLED.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// 记录一次上游请求失败后的重试
+pub fn record_upstream_retry() {
+    UPSTREAM_RETRIES.fetch_add(1, Ordering::Relaxed);
+}
+
+/// 记录一次被跳转到登录页的上游请求
+pub fn record_login_required() {
+    UPSTREAM_LOGIN_REQUIRED.fetch_add(1, Ordering::Relaxed);
+}
+
+/// 记录一次下载到的不完整图片，retry 表示随后会重试
+pub fn record_incomplete_image(retry: bool) {
+    IMAGE_INCOMPLETE.fetch_add(1, Ordering::Relaxed);
+    if retry {
+        IMAGE_RETRIES.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+pub fn set_upstream_qps(qps: f64) {
+    UPSTREAM_QPS.store(qps.to_bits(), Ordering::Relaxed);
+}
+
+/// 输出 prometheus 文本格式的指标
+pub fn render() -> String {
+    let stats = FIELD_STATS.lock().unwrap();
+    let mut out = String::new();
+    writeln!(out, "# TYPE douban_upstream_requests_total counter").unwrap();
+    writeln!(
+        out,
+        "douban_upstream_requests_total {}",
+        UPSTREAM_REQUESTS.load(Ordering::Relaxed)
+    )
+    .unwrap();
+    writeln!(out, "# TYPE douban_upstream_throttled_total counter").unwrap();
+    writeln!(
+        out,
+        "douban_upstream_throttled_total {}",
+        UPSTREAM_THROTTLED.load(Ordering::Relaxed)
+    )
+    .unwrap();
+    writeln!(out, "# TYPE douban_upstream_retries_total counter").unwrap();
+    writeln!(
+        out,
+        "douban_upstream_retries_total {}",
+        UPSTREAM_RETRIES.load(Ordering::Relaxed)
+    )
+    .unwrap();
+    writeln!(out, "# TYPE douban_upstream_login_required_total counter").unwrap();
+    writeln!(
+        out,
+        "douban_upstream_login_required_total {}",
+        UPSTREAM_LOGIN_REQUIRED.load(Ordering::Relaxed)
+    )
+    .unwrap();
+    writeln!(out, "# TYPE douban_image_incomplete_total counter").unwrap();
+    writeln!(
+        out,
+        "douban_image_incomplete_total {}",
+        IMAGE_INCOMPLETE.load(Ordering::Relaxed)
+    )
+    .unwrap();
+    writeln!(out, "# TYPE douban_image_retries_total counter").unwrap();
+    writeln!(
+        out,
+        "douban_image_retries_total {}",
+        IMAGE_RETRIES.load(Ordering::Relaxed)
+    )
+    .unwrap();
+    writeln!(out, "# TYPE douban_upstream_qps gauge").unwrap();
+    writeln!(
+        out,
+        "douban_upstream_qps {}",
+        f64::from_bits(UPSTREAM_QPS.load(Ordering::Relaxed))
+    )
+    .unwrap();
+    write_metric(
+        &mut out,
+        &stats,
+        "douban_field_parsed_total",
+        "counter",
+        |x| x.total,
+    );
+    write_metric(
+        &mut out,
+        &stats,
+        "douban_field_empty_total",
+        "counter",
+        |x| x.empty,
+    );
+    write_metric(
+        &mut out,
+        &stats,
+        "douban_field_empty_alarm_total",
+        "counter",
+        |x| x.alarms,
+    );
+    write_metric(
+        &mut out,
+        &stats,
+        "douban_field_empty_alarming",
+        "gauge",
+        |x| x.alarming as u64,
+    );
+
+    out
+}
+
+fn write_metric(
+    out: &mut String,
+    stats: &BTreeMap<(String, String), FieldStat>,
+    name: &str,
+    metric_type: &str,
+    value: fn(&FieldStat) -> u64,
+) {
+    writeln!(out, "# TYPE {} {}", name, metric_type).unwrap();
+    for ((kind, field), stat) in stats.iter() {
+        writeln!(
+            out,
+            "{}{{kind=\"{}\",field=\"{}\"}} {}",
+            name,
+            kind,
+            field,
+            value(stat)
+        )
+        .unwrap();
+    }
+}
@@ -0,0 +1,275 @@
+use crate::bookapi::{is_login_wall, Image, Rating, Tag};
+use crate::http::HttpClient;
+use crate::util::normalize_date;
+use anyhow::Result;
+use lazy_static::*;
+use moka::future::{Cache, CacheBuilder};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use visdom::Vis;
+
+lazy_static! {
+    static ref MUSIC_CACHE: Cache<String, DoubanMusic> = CacheBuilder::new(CACHE_SIZE)
+        .time_to_live(MUSIC_CACHE_TTL)
+        .build();
+}
+
+const CACHE_SIZE: usize = 100;
+const MUSIC_CACHE_TTL: Duration = Duration::from_secs(10 * 60);
+
+#[derive(Clone)]
+pub struct DoubanMusicApi {
+    client: Arc<HttpClient>,      //请求客户端
+    re_id: Regex,                 //id 正则
+    re_info_pair: Regex,          //匹配:字符两边的信息
+    re_remove_split_space: Regex, //去除/分隔符两边多余空格
+    re_num_raters: Regex,         //评价人数
+    re_track_no: Regex,           //曲目序号
+}
+
+impl DoubanMusicApi {
+    pub fn new(client: Arc<HttpClient>) -> DoubanMusicApi {
+        let re_id = Regex::new(r"sid: (\d+?),").unwrap();
+        let re_info_pair = Regex::new(r"([^\s]+?):\s*([^\n]+)").unwrap();
+        let re_remove_split_space = Regex::new(r"\s+?/\s+").unwrap();
+        let re_num_raters = Regex::new(r"(\d+)\s*人评价").unwrap();
+        let re_track_no = Regex::new(r"^\d+[.、]?\s*").unwrap();
+        Self {
+            client,
+            re_id,
+            re_info_pair,
+            re_remove_split_space,
+            re_num_raters,
+            re_track_no,
+        }
+    }
+
+    pub async fn search(&self, q: &str, count: usize) -> Result<DoubanMusicResult> {
+        let mut list = Vec::new();
+        if !q.is_empty() {
+            let url = format!("{}/search", self.client.www_base());
+            let res = self
+                .client
+                .send(self.client.get(url).query(&[("cat", "1003"), ("q", q)]))
+                .await?
+                .error_for_status()?;
+            let final_url = res.url().to_string();
+            let res = res.text().await?;
+            if is_login_wall(&final_url, &res) {
+                println!("音乐搜索需要登录: {}", q);
+            } else {
+                list = self.parse_list(&res);
+            }
+            list.truncate(count);
+        }
+
+        Ok(DoubanMusicResult {
+            code: 0,
+            msg: String::new(),
+            musics: list,
+        })
+    }
+
+    fn parse_list(&self, res: &str) -> Vec<DoubanMusic> {
+        let document = Vis::load(res).unwrap();
+        document
+            .find("div.result-list")
+            .first()
+            .find(".result")
+            .map(|_index, x| {
+                let x = Vis::dom(x);
+                let onclick = x.find("div.title a").attr("onclick");
+                let id = onclick
+                    .and_then(|x| self.re_id.captures(&x.to_string()).map(|c| c[1].to_string()))
+                    .unwrap_or_default();
+                let title = x.find("div.title a").text().trim().to_string();
+                let summary = x.find("p").text().trim().to_string();
+                let large = match x.find(".pic img").attr("src") {
+                    Some(src) => src.to_string(),
+                    None => String::new(),
+                };
+                let rate = x.find(".rating_nums").text().trim().to_string();
+                let num_raters = self.parse_num_raters(x.find(".rating-info").text());
+                // 表演者 / 发行时间 / 专辑类型 / 介质 / 流派
+                let cast = x.find(".subject-cast").text().to_string();
+                let parts: Vec<&str> = cast
+                    .split('/')
+                    .map(|x| x.trim())
+                    .filter(|x| !x.is_empty())
+                    .collect();
+                let pubdate = parts
+                    .iter()
+                    .find(|x| x.chars().take(4).filter(|c| c.is_ascii_digit()).count() == 4)
+                    .map(|x| x.to_string())
+                    .unwrap_or_default();
+                let performers = match parts.first() {
+                    Some(x) if **x != pubdate => vec![x.to_string()],
+                    _ => Vec::new(),
+                };
+                DoubanMusic {
+                    id,
+                    title,
+                    alt_title: String::new(),
+                    performers,
+                    genre: String::new(),
+                    album_type: String::new(),
+                    media: String::new(),
+                    pubdate_normalized: normalize_date(&pubdate),
+                    pubdate,
+                    publisher: String::new(),
+                    discs: String::new(),
+                    barcode: String::new(),
+                    images: Image::new(large),
+                    rating: Rating::new(rate.parse::<f32>().unwrap_or(0.0), num_raters),
+                    tracks: Vec::new(),
+                    summary,
+                    tags: Vec::new(),
+                }
+            })
+            .into_iter()
+            .filter(|x| !x.id.is_empty())
+            .collect::<Vec<DoubanMusic>>()
+    }
+
+    pub async fn get_music_info(&self, id: &str) -> Result<DoubanMusic> {
+        let cache_key = id.to_string();
+        if let Some(info) = MUSIC_CACHE.get(&cache_key) {
+            return Ok(info);
+        }
+
+        let url = format!("{}/subject/{}/", self.client.music_base(), id);
+        let html = self.client.get_html(&url).await?.body;
+        let document = Vis::load(&html).unwrap();
+        let x = document.find("#wrapper");
+        let title = x.find("h1>span:first-child").text().trim().to_string();
+        let large = match x.find("#mainpic a.nbg").attr("href") {
+            Some(href) => href.to_string(),
+            None => match x.find("#mainpic img").attr("src") {
+                Some(src) => src.to_string(),
+                None => String::new(),
+            },
+        };
+        let rate = x
+            .find("strong.rating_num")
+            .text()
+            .trim()
+            .parse::<f32>()
+            .unwrap_or(0.0);
+        let num_raters = self.parse_num_raters(x.find("a.rating_people").text());
+        let mut summary = x
+            .find("#link-report .all.hidden")
+            .text()
+            .trim()
+            .to_string();
+        if summary.is_empty() {
+            summary = x.find("#link-report").text().trim().to_string();
+        }
+        let tags = x
+            .find("#db-tags-section .tags-body a")
+            .map(|_index, t| Tag::new(t.text().trim().to_string()));
+
+        let info_text_map = self.parse_info_text(x.find("#info").text().trim());
+        let pubdate = self.get_text(&info_text_map, "发行时间");
+        let info = DoubanMusic {
+            id: id.to_string(),
+            title,
+            alt_title: self.get_text(&info_text_map, "又名"),
+            performers: info_text_map
+                .get("表演者")
+                .map(|x| {
+                    x.split('/')
+                        .map(|x| x.trim().to_string())
+                        .filter(|x| !x.is_empty())
+                        .collect()
+                })
+                .unwrap_or_default(),
+            genre: self.get_text(&info_text_map, "流派"),
+            album_type: self.get_text(&info_text_map, "专辑类型"),
+            media: self.get_text(&info_text_map, "介质"),
+            pubdate_normalized: normalize_date(&pubdate),
+            pubdate,
+            publisher: self.get_text(&info_text_map, "出版者"),
+            discs: self.get_text(&info_text_map, "唱片数"),
+            barcode: self.get_text(&info_text_map, "条形码"),
+            images: Image::new(large),
+            rating: Rating::new(rate, num_raters),
+            tracks: self.parse_tracks(&x),
+            summary,
+            tags,
+        };
+        MUSIC_CACHE.insert(cache_key, info.clone()).await;
+
+        Ok(info)
+    }
+
+    /// 新版页面曲目为 ul.track-items 列表，旧版为 .track-list 中按行排列的文本
+    fn parse_tracks(&self, x: &visdom::types::Elements) -> Vec<String> {
+        let mut tracks = x
+            .find("ul.track-items li")
+            .map(|_index, li| li.text().trim().to_string());
+        if tracks.is_empty() {
+            tracks = x
+                .find(".track-list .indent")
+                .text()
+                .lines()
+                .map(|line| self.re_track_no.replace(line.trim(), "").to_string())
+                .collect();
+        }
+        tracks.retain(|x| !x.is_empty());
+
+        tracks
+    }
+
+    fn parse_num_raters(&self, text: &str) -> u32 {
+        match self.re_num_raters.captures(text) {
+            Some(x) => x[1].parse::<u32>().unwrap_or(0),
+            None => 0,
+        }
+    }
+
+    fn get_text(&self, info_text_map: &HashMap<String, String>, key: &str) -> String {
+        info_text_map.get(key).cloned().unwrap_or_default()
+    }
+
+    fn parse_info_text(&self, s: &str) -> HashMap<String, String> {
+        let mut map = HashMap::new();
+        // 先替换掉多表演者/之间的换行符，避免下面的正则匹配少表演者
+        let fix_str = self.re_remove_split_space.replace_all(s, "/").to_string();
+        for cap in self.re_info_pair.captures_iter(&fix_str) {
+            map.insert(cap[1].trim().to_string(), cap[2].trim().to_string());
+        }
+
+        map
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DoubanMusicResult {
+    code: u32,
+    msg: String,
+    musics: Vec<DoubanMusic>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DoubanMusic {
+    id: String,                 //id
+    title: String,              //专辑名
+    alt_title: String,          //又名
+    performers: Vec<String>,    //表演者
+    genre: String,              //流派
+    album_type: String,         //专辑类型
+    media: String,              //介质
+    pubdate: String,            //发行时间
+    pubdate_normalized: String, //ISO 8601格式的发行时间，无法解析时同原文
+    publisher: String,          //出版者
+    discs: String,              //唱片数
+    barcode: String,            //条形码
+    images: Image,              //封面
+    rating: Rating,             //评分
+    tracks: Vec<String>,        //曲目
+    summary: String,            //简介
+    tags: Vec<Tag>,             //标签
+}
@@ -0,0 +1,52 @@
+use anyhow::Result;
+use std::collections::HashMap;
+
+/// 缩略图边长，主色调不需要原图精度
+const THUMBNAIL_SIZE: u32 = 64;
+/// 两个颜色的最小距离(RGB欧氏距离)，过近的辅色会被跳过
+const MIN_DISTANCE: i32 = 48;
+
+/// 提取图片主色调，第一个为主色，其后为按占比排序的辅色，格式为#rrggbb
+pub fn extract(data: &[u8], size: usize) -> Result<Vec<String>> {
+    let img = image::load_from_memory(data)?
+        .thumbnail(THUMBNAIL_SIZE, THUMBNAIL_SIZE)
+        .to_rgb8();
+
+    // 每个通道量化为16级后统计，颜色取桶内的平均值
+    let mut buckets: HashMap<(u8, u8, u8), (u32, [u32; 3])> = HashMap::new();
+    for pixel in img.pixels() {
+        let [r, g, b] = pixel.0;
+        let bucket = buckets.entry((r >> 4, g >> 4, b >> 4)).or_insert((0, [0; 3]));
+        bucket.0 += 1;
+        bucket.1[0] += r as u32;
+        bucket.1[1] += g as u32;
+        bucket.1[2] += b as u32;
+    }
+    let mut list = buckets
+        .into_values()
+        .map(|(count, sum)| {
+            let color = [sum[0] / count, sum[1] / count, sum[2] / count].map(|x| x as i32);
+            (count, color)
+        })
+        .collect::<Vec<(u32, [i32; 3])>>();
+    list.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(&b.1)));
+
+    let mut colors: Vec<[i32; 3]> = Vec::with_capacity(size);
+    for (_, color) in list.into_iter() {
+        if colors.len() >= size {
+            break;
+        }
+        let distinct = colors.iter().all(|x| {
+            let d = (0..3).map(|i| (x[i] - color[i]).pow(2)).sum::<i32>();
+            d >= MIN_DISTANCE * MIN_DISTANCE
+        });
+        if distinct {
+            colors.push(color);
+        }
+    }
+
+    Ok(colors
+        .iter()
+        .map(|x| format!("#{:02x}{:02x}{:02x}", x[0], x[1], x[2]))
+        .collect())
+}
@@ -0,0 +1,160 @@
+use actix_web::body::{to_bytes, BoxBody, MessageBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::Error;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::HashSet;
+
+/// 解析字段黑名单配置，逗号分隔
+pub fn parse_fields(text: &str) -> HashSet<String> {
+    text.split(',')
+        .map(|x| x.trim().to_string())
+        .filter(|x| !x.is_empty())
+        .collect()
+}
+
+/// 管理接口导出的缓存需要完整数据，不做剔除
+pub fn is_exempt(req: &ServiceRequest) -> bool {
+    req.path().starts_with("/admin/")
+}
+
+/// 剔除json响应中任意层级的黑名单字段，非json响应原样返回
+pub async fn strip<B>(
+    res: ServiceResponse<B>,
+    fields: &HashSet<String>,
+) -> Result<ServiceResponse, Error>
+where
+    B: MessageBody + 'static,
+{
+    if !res.status().is_success() {
+        return Ok(res.map_into_boxed_body());
+    }
+
+    let (req, res) = res.into_parts();
+    let (res, body) = res.into_parts();
+    let bytes = to_bytes(body)
+        .await
+        .map_err(|e| actix_web::error::ErrorInternalServerError(e.into()))?;
+    let body = if bytes.starts_with(b"{") || bytes.starts_with(b"[") {
+        match serde_json::from_slice::<Value>(&bytes) {
+            Ok(mut value) => {
+                remove_fields(&mut value, fields);
+                BoxBody::new(value.to_string())
+            }
+            Err(_) => BoxBody::new(bytes),
+        }
+    } else {
+        BoxBody::new(bytes)
+    };
+    let res = res.set_body(body);
+    Ok(ServiceResponse::new(req, res))
+}
+
+/// 把数据中任意层级的黑名单字段清空，用于nfo/opf/卡片等非json输出在渲染前剔除字段，
+/// 字段置为同类型的空值以保持结构可反序列化
+pub fn apply<T: Serialize + DeserializeOwned>(data: &mut T, fields: &HashSet<String>) {
+    if fields.is_empty() {
+        return;
+    }
+    let mut value = match serde_json::to_value(&*data) {
+        Ok(value) => value,
+        Err(_) => return,
+    };
+    clear_fields(&mut value, fields);
+    match serde_json::from_value(value) {
+        Ok(cleared) => *data = cleared,
+        Err(err) => println!("剔除黑名单字段失败: {:?}", err),
+    }
+}
+
+/// 只保留对象中的指定字段(白名单)，非对象不处理
+pub fn select_fields(value: &mut Value, fields: &HashSet<String>) {
+    if let Value::Object(map) = value {
+        map.retain(|k, _| fields.contains(k));
+    }
+}
+
+fn remove_fields(value: &mut Value, fields: &HashSet<String>) {
+    match value {
+        Value::Object(map) => {
+            map.retain(|k, _| !fields.contains(k));
+            for v in map.values_mut() {
+                remove_fields(v, fields);
+            }
+        }
+        Value::Array(list) => {
+            for v in list.iter_mut() {
+                remove_fields(v, fields);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn clear_fields(value: &mut Value, fields: &HashSet<String>) {
+    match value {
+        Value::Object(map) => {
+            for (k, v) in map.iter_mut() {
+                if fields.contains(k) {
+                    empty(v);
+                } else {
+                    clear_fields(v, fields);
+                }
+            }
+        }
+        Value::Array(list) => {
+            for v in list.iter_mut() {
+                clear_fields(v, fields);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// 置为同类型的空值，对象逐个字段置空
+fn empty(value: &mut Value) {
+    match value {
+        Value::String(x) => x.clear(),
+        Value::Array(x) => x.clear(),
+        Value::Number(_) => *value = Value::from(0),
+        Value::Bool(x) => *x = false,
+        Value::Object(map) => map.values_mut().for_each(empty),
+        Value::Null => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    #[derive(Serialize, Deserialize)]
+    struct Item {
+        name: String,
+        intro: String,
+        tags: Vec<String>,
+        children: Vec<Item>,
+    }
+
+    #[test]
+    fn apply_clears_nested_fields() {
+        let mut item = Item {
+            name: "a".to_string(),
+            intro: "intro".to_string(),
+            tags: vec!["x".to_string()],
+            children: vec![Item {
+                name: "b".to_string(),
+                intro: "child intro".to_string(),
+                tags: Vec::new(),
+                children: Vec::new(),
+            }],
+        };
+        apply(&mut item, &parse_fields("intro, tags"));
+        assert_eq!(item.name, "a");
+        assert!(item.intro.is_empty());
+        assert!(item.tags.is_empty());
+        assert_eq!(item.children[0].name, "b");
+        assert!(item.children[0].intro.is_empty());
+    }
+}
@@ -0,0 +1,151 @@
+use crate::api::parse_chinese_number;
+use lazy_static::*;
+use regex::Regex;
+
+lazy_static! {
+    // S01E01、s1.e02
+    static ref RE_SEASON_EPISODE: Regex =
+        Regex::new(r"(?i)s(\d{1,2})[ ._-]*e(\d{1,4})").unwrap();
+    // 第1集、第01话
+    static ref RE_EPISODE_CN: Regex = Regex::new(r"第\s*(\d{1,4})\s*[集话話]").unwrap();
+    // EP01、E01、[01]
+    static ref RE_EPISODE: Regex =
+        Regex::new(r"(?i)(?:^|[ ._\-\[])(?:ep?)?(\d{1,4})(?:[ ._\-\]]|$)").unwrap();
+    // Season 2、第二季
+    static ref RE_SEASON: Regex =
+        Regex::new(r"(?i)season[ ._]?(\d{1,2})|第\s*([\d一二两三四五六七八九十]+)\s*季").unwrap();
+    static ref RE_BRACKET: Regex = Regex::new(r"\[[^\]]*\]|【[^】]*】").unwrap();
+    static ref RE_YEAR: Regex = Regex::new(r"[(（]?((?:19|20)\d{2})[)）]?\s*$").unwrap();
+}
+
+const VIDEO_EXTENSIONS: [&str; 10] = [
+    "mkv", "mp4", "avi", "ts", "rmvb", "wmv", "mov", "flv", "m2ts", "iso",
+];
+
+/// 从剧集文件名识别出的剧名、季号、集号
+#[derive(Debug, Clone)]
+pub struct EpisodeFile {
+    pub file: String,
+    pub title: String,
+    pub year: String,
+    pub season: u32,
+    pub episode: u32,
+}
+
+/// 依次查找独立的数字，相邻数字共用中间的分隔符，如2005.E05中的年份与集号
+fn episode_numbers(name: &str) -> Vec<regex::Captures<'_>> {
+    let mut list = Vec::new();
+    let mut start = 0;
+    while let Some(cs) = RE_EPISODE.captures_at(name, start) {
+        start = cs.get(1).unwrap().end();
+        list.push(cs);
+    }
+
+    list
+}
+
+/// 解析剧集文件名，无法识别集号时返回None，未标注季号的视为第1季
+pub fn parse_episode_file(file: &str) -> Option<EpisodeFile> {
+    // 只取文件名，并去掉视频扩展名
+    let name = file.rsplit(['/', '\\']).next().unwrap_or(file);
+    let name = match name.rsplit_once('.') {
+        Some((stem, ext)) if VIDEO_EXTENSIONS.contains(&ext.to_lowercase().as_str()) => stem,
+        _ => name,
+    };
+
+    let (mut title, mut season, episode) = if let Some(cs) = RE_SEASON_EPISODE.captures(name) {
+        let m = cs.get(0).unwrap();
+        (
+            name[..m.start()].to_string(),
+            cs[1].parse::<u32>().unwrap_or(0),
+            cs[2].parse::<u32>().unwrap_or(0),
+        )
+    } else if let Some(cs) = RE_EPISODE_CN.captures(name) {
+        let m = cs.get(0).unwrap();
+        (
+            name[..m.start()].to_string(),
+            0,
+            cs[1].parse::<u32>().unwrap_or(0),
+        )
+    } else {
+        // 没有明确标记时，取最后一个独立的数字作为集号，跳过年份
+        let cs = episode_numbers(name)
+            .into_iter()
+            .filter(|x| x[1].len() < 4 || x[1].parse::<u32>().unwrap_or(0) < 1900)
+            .last()?;
+        (
+            name[..cs.get(0).unwrap().start()].to_string(),
+            0,
+            cs[1].parse::<u32>().unwrap_or(0),
+        )
+    };
+    if episode == 0 {
+        return None;
+    }
+
+    if let Some(cs) = RE_SEASON.captures(&title) {
+        if season == 0 {
+            season = match (cs.get(1), cs.get(2)) {
+                (Some(x), _) => x.as_str().parse::<u32>().unwrap_or(0),
+                (_, Some(x)) => parse_chinese_number(x.as_str()),
+                _ => 0,
+            };
+        }
+        title = title[..cs.get(0).unwrap().start()].to_string();
+    }
+    let title = RE_BRACKET
+        .replace_all(&title, " ")
+        .replace(['.', '_'], " ")
+        .trim_matches(|c: char| c.is_whitespace() || c == '-')
+        .to_string();
+    let (title, year) = match RE_YEAR.captures(&title) {
+        Some(cs) => (
+            title[..cs.get(0).unwrap().start()].trim().to_string(),
+            cs[1].to_string(),
+        ),
+        None => (title.clone(), String::new()),
+    };
+
+    Some(EpisodeFile {
+        file: file.to_string(),
+        title,
+        year,
+        season: season.max(1),
+        episode,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_episode_file_table() {
+        // (文件名, 剧名, 年份, 季号, 集号)
+        let cases = [
+            ("Breaking.Bad.S01E01.720p.BluRay.mkv", "Breaking Bad", "", 1, 1),
+            ("Friends (1994) S02E03.mkv", "Friends", "1994", 2, 3),
+            ("/tv/庆余年/庆余年 第二季 第01集.mp4", "庆余年", "", 2, 1),
+            ("长安十二时辰 第12集.mp4", "长安十二时辰", "", 1, 12),
+            ("[SweetSub] Frieren [01][1080p].mkv", "Frieren", "", 1, 1),
+            ("The.Office.2005.E05.mkv", "The Office", "2005", 1, 5),
+            ("Show Season 3 - 07 [1080p].mkv", "Show", "", 3, 7),
+            ("C:\\tv\\Dark.s2.e10.2160p.mkv", "Dark", "", 2, 10),
+            ("Show_2019_07_720p.mkv", "Show", "2019", 1, 7),
+        ];
+        for (file, title, year, season, episode) in cases {
+            let x = parse_episode_file(file).unwrap_or_else(|| panic!("{}: 未识别", file));
+            assert_eq!(x.title, title, "{}", file);
+            assert_eq!(x.year, year, "{}", file);
+            assert_eq!(x.season, season, "{}", file);
+            assert_eq!(x.episode, episode, "{}", file);
+        }
+    }
+
+    #[test]
+    fn parse_episode_file_unrecognized() {
+        for file in ["Movie.2160p.mkv", "Movie.2019.mkv", "Show.S01E00.mkv", "readme"] {
+            assert!(parse_episode_file(file).is_none(), "{}", file);
+        }
+    }
+}
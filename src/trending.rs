@@ -0,0 +1,207 @@
+use crate::util::now_secs;
+use lazy_static::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::RwLock;
+
+lazy_static! {
+    // 条目想看/在看人数的快照，key 为 sid
+    static ref TRENDS: RwLock<HashMap<String, Trend>> = RwLock::new(HashMap::new());
+}
+
+// 最多跟踪的条目数
+const TRACK_SIZE: usize = 500;
+// 每个条目保留的快照数
+const SNAPSHOT_LIMIT: usize = 100;
+// 间隔小于该值(秒)的抓取只更新最近一次快照
+const SNAPSHOT_GAP: u64 = 10 * 60;
+// 超过该时长(秒)未再抓取的条目视为过期，跟踪已满时优先淘汰
+const STALE_SECS: u64 = 7 * 24 * 3600;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Trend {
+    name: String,
+    snapshots: Vec<Interest>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Interest {
+    taken_at: u64,
+    wish: u32,  //想看人数
+    doing: u32, //在看人数
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Trending {
+    sid: String,
+    name: String,
+    wish: u32,
+    doing: u32,
+    wish_delta: i64,  //统计时段内想看人数的增量
+    doing_delta: i64, //统计时段内在看人数的增量
+    hours: f64,       //实际统计时段(小时)
+    growth: f64,      //每小时新增想看+在看人数
+}
+
+/// 记录一次详情抓取到的想看/在看人数，没有人数的条目不跟踪
+pub fn record(sid: &str, name: &str, wish: u32, doing: u32) {
+    if wish == 0 && doing == 0 {
+        return;
+    }
+    let now = now_secs();
+    let mut trends = TRENDS.write().unwrap();
+    if !trends.contains_key(sid) && trends.len() >= TRACK_SIZE {
+        evict(&mut trends, now);
+    }
+    let trend = trends.entry(sid.to_string()).or_insert_with(|| Trend {
+        name: name.to_string(),
+        snapshots: Vec::new(),
+    });
+    trend.name = name.to_string();
+    let interest = Interest {
+        taken_at: now,
+        wish,
+        doing,
+    };
+    match trend.snapshots.last_mut() {
+        Some(last) if now < last.taken_at + SNAPSHOT_GAP => *last = interest,
+        _ => trend.snapshots.push(interest),
+    }
+    if trend.snapshots.len() > SNAPSHOT_LIMIT {
+        trend.snapshots.remove(0);
+    }
+}
+
+/// 跟踪已满时淘汰一个条目：优先淘汰过期条目，其次淘汰增速最慢、最久未更新的条目
+fn evict(trends: &mut HashMap<String, Trend>, now: u64) {
+    let victim = trends
+        .iter()
+        .map(|(sid, trend)| {
+            let updated = trend.snapshots.last().map(|x| x.taken_at).unwrap_or(0);
+            let stale = now.saturating_sub(updated) > STALE_SECS;
+            (!stale, trend.growth(), updated, sid)
+        })
+        .min_by(|a, b| {
+            a.0.cmp(&b.0)
+                .then(a.1.total_cmp(&b.1))
+                .then(a.2.cmp(&b.2))
+        })
+        .map(|(_, _, _, sid)| sid.clone());
+    if let Some(sid) = victim {
+        trends.remove(&sid);
+    }
+}
+
+impl Trend {
+    /// 全部快照期间每小时新增想看+在看人数，少于两次快照时为0
+    fn growth(&self) -> f64 {
+        match (self.snapshots.first(), self.snapshots.last()) {
+            (Some(first), Some(last)) if last.taken_at > first.taken_at => {
+                let delta = last.wish as f64 + last.doing as f64
+                    - first.wish as f64
+                    - first.doing as f64;
+                delta * 3600.0 / (last.taken_at - first.taken_at) as f64
+            }
+            _ => 0.0,
+        }
+    }
+}
+
+/// 正在跟踪的条目
+pub fn sids() -> Vec<String> {
+    TRENDS.read().unwrap().keys().cloned().collect()
+}
+
+/// 按最近window秒内想看+在看人数的增速排序，时段内少于两次快照的条目不参与排序
+pub fn top(window: u64, count: usize) -> Vec<Trending> {
+    let since = now_secs().saturating_sub(window);
+    let trends = TRENDS.read().unwrap();
+    let mut list = trends
+        .iter()
+        .filter_map(|(sid, trend)| {
+            let base = trend.snapshots.iter().find(|x| x.taken_at >= since)?;
+            let latest = trend.snapshots.last()?;
+            if latest.taken_at <= base.taken_at {
+                return None;
+            }
+            let hours = (latest.taken_at - base.taken_at) as f64 / 3600.0;
+            let wish_delta = latest.wish as i64 - base.wish as i64;
+            let doing_delta = latest.doing as i64 - base.doing as i64;
+            Some(Trending {
+                sid: sid.clone(),
+                name: trend.name.clone(),
+                wish: latest.wish,
+                doing: latest.doing,
+                wish_delta,
+                doing_delta,
+                hours,
+                growth: (wish_delta + doing_delta) as f64 / hours,
+            })
+        })
+        .filter(|x| x.growth > 0.0)
+        .collect::<Vec<Trending>>();
+    list.sort_by(|a, b| b.growth.partial_cmp(&a.growth).unwrap());
+    list.truncate(count);
+
+    list
+}
+
+/// 写入快照文件
+pub async fn save(file: &str) {
+    if file.is_empty() {
+        return;
+    }
+    let data = serde_json::to_vec(&*TRENDS.read().unwrap()).unwrap();
+    if let Err(err) = tokio::fs::write(file, data).await {
+        println!("写入热度快照失败: {:?}", err);
+    }
+}
+
+/// 加载快照文件，文件不存在时忽略
+pub async fn load(file: &str) {
+    if file.is_empty() {
+        return;
+    }
+    let data = match tokio::fs::read(file).await {
+        Ok(data) => data,
+        Err(_) => return,
+    };
+    match serde_json::from_slice::<HashMap<String, Trend>>(&data) {
+        Ok(trends) => *TRENDS.write().unwrap() = trends,
+        Err(err) => println!("解析热度快照失败: {:?}", err),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn trend(points: &[(u64, u32)]) -> Trend {
+        Trend {
+            name: String::new(),
+            snapshots: points
+                .iter()
+                .map(|&(taken_at, wish)| Interest {
+                    taken_at,
+                    wish,
+                    doing: 0,
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn evict_prefers_stale_then_slowest() {
+        let now = 30 * 24 * 3600;
+        let mut trends = HashMap::new();
+        trends.insert("fast".to_string(), trend(&[(now - 7200, 100), (now, 500)]));
+        trends.insert("slow".to_string(), trend(&[(now - 7200, 100), (now, 110)]));
+        trends.insert("stale".to_string(), trend(&[(1000, 100), (8200, 900)]));
+
+        evict(&mut trends, now);
+        assert!(!trends.contains_key("stale"));
+        evict(&mut trends, now);
+        assert!(!trends.contains_key("slow"));
+        assert!(trends.contains_key("fast"));
+    }
+}
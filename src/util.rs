@@ -0,0 +1,258 @@
+use lazy_static::*;
+use regex::Regex;
+use serde::Serialize;
+use std::net::IpAddr;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::time::Duration;
+
+lazy_static! {
+    // 2019-7-1、2019年7月1日、2019.07、2019/7
+    static ref RE_DATE: Regex =
+        Regex::new(r"^(\d{4})(?:\s*[-./年]\s*(\d{1,2}))?(?:\s*[-./月]\s*(\d{1,2}))?").unwrap();
+    // 换行/分段标签
+    static ref RE_BREAK: Regex = Regex::new(r"(?i)<br\s*/?>|</p>|</div>").unwrap();
+    static ref RE_TAG: Regex = Regex::new(r"<[^>]*>").unwrap();
+    static ref RE_SPACE: Regex = Regex::new(r"[ \t\r\u{3000}\u{a0}]+").unwrap();
+}
+
+// 输出时间使用的时区偏移(秒)，默认UTC
+static TIME_OFFSET: AtomicI64 = AtomicI64::new(0);
+
+/// 转义xml特殊字符
+pub fn xml_escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+/// 当前的东八区日期 (年, 月, 日)
+pub fn today() -> (i64, u32, u32) {
+    let secs = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|x| x.as_secs() as i64)
+        .unwrap_or(0);
+    civil_from_days((secs + 8 * 3600).div_euclid(86400))
+}
+
+/// 1970-01-01 起的天数转换为公历日期
+pub fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    (year, month, day)
+}
+
+/// 当前unix时间戳(秒)
+pub fn now_secs() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|x| x.as_secs())
+        .unwrap_or(0)
+}
+
+/// 解析时区配置，支持 UTC 与 +08:00、-05:30、+8 格式，返回偏移秒数
+pub fn parse_time_offset(text: &str) -> Option<i64> {
+    let text = text.trim();
+    if text.is_empty() || text.eq_ignore_ascii_case("utc") || text == "Z" {
+        return Some(0);
+    }
+    let sign = match text.chars().next() {
+        Some('+') => 1,
+        Some('-') => -1,
+        _ => return None,
+    };
+    let mut parts = text[1..].splitn(2, ':');
+    let hours = parts.next()?.parse::<i64>().ok()?;
+    let minutes = match parts.next() {
+        Some(x) => x.parse::<i64>().ok()?,
+        None => 0,
+    };
+    if hours > 14 || minutes >= 60 {
+        return None;
+    }
+
+    Some(sign * (hours * 3600 + minutes * 60))
+}
+
+pub fn set_time_offset(offset: i64) {
+    TIME_OFFSET.store(offset, Ordering::Relaxed);
+}
+
+/// 配置的时区偏移(秒)
+pub fn time_offset() -> i64 {
+    TIME_OFFSET.load(Ordering::Relaxed)
+}
+
+/// unix时间戳转换为ISO 8601格式的时间，按配置的时区输出
+pub fn iso_time(secs: u64) -> String {
+    let offset = TIME_OFFSET.load(Ordering::Relaxed);
+    let local = secs as i64 + offset;
+    let (year, month, day) = civil_from_days(local.div_euclid(86400));
+    let rest = local.rem_euclid(86400);
+    let zone = if offset == 0 {
+        "Z".to_string()
+    } else {
+        format!(
+            "{}{:02}:{:02}",
+            if offset > 0 { '+' } else { '-' },
+            offset.abs() / 3600,
+            offset.abs() % 3600 / 60
+        )
+    };
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}{}",
+        year,
+        month,
+        day,
+        rest / 3600,
+        rest % 3600 / 60,
+        rest % 60,
+        zone
+    )
+}
+
+/// 按文件尾标记检查JPEG/PNG/GIF是否完整，其它格式不做判断
+pub fn is_complete_image(body: &[u8]) -> bool {
+    if body.starts_with(&[0xFF, 0xD8]) {
+        // 部分图片在结束标记后有填充的0
+        let end = body.iter().rposition(|&x| x != 0).map(|x| x + 1).unwrap_or(0);
+        return body[..end].ends_with(&[0xFF, 0xD9]);
+    }
+    if body.starts_with(b"\x89PNG\r\n\x1a\n") {
+        return body.ends_with(b"IEND\xaeB`\x82");
+    }
+    if body.starts_with(b"GIF8") {
+        return body.ends_with(&[0x3B]);
+    }
+
+    true
+}
+
+/// 是否为公网地址，回环、内网、链路本地、保留等地址均视为非公网
+pub fn is_public_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, ..] = ip.octets();
+            !(ip.is_unspecified()
+                || ip.is_loopback()
+                || ip.is_private()
+                || ip.is_link_local()
+                || ip.is_broadcast()
+                || ip.is_documentation()
+                || ip.is_multicast()
+                || a == 0
+                || a >= 240
+                || (a == 100 && (64..128).contains(&b)) // 100.64.0.0/10 运营商级NAT
+                || (a == 198 && (18..20).contains(&b))) // 198.18.0.0/15 基准测试
+        }
+        IpAddr::V6(ip) => {
+            if let Some(v4) = ip.to_ipv4_mapped() {
+                return is_public_ip(IpAddr::V4(v4));
+            }
+            let first = ip.segments()[0];
+            !(ip.is_unspecified()
+                || ip.is_loopback()
+                || ip.is_multicast()
+                || (first & 0xfe00) == 0xfc00 // fc00::/7 唯一本地地址
+                || (first & 0xffc0) == 0xfe80) // fe80::/10 链路本地地址
+        }
+    }
+}
+
+/// html片段转为纯文本：去掉标签、还原常见实体、合并空白，段落之间以换行分隔
+pub fn html_to_text(html: &str) -> String {
+    let text = RE_BREAK.replace_all(html, "\n");
+    let text = RE_TAG.replace_all(&text, "");
+    let text = text
+        .replace("&nbsp;", " ")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&amp;", "&");
+    text.lines()
+        .map(|line| RE_SPACE.replace_all(line, " ").trim().to_string())
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+/// 日期统一为ISO 8601格式(YYYY-MM-DD，缺少日/月时为YYYY-MM/YYYY)，无法解析时返回原文
+pub fn normalize_date(text: &str) -> String {
+    parse_date(text).unwrap_or_else(|| text.trim().to_string())
+}
+
+/// 同normalize_date，无法解析或日期不合法时返回None
+pub fn parse_date(text: &str) -> Option<String> {
+    let cs = RE_DATE.captures(text.trim())?;
+    let year = cs[1].parse::<i64>().unwrap_or(0);
+    let month = cs.get(2).map(|x| x.as_str().parse::<u32>().unwrap_or(0));
+    let day = cs.get(3).map(|x| x.as_str().parse::<u32>().unwrap_or(0));
+    match (month, day) {
+        (Some(m), Some(d)) if (1..=12).contains(&m) && d >= 1 && d <= days_in_month(year, m) => {
+            Some(format!("{:04}-{:02}-{:02}", year, m, d))
+        }
+        (Some(m), None) if (1..=12).contains(&m) => Some(format!("{:04}-{:02}", year, m)),
+        (None, None) => Some(format!("{:04}", year)),
+        _ => None,
+    }
+}
+
+pub fn days_in_month(year: i64, month: u32) -> u32 {
+    match month {
+        2 if (year % 4 == 0 && year % 100 != 0) || year % 400 == 0 => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// 详情数据的缓存状态，用于输出X-Cache等响应头
+pub struct CacheMeta {
+    pub hit: bool,
+    pub fetched_at: u64,
+    pub ttl_remaining: u64,
+}
+
+impl CacheMeta {
+    pub fn new(hit: bool, fetched_at: Option<u64>, ttl: Duration) -> CacheMeta {
+        let now = now_secs();
+        let fetched_at = fetched_at.unwrap_or(now);
+        CacheMeta {
+            hit,
+            fetched_at,
+            ttl_remaining: (fetched_at + ttl.as_secs()).saturating_sub(now),
+        }
+    }
+}
+
+/// 统一的列表分页结构，total未知时为null
+#[derive(Debug, Clone, Serialize)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    pub start: usize,
+    pub count: usize,
+    pub total: Option<usize>,
+    pub has_more: bool,
+}
+
+impl<T> Page<T> {
+    pub fn new(items: Vec<T>, start: usize, total: Option<usize>, has_more: bool) -> Page<T> {
+        Page {
+            count: items.len(),
+            items,
+            start,
+            total,
+            has_more,
+        }
+    }
+}